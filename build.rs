@@ -16,6 +16,7 @@ fn main() {
         let mut config = gcc::Config::new();
         config.file("src/util_helpers.asm");
         config.file("src/aesni_helpers.asm");
+        config.file("src/sha2_helpers.asm");
         if target.contains("x86_64") {
             config.define("X64", None);
         }
@@ -25,6 +26,7 @@ fn main() {
         let mut cfg = gcc::Config::new();
         cfg.file("src/util_helpers.c");
         cfg.file("src/aesni_helpers.c");
+        cfg.file("src/sha2_helpers.c");
         if env::var_os("CC").is_none() {
             if host.contains("openbsd") {
                 // Use clang on openbsd since there have been reports that
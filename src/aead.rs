@@ -4,6 +4,12 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use aes::KeySize;
+use aes_gcm::AesGcm;
+use ascon::Ascon128;
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use symmetriccipher::SymmetricCipherError;
+
 pub trait AeadEncryptor {
 
 	fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]);
@@ -11,5 +17,196 @@ pub trait AeadEncryptor {
 
 pub trait AeadDecryptor {
 
-	fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8]) -> bool;
-}
\ No newline at end of file
+	/// Verify the tag and decrypt input into output. If the tag doesn't match,
+	/// SymmetricCipherError::AuthenticationFailed is returned and nothing is written to output.
+	/// Callers that report this error must take care not to reveal how much of the tag matched,
+	/// for example through the timing of their own error handling.
+	fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+		-> Result<(), SymmetricCipherError>;
+}
+
+/// An AEAD that can be used for either encryption or decryption, so that algorithms can be used
+/// interchangeably as trait objects. It is implemented for every type that implements both
+/// AeadEncryptor and AeadDecryptor.
+pub trait Aead: AeadEncryptor + AeadDecryptor {}
+
+impl <T: AeadEncryptor + AeadDecryptor> Aead for T {}
+
+/// Construct the AEAD with the given name, keyed with key and bound to nonce and aad. Returns None
+/// if the name is not recognized or if the key or nonce is not a valid length for the algorithm.
+///
+/// The recognized names and their key and nonce lengths in bytes are:
+///
+/// * "aes-128-gcm", "aes-192-gcm" and "aes-256-gcm": 16, 24 and 32 byte keys, 12 byte nonce
+/// * "ascon-128": 16 byte key, 16 byte nonce
+/// * "chacha20-poly1305-legacy": 32 byte key, 8 byte nonce. This is the original construction
+///   with a 64-bit nonce, which does not interoperate with the RFC 8439 construction with a
+///   96-bit nonce. The standard name "chacha20-poly1305" refers to the RFC 8439 construction,
+///   which is not implemented here, so that name is not recognized.
+/// * "xchacha20-poly1305": 32 byte key, 24 byte nonce
+pub fn new_by_name(name: &str, key: &[u8], nonce: &[u8], aad: &[u8]) -> Option<Box<dyn Aead>> {
+    let (key_len, nonce_len) = match name {
+        "aes-128-gcm" => (16, 12),
+        "aes-192-gcm" => (24, 12),
+        "aes-256-gcm" => (32, 12),
+        "ascon-128" => (16, 16),
+        "chacha20-poly1305-legacy" => (32, 8),
+        "xchacha20-poly1305" => (32, 24),
+        _ => return None
+    };
+    if key.len() != key_len || nonce.len() != nonce_len {
+        return None;
+    }
+
+    Some(match name {
+        "aes-128-gcm" => Box::new(AesGcm::new(KeySize::KeySize128, key, nonce, aad)),
+        "aes-192-gcm" => Box::new(AesGcm::new(KeySize::KeySize192, key, nonce, aad)),
+        "aes-256-gcm" => Box::new(AesGcm::new(KeySize::KeySize256, key, nonce, aad)),
+        "ascon-128" => Box::new(Ascon128::new(key, nonce, aad)),
+        "chacha20-poly1305-legacy" => Box::new(ChaCha20Poly1305::new(key, nonce, aad)),
+        _ => Box::new(XChaCha20Poly1305::new(key, nonce, aad))
+    })
+}
+
+/// A NonceSequence produces a distinct 12 byte nonce for every message encrypted under a key by
+/// treating the nonce as a 96-bit big-endian counter.
+///
+/// Reusing a nonce with the same key is catastrophic for AEAD modes such as GCM, so rather than
+/// wrapping around, next_nonce() returns an error once all 2^96 nonces have been used.
+pub struct NonceSequence {
+    counter: [u8; 12],
+    exhausted: bool
+}
+
+impl NonceSequence {
+    /// Create a new NonceSequence starting at zero.
+    pub fn new() -> NonceSequence {
+        NonceSequence::new_with_counter(&[0u8; 12])
+    }
+
+    /// Create a new NonceSequence whose first nonce is counter.
+    pub fn new_with_counter(counter: &[u8; 12]) -> NonceSequence {
+        NonceSequence {
+            counter: *counter,
+            exhausted: false
+        }
+    }
+
+    /// Get the next nonce in the sequence, or an error if the counter has been exhausted.
+    pub fn next_nonce(&mut self) -> Result<[u8; 12], &'static str> {
+        if self.exhausted {
+            return Err("Nonce sequence exhausted.");
+        }
+        let nonce = self.counter;
+        self.exhausted = true;
+        for b in self.counter.iter_mut().rev() {
+            *b = b.wrapping_add(1);
+            if *b != 0 {
+                self.exhausted = false;
+                break;
+            }
+        }
+        Ok(nonce)
+    }
+}
+
+impl Default for NonceSequence {
+    fn default() -> NonceSequence {
+        NonceSequence::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aead::{new_by_name, AeadEncryptor, NonceSequence};
+    use aes::KeySize;
+    use aes_gcm::AesGcm;
+    use ascon::Ascon128;
+    use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+
+    // Check that the named AEAD produces the same output as the directly constructed one and that
+    // it can decrypt it again
+    fn check_by_name<A: AeadEncryptor>(name: &str, key: &[u8], nonce: &[u8], mut direct: A) {
+        let aad = b"additional data";
+        let plaintext = b"a message to be encrypted under the named algorithm";
+
+        let mut expected = [0u8; 51];
+        let mut expected_tag = [0u8; 16];
+        direct.encrypt(plaintext, &mut expected, &mut expected_tag);
+
+        let mut ciphertext = [0u8; 51];
+        let mut tag = [0u8; 16];
+        new_by_name(name, key, nonce, aad).unwrap().encrypt(plaintext, &mut ciphertext, &mut tag);
+        assert_eq!(&ciphertext[..], &expected[..]);
+        assert_eq!(tag, expected_tag);
+
+        let mut decrypted = [0u8; 51];
+        new_by_name(name, key, nonce, aad).unwrap().decrypt(&ciphertext, &mut decrypted, &tag).unwrap();
+        assert_eq!(&decrypted[..], &plaintext[..]);
+
+        // Lengths that are wrong for this algorithm
+        assert!(new_by_name(name, &key[1..], nonce, aad).is_none());
+        assert!(new_by_name(name, key, &nonce[1..], aad).is_none());
+    }
+
+    #[test]
+    fn test_new_by_name() {
+        let key = [7u8; 32];
+        let aad = b"additional data";
+        check_by_name("aes-128-gcm", &key[..16], &key[..12],
+            AesGcm::new(KeySize::KeySize128, &key[..16], &key[..12], aad));
+        check_by_name("aes-192-gcm", &key[..24], &key[..12],
+            AesGcm::new(KeySize::KeySize192, &key[..24], &key[..12], aad));
+        check_by_name("aes-256-gcm", &key, &key[..12],
+            AesGcm::new(KeySize::KeySize256, &key, &key[..12], aad));
+        check_by_name("ascon-128", &key[..16], &key[..16],
+            Ascon128::new(&key[..16], &key[..16], aad));
+        check_by_name("chacha20-poly1305-legacy", &key, &key[..8],
+            ChaCha20Poly1305::new(&key, &key[..8], aad));
+        check_by_name("xchacha20-poly1305", &key, &key[..24],
+            XChaCha20Poly1305::new(&key, &key[..24], aad));
+    }
+
+    #[test]
+    fn test_new_by_name_unknown() {
+        let key = [0u8; 32];
+        assert!(new_by_name("aes-512-gcm", &key, &key[..12], &[]).is_none());
+        assert!(new_by_name("AES-128-GCM", &key[..16], &key[..12], &[]).is_none());
+        assert!(new_by_name("", &key, &key[..12], &[]).is_none());
+        // The RFC 8439 name must not select the legacy construction, with either nonce length
+        assert!(new_by_name("chacha20-poly1305", &key, &key[..8], &[]).is_none());
+        assert!(new_by_name("chacha20-poly1305", &key, &key[..12], &[]).is_none());
+    }
+
+    #[test]
+    fn test_nonce_sequence_increasing() {
+        let mut seq = NonceSequence::new();
+        let mut prev = seq.next_nonce().unwrap();
+        assert_eq!(prev, [0u8; 12]);
+        for _ in 0..1000 {
+            let nonce = seq.next_nonce().unwrap();
+            assert!(nonce > prev);
+            prev = nonce;
+        }
+        assert_eq!(prev, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x03, 0xe8]);
+    }
+
+    #[test]
+    fn test_nonce_sequence_carry() {
+        let mut seq = NonceSequence::new_with_counter(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff]);
+        assert_eq!(seq.next_nonce().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff]);
+        assert_eq!(seq.next_nonce().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_nonce_sequence_exhausted() {
+        let mut start = [0xffu8; 12];
+        start[11] = 0xfe;
+        let mut seq = NonceSequence::new_with_counter(&start);
+        assert_eq!(seq.next_nonce().unwrap(), start);
+        assert_eq!(seq.next_nonce().unwrap(), [0xffu8; 12]);
+        assert!(seq.next_nonce().is_err());
+        // The sequence must never wrap around to reuse a nonce
+        assert!(seq.next_nonce().is_err());
+    }
+}
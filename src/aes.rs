@@ -10,7 +10,8 @@ use aesni;
 use aessafe;
 use blockmodes::{PaddingProcessor, EcbEncryptor, EcbDecryptor, CbcEncryptor, CbcDecryptor, CtrMode,
     CtrModeX8};
-use symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher};
+use cryptoutil::from_hex_ct;
+use symmetriccipher::{Encryptor, Decryptor, SymmetricCipherError, SynchronousStreamCipher};
 use util;
 
 /// AES key size
@@ -249,6 +250,145 @@ pub fn cbc_decryptor<X: PaddingProcessor + Send + 'static>(
     }
 }
 
+// The length in bytes of a key of the given size
+fn key_len(key_size: KeySize) -> usize {
+    match key_size {
+        KeySize::KeySize128 => 16,
+        KeySize::KeySize192 => 24,
+        KeySize::KeySize256 => 32
+    }
+}
+
+fn check_key(key_size: KeySize, key: &[u8]) -> Result<(), SymmetricCipherError> {
+    if key.len() != key_len(key_size) {
+        return Err(SymmetricCipherError::InvalidLength);
+    }
+    Ok(())
+}
+
+fn check_iv(iv: &[u8]) -> Result<(), SymmetricCipherError> {
+    if iv.len() != 16 {
+        return Err(SymmetricCipherError::InvalidLength);
+    }
+    Ok(())
+}
+
+/// Like ecb_encryptor, but returns InvalidLength if the length of the key doesn't match key_size
+/// instead of panicking.
+pub fn try_ecb_encryptor<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        key: &[u8],
+        padding: X) -> Result<Box<dyn Encryptor>, SymmetricCipherError> {
+    check_key(key_size, key)?;
+    Ok(ecb_encryptor(key_size, key, padding))
+}
+
+/// Like ecb_decryptor, but returns InvalidLength if the length of the key doesn't match key_size
+/// instead of panicking.
+pub fn try_ecb_decryptor<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        key: &[u8],
+        padding: X) -> Result<Box<dyn Decryptor>, SymmetricCipherError> {
+    check_key(key_size, key)?;
+    Ok(ecb_decryptor(key_size, key, padding))
+}
+
+/// Like cbc_encryptor, but returns InvalidLength if the length of the key doesn't match key_size
+/// or if the iv isn't 16 bytes instead of panicking.
+pub fn try_cbc_encryptor<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        key: &[u8],
+        iv: &[u8],
+        padding: X) -> Result<Box<dyn Encryptor>, SymmetricCipherError> {
+    check_key(key_size, key)?;
+    check_iv(iv)?;
+    Ok(cbc_encryptor(key_size, key, iv, padding))
+}
+
+/// Like cbc_decryptor, but returns InvalidLength if the length of the key doesn't match key_size
+/// or if the iv isn't 16 bytes instead of panicking.
+pub fn try_cbc_decryptor<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        key: &[u8],
+        iv: &[u8],
+        padding: X) -> Result<Box<dyn Decryptor>, SymmetricCipherError> {
+    check_key(key_size, key)?;
+    check_iv(iv)?;
+    Ok(cbc_decryptor(key_size, key, iv, padding))
+}
+
+/// Like ctr, but returns InvalidLength if the length of the key doesn't match key_size or if the
+/// iv isn't 16 bytes instead of panicking.
+pub fn try_ctr(
+        key_size: KeySize,
+        key: &[u8],
+        iv: &[u8]) -> Result<Box<dyn SynchronousStreamCipher>, SymmetricCipherError> {
+    check_key(key_size, key)?;
+    check_iv(iv)?;
+    Ok(ctr(key_size, key, iv))
+}
+
+// Decode a hex encoded key, checking that its length matches key_size. A key that isn't valid hex
+// can't be decoded to the required length either, so it is reported as InvalidLength too.
+fn decode_hex_key(key_size: KeySize, hex_key: &str) -> Result<Vec<u8>, SymmetricCipherError> {
+    let mut key = from_hex_ct(hex_key).map_err(|_| SymmetricCipherError::InvalidLength)?;
+    if let Err(e) = check_key(key_size, &key) {
+        util::secure_memset(&mut key, 0);
+        return Err(e);
+    }
+    Ok(key)
+}
+
+/// Get the best implementation of an EcbEncryptor for a hex encoded key. Returns InvalidLength if
+/// the key is not valid hex or if its length doesn't match key_size.
+pub fn ecb_encryptor_hex<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        hex_key: &str,
+        padding: X) -> Result<Box<dyn Encryptor>, SymmetricCipherError> {
+    let mut key = decode_hex_key(key_size, hex_key)?;
+    let enc = ecb_encryptor(key_size, &key, padding);
+    util::secure_memset(&mut key, 0);
+    Ok(enc)
+}
+
+/// Get the best implementation of an EcbDecryptor for a hex encoded key. Returns InvalidLength if
+/// the key is not valid hex or if its length doesn't match key_size.
+pub fn ecb_decryptor_hex<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        hex_key: &str,
+        padding: X) -> Result<Box<dyn Decryptor>, SymmetricCipherError> {
+    let mut key = decode_hex_key(key_size, hex_key)?;
+    let dec = ecb_decryptor(key_size, &key, padding);
+    util::secure_memset(&mut key, 0);
+    Ok(dec)
+}
+
+/// Get the best implementation of a CbcEncryptor for a hex encoded key. Returns InvalidLength if
+/// the key is not valid hex, if its length doesn't match key_size, or if the iv isn't 16 bytes.
+pub fn cbc_encryptor_hex<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        hex_key: &str,
+        iv: &[u8],
+        padding: X) -> Result<Box<dyn Encryptor + 'static>, SymmetricCipherError> {
+    let mut key = decode_hex_key(key_size, hex_key)?;
+    let enc = try_cbc_encryptor(key_size, &key, iv, padding);
+    util::secure_memset(&mut key, 0);
+    enc
+}
+
+/// Get the best implementation of a CbcDecryptor for a hex encoded key. Returns InvalidLength if
+/// the key is not valid hex, if its length doesn't match key_size, or if the iv isn't 16 bytes.
+pub fn cbc_decryptor_hex<X: PaddingProcessor + Send + 'static>(
+        key_size: KeySize,
+        hex_key: &str,
+        iv: &[u8],
+        padding: X) -> Result<Box<dyn Decryptor + 'static>, SymmetricCipherError> {
+    let mut key = decode_hex_key(key_size, hex_key)?;
+    let dec = try_cbc_decryptor(key_size, &key, iv, padding);
+    util::secure_memset(&mut key, 0);
+    dec
+}
+
 /// Get the best implementation of a Ctr
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 pub fn ctr(
@@ -314,10 +454,13 @@ mod test {
 
     use aessafe;
     use symmetriccipher::{BlockEncryptor, BlockDecryptor, BlockEncryptorX8, BlockDecryptorX8,
-            SynchronousStreamCipher};
+            BlockEncryptorX16, SynchronousStreamCipher};
+    use symmetriccipher::SymmetricCipherError::InvalidLength;
     use util;
     use aes;
     use aes::KeySize::{KeySize128, KeySize192, KeySize256};
+    use blockmodes::NoPadding;
+    use buffer::{RefReadBuffer, RefWriteBuffer};
 
     // Test vectors from:
     // http://www.inconteam.com/software-development/41-encryption/55-aes-test-vectors
@@ -541,6 +684,41 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_aessafe_round_keys() {
+        // The key expansion example from FIPS-197, Appendix A.1
+        let key: [u8; 16] = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6,
+            0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c ];
+        let expected: [u32; 44] = [
+            0x2b7e1516, 0x28aed2a6, 0xabf71588, 0x09cf4f3c,
+            0xa0fafe17, 0x88542cb1, 0x23a33939, 0x2a6c7605,
+            0xf2c295f2, 0x7a96b943, 0x5935807a, 0x7359f67f,
+            0x3d80477d, 0x4716fe3e, 0x1e237e44, 0x6d7a883b,
+            0xef44a541, 0xa8525b7f, 0xb671253b, 0xdb0bad00,
+            0xd4d1c6f8, 0x7c839d87, 0xcaf2b8bc, 0x11f915bc,
+            0x6d88a37a, 0x110b3efd, 0xdbf98641, 0xca0093fd,
+            0x4e54f70e, 0x5f5fc9f3, 0x84a64fb2, 0x4ea6dc4f,
+            0xead27321, 0xb58dbad2, 0x312bf560, 0x7f8d292f,
+            0xac7766f3, 0x19fadc21, 0x28d12941, 0x575c006e,
+            0xd014f9a8, 0xc9ee2589, 0xe13f0cc8, 0xb6630ca6 ];
+
+        let enc = aessafe::AesSafe128Encryptor::new(&key);
+        assert!(enc.round_keys()[..] == expected[..]);
+
+        // The first round keys are always the key itself
+        let key: Vec<u8> = (0..32).collect();
+        let round_keys = aessafe::AesSafe192Encryptor::new(&key[..24]).round_keys();
+        assert_eq!(round_keys.len(), 52);
+        assert_eq!(&round_keys[..6], &[0x00010203, 0x04050607, 0x08090a0b, 0x0c0d0e0f,
+            0x10111213, 0x14151617]);
+        let round_keys = aessafe::AesSafe256Encryptor::new(&key).round_keys();
+        assert_eq!(round_keys.len(), 60);
+        assert_eq!(round_keys[7], 0x1c1d1e1f);
+        // FIPS-197, Appendix C.3
+        assert_eq!(&round_keys[56..], &[0x24fc79cc, 0xbf0979e9, 0x371ac23c, 0x6d68de36]);
+    }
+
     // The following test vectors are all from NIST SP 800-38A
 
     #[test]
@@ -692,6 +870,47 @@ mod test {
         assert!(tmp[..] == plain[..]);
     }
 
+    // Encrypting 16 blocks at once must give the same result as encrypting each half of them with
+    // the X8 implementation.
+    fn run_x16_test<E8: BlockEncryptorX8, E16: BlockEncryptorX16>(enc_x8: &E8, enc_x16: &E16) {
+        let mut plain = [0u8; 256];
+        for (i, p) in plain.iter_mut().enumerate() {
+            *p = (i * 7 + 3) as u8;
+        }
+
+        let mut expected = [0u8; 256];
+        enc_x8.encrypt_block_x8(&plain[..128], &mut expected[..128]);
+        enc_x8.encrypt_block_x8(&plain[128..], &mut expected[128..]);
+
+        let mut tmp = [0u8; 256];
+        enc_x16.encrypt_block_x16(&plain, &mut tmp);
+        assert!(tmp[..] == expected[..]);
+    }
+
+    #[test]
+    fn test_aessafe_128_x16() {
+        let key = [0x2bu8; 16];
+        run_x16_test(
+            &aessafe::AesSafe128EncryptorX8::new(&key),
+            &aessafe::AesSafe128EncryptorX16::new(&key));
+    }
+
+    #[test]
+    fn test_aessafe_192_x16() {
+        let key = [0x8eu8; 24];
+        run_x16_test(
+            &aessafe::AesSafe192EncryptorX8::new(&key),
+            &aessafe::AesSafe192EncryptorX16::new(&key));
+    }
+
+    #[test]
+    fn test_aessafe_256_x16() {
+        let key = [0x60u8; 32];
+        run_x16_test(
+            &aessafe::AesSafe256EncryptorX8::new(&key),
+            &aessafe::AesSafe256EncryptorX16::new(&key));
+    }
+
     #[test]
     fn aes_ctr_box() {
         let tests = aes_ctr_tests();
@@ -703,6 +922,87 @@ mod test {
             assert!(res == &test.cipher[..]);
         }
     }
+
+    #[test]
+    fn aes_ecb_hex_key() {
+        let test = &tests128()[0];
+        let hex_key = "2b7e151628aed2a6abf7158809cf4f3c";
+        let data = &test.data[0];
+
+        let mut enc = aes::ecb_encryptor_hex(KeySize128, hex_key, NoPadding).unwrap();
+        let mut out = [0u8; 16];
+        enc.encrypt(&mut RefReadBuffer::new(&data.plain), &mut RefWriteBuffer::new(&mut out), true)
+            .unwrap();
+        assert!(out[..] == data.cipher[..]);
+
+        // Upper case digits are accepted too
+        let mut dec = aes::ecb_decryptor_hex(KeySize128, &hex_key.to_uppercase(), NoPadding)
+            .unwrap();
+        dec.decrypt(&mut RefReadBuffer::new(&data.cipher), &mut RefWriteBuffer::new(&mut out), true)
+            .unwrap();
+        assert!(out[..] == data.plain[..]);
+    }
+
+    #[test]
+    fn aes_hex_key_invalid() {
+        // Odd length
+        assert!(matches!(
+            aes::ecb_encryptor_hex(KeySize128, "2b7e151628aed2a6abf7158809cf4f3", NoPadding),
+            Err(InvalidLength)));
+        // Invalid digit
+        assert!(matches!(
+            aes::ecb_decryptor_hex(KeySize128, "2b7e151628aed2a6abf7158809cf4fxc", NoPadding),
+            Err(InvalidLength)));
+        // Valid hex, but the wrong length for the key size
+        assert!(matches!(aes::cbc_encryptor_hex(KeySize256,
+            "2b7e151628aed2a6abf7158809cf4f3c", &[0; 16], NoPadding), Err(InvalidLength)));
+        assert!(matches!(
+            aes::cbc_decryptor_hex(KeySize192, "", &[0; 16], NoPadding),
+            Err(InvalidLength)));
+        // A valid key, but the iv isn't a full block
+        assert!(matches!(aes::cbc_decryptor_hex(KeySize128,
+            "2b7e151628aed2a6abf7158809cf4f3c", &[0; 8], NoPadding), Err(InvalidLength)));
+        assert!(aes::cbc_decryptor_hex(KeySize128, "2b7e151628aed2a6abf7158809cf4f3c", &[0; 16],
+            NoPadding).is_ok());
+    }
+
+    #[test]
+    fn aes_try_key_lengths() {
+        let key = [0u8; 33];
+        let iv = [0u8; 16];
+        for &(key_size, len) in [(KeySize128, 16), (KeySize192, 24), (KeySize256, 32)].iter() {
+            assert!(aes::try_ecb_encryptor(key_size, &key[..len], NoPadding).is_ok());
+            assert!(aes::try_ecb_decryptor(key_size, &key[..len], NoPadding).is_ok());
+            assert!(aes::try_cbc_encryptor(key_size, &key[..len], &iv, NoPadding).is_ok());
+            assert!(aes::try_cbc_decryptor(key_size, &key[..len], &iv, NoPadding).is_ok());
+            assert!(aes::try_ctr(key_size, &key[..len], &iv).is_ok());
+
+            for &bad in [0, len - 1, len + 1].iter() {
+                assert!(matches!(
+                    aes::try_ecb_encryptor(key_size, &key[..bad], NoPadding),
+                    Err(InvalidLength)));
+                assert!(matches!(
+                    aes::try_ecb_decryptor(key_size, &key[..bad], NoPadding),
+                    Err(InvalidLength)));
+                assert!(matches!(
+                    aes::try_cbc_encryptor(key_size, &key[..bad], &iv, NoPadding),
+                    Err(InvalidLength)));
+                assert!(matches!(
+                    aes::try_cbc_decryptor(key_size, &key[..bad], &iv, NoPadding),
+                    Err(InvalidLength)));
+                assert!(matches!(aes::try_ctr(key_size, &key[..bad], &iv), Err(InvalidLength)));
+            }
+
+            // The key is fine, but the iv is not a full block
+            assert!(matches!(
+                aes::try_cbc_encryptor(key_size, &key[..len], &iv[..15], NoPadding),
+                Err(InvalidLength)));
+            assert!(matches!(
+                aes::try_cbc_decryptor(key_size, &key[..len], &iv[..8], NoPadding),
+                Err(InvalidLength)));
+            assert!(matches!(aes::try_ctr(key_size, &key[..len], &[]), Err(InvalidLength)));
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
@@ -713,7 +1013,7 @@ mod bench {
     use aesni;
 
     use aessafe;
-    use symmetriccipher::{BlockEncryptor, BlockEncryptorX8};
+    use symmetriccipher::{BlockEncryptor, BlockEncryptorX8, BlockEncryptorX16};
     use util;
     use aes::KeySize::{self, KeySize128, KeySize192, KeySize256};
 
@@ -784,4 +1084,20 @@ mod bench {
 
         bh.bytes = (plain.len()) as u64;
     }
+
+    #[bench]
+    pub fn aes_safe_x16_bench(bh: &mut Bencher) {
+        let key: [u8; 16] = [1u8; 16];
+        let plain: [u8; 256] = [2u8; 256];
+
+        let a = aessafe::AesSafe128EncryptorX16::new(&key);
+
+        let mut tmp = [0u8; 256];
+
+        bh.iter( || {
+            a.encrypt_block_x16(&plain, &mut tmp);
+        });
+
+        bh.bytes = (plain.len()) as u64;
+    }
 }
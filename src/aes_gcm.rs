@@ -6,9 +6,17 @@
 
 use aes::{ctr, KeySize};
 use aead::{AeadEncryptor,AeadDecryptor};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use aesni;
+use aessafe;
+use blockmodes::CtrModeX8;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use blockmodes::{CtrMode, CtrModeX16};
 use cryptoutil::copy_memory;
-use symmetriccipher::SynchronousStreamCipher;
+use symmetriccipher::{SymmetricCipherError, SynchronousStreamCipher};
 use ghash::{Ghash};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use util;
 use util::fixed_time_eq;
 
 pub struct AesGcm<'a> {
@@ -57,7 +65,7 @@ impl<'a> AeadEncryptor for AesGcm<'static> {
         assert!(input.len() == output.len());
         assert!(!self.finished);
         self.cipher.process(input, output);
-        let result = self.mac.input_c(output).result();
+        let result = self.mac.clone().input_c(output).result();
         self.finished = true;
         for i in 0..16 {
             tag[i] = result[i] ^ self.end_tag[i];
@@ -66,29 +74,108 @@ impl<'a> AeadEncryptor for AesGcm<'static> {
 }
 
 impl<'a> AeadDecryptor for AesGcm<'static> {
-    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])  -> bool {
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+            -> Result<(), SymmetricCipherError> {
         assert!(input.len() == output.len());
         assert!(!self.finished);
         self.finished = true;
-        let mut calc_tag = self.mac.input_c(input).result();
+        let mut calc_tag = self.mac.clone().input_c(input).result();
         for i in 0..16 {
             calc_tag[i] ^= self.end_tag[i];
         }
         if fixed_time_eq(&calc_tag, tag) {
             self.cipher.process(input, output);
-            true
+            Ok(())
         } else {
-            false
+            Err(SymmetricCipherError::AuthenticationFailed)
         }
     }
 }
 
+// Encrypt plaintext and compute the tag for a 96 bit nonce. new_ctr creates the CTR mode
+// implementation for a given initial counter block; because it is a concrete type, the whole buffer
+// is processed without going through a trait object.
+fn seal_with<C, F>(new_ctr: F, nonce: &[u8; 12], aad: &[u8], plaintext: &[u8])
+        -> (Vec<u8>, [u8; 16]) where C: SynchronousStreamCipher, F: Fn(&[u8]) -> C {
+    let zeros = [0u8; 16];
+    let mut hash_key = [0u8; 16];
+    new_ctr(&zeros).process(&zeros, &mut hash_key);
+
+    let mut iv = [0u8; 16];
+    copy_memory(nonce, &mut iv);
+    iv[15] = 1u8;
+    let mut cipher = new_ctr(&iv);
+    let mut end_tag = [0u8; 16];
+    cipher.process(&zeros, &mut end_tag);
+
+    let mut ciphertext = vec![0; plaintext.len()];
+    cipher.process(plaintext, &mut ciphertext);
+    let mut tag = Ghash::new(&hash_key).input_a(aad).input_c(&ciphertext).result();
+    for (t, &e) in tag.iter_mut().zip(end_tag.iter()) {
+        *t ^= e;
+    }
+    (ciphertext, tag)
+}
+
+/**
+ * Encrypt plaintext with AES-GCM in a single call, returning the ciphertext and the 16 byte tag.
+ * The size of the key, which must be 16, 24, or 32 bytes long, selects AES-128, AES-192, or
+ * AES-256.
+ *
+ * The output is identical to that of AesGcm, but the fastest AES implementation supported by the
+ * CPU is selected once up front, so the buffer is processed without any dynamic dispatch. This is
+ * the better choice when the whole message is available at once.
+ */
+pub fn seal(key: &[u8], nonce: &[u8; 12], aad: &[u8], plaintext: &[u8]) -> (Vec<u8>, [u8; 16]) {
+    let key_size = match key.len() {
+        16 => KeySize::KeySize128,
+        24 => KeySize::KeySize192,
+        32 => KeySize::KeySize256,
+        _ => panic!("AES-GCM requires a 16, 24, or 32 byte key")
+    };
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if util::supports_aesni() {
+            return seal_with(
+                |iv| CtrMode::new(aesni::AesNiEncryptor::new(key_size, key), iv.to_vec()),
+                nonce, aad, plaintext);
+        }
+        if util::supports_avx2() {
+            return match key_size {
+                KeySize::KeySize128 => seal_with(
+                    |iv| CtrModeX16::new(aessafe::AesSafe128EncryptorX16::new(key), iv),
+                    nonce, aad, plaintext),
+                KeySize::KeySize192 => seal_with(
+                    |iv| CtrModeX16::new(aessafe::AesSafe192EncryptorX16::new(key), iv),
+                    nonce, aad, plaintext),
+                KeySize::KeySize256 => seal_with(
+                    |iv| CtrModeX16::new(aessafe::AesSafe256EncryptorX16::new(key), iv),
+                    nonce, aad, plaintext)
+            };
+        }
+    }
+
+    match key_size {
+        KeySize::KeySize128 => seal_with(
+            |iv| CtrModeX8::new(aessafe::AesSafe128EncryptorX8::new(key), iv),
+            nonce, aad, plaintext),
+        KeySize::KeySize192 => seal_with(
+            |iv| CtrModeX8::new(aessafe::AesSafe192EncryptorX8::new(key), iv),
+            nonce, aad, plaintext),
+        KeySize::KeySize256 => seal_with(
+            |iv| CtrModeX8::new(aessafe::AesSafe256EncryptorX8::new(key), iv),
+            nonce, aad, plaintext)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use aes::KeySize;
-    use aes_gcm::AesGcm;
+    use aes_gcm::{seal, AesGcm};
     use aead::{AeadEncryptor, AeadDecryptor};
     use serialize::hex::FromHex;
+    use symmetriccipher::SymmetricCipherError;
     use std::iter::repeat;
     fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
         raw_hex.from_hex().ok().unwrap()
@@ -182,7 +269,7 @@ mod test {
                         
             let result = decipher.decrypt(&item.cipher_text[..], &mut out[..], &item.tag[..]);
             assert_eq!(out, item.plain_text);
-            assert!(result);
+            assert!(result.is_ok());
         }
     }
     #[test]
@@ -201,17 +288,51 @@ mod test {
             let out2: Vec<u8> = repeat(0).take(item.plain_text.len()).collect();
             let result = decipher.decrypt(&item.cipher_text[..], &mut out1[..], &tag[..]);
             assert_eq!(out1, out2);
-            assert!(!result);
+            match result {
+                Err(SymmetricCipherError::AuthenticationFailed) => {}
+                _ => panic!("Expected AuthenticationFailed")
+            }
         }
     }
 
+    #[test]
+    fn aes_gcm_seal_test() {
+        for item in get_test_vectors().iter() {
+            let mut nonce = [0u8; 12];
+            nonce.copy_from_slice(&item.iv);
+            let (out, out_tag) = seal(&item.key, &nonce, &item.aad, &item.plain_text);
+            assert_eq!(out, item.cipher_text);
+            assert_eq!(&out_tag[..], &item.tag[..]);
+        }
+    }
+
+    #[test]
+    fn aes_gcm_seal_matches_aead() {
+        let nonce = [7u8; 12];
+        let aad = [3u8; 20];
+        let plain_text: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        for &(key_size, key_len) in [(KeySize::KeySize128, 16), (KeySize::KeySize192, 24),
+                                     (KeySize::KeySize256, 32)].iter() {
+            let key: Vec<u8> = (0..key_len).map(|i| 0x40 + i as u8).collect();
+            for &len in [0, 1, 15, 16, 17, 127, 128, 129, 1000].iter() {
+                let mut cipher = AesGcm::new(key_size, &key, &nonce, &aad);
+                let mut out: Vec<u8> = repeat(0).take(len).collect();
+                let mut out_tag = [0u8; 16];
+                cipher.encrypt(&plain_text[..len], &mut out, &mut out_tag);
+
+                let (sealed, sealed_tag) = seal(&key, &nonce, &aad, &plain_text[..len]);
+                assert_eq!(sealed, out);
+                assert_eq!(sealed_tag, out_tag);
+            }
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
 mod bench {
     use test::Bencher;
     use aes::KeySize;
-    use aes_gcm::AesGcm;
+    use aes_gcm::{seal, AesGcm};
     use aead::{AeadEncryptor, AeadDecryptor};
 
     #[bench]
@@ -226,7 +347,7 @@ mod bench {
 	        let mut tag = [0u8; 16];
 	        let mut output2 = [0u8; 10];
             cipher.encrypt(&input, &mut output, &mut tag);
-            decipher.decrypt(&output, &mut output2, &tag);
+            decipher.decrypt(&output, &mut output2, &tag).unwrap();
             
         });
         bh.bytes = 10u64;
@@ -246,7 +367,7 @@ mod bench {
         let mut output2 = [0u8; 1024];
         
             cipher.encrypt(&input, &mut output, &mut tag);
-            decipher.decrypt(&output, &mut output2, &tag);
+            decipher.decrypt(&output, &mut output2, &tag).unwrap();
         });
     	bh.bytes = 1024u64;
         
@@ -265,10 +386,33 @@ mod bench {
         let mut output2 = [0u8; 65536];
       
             cipher.encrypt(&input, &mut output, &mut tag);
-            decipher.decrypt(&output, &mut output2, &tag);
+            decipher.decrypt(&output, &mut output2, &tag).unwrap();
 
         });
     	   bh.bytes = 65536u64;
         
     }
+
+    #[bench]
+    pub fn gsm_1m(bh: & mut Bencher) {
+        let input = vec![1u8; 1 << 20];
+        let aad = [3u8; 16];
+        let mut output = vec![0u8; 1 << 20];
+        bh.iter( || {
+            let mut cipher = AesGcm::new(KeySize::KeySize256, &[0; 32], &[0; 12], &aad);
+            let mut tag = [0u8; 16];
+            cipher.encrypt(&input, &mut output, &mut tag);
+        });
+        bh.bytes = 1u64 << 20;
+    }
+
+    #[bench]
+    pub fn gsm_seal_1m(bh: & mut Bencher) {
+        let input = vec![1u8; 1 << 20];
+        let aad = [3u8; 16];
+        bh.iter( || {
+            seal(&[0; 32], &[0; 12], &aad, &input)
+        });
+        bh.bytes = 1u64 << 20;
+    }
 }
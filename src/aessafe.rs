@@ -94,7 +94,8 @@ implementation stores these bits in a u32 which permits up to 8 * 32 = 1024 bits
 processed at once. This implementation only processes a single block at a time, so, in reality, only
 512 bits are processed at once and the remaining 512 bits of the variables are unused. The 2nd
 implementation uses u32x4s - vectors of 4 u32s. Thus, we can process 8 * 128 = 4096 bits at once,
-which corresponds exactly to 8 blocks.
+which corresponds exactly to 8 blocks. Finally, the encryption only X16 implementation operates on
+pairs of u32x4s, processing 16 blocks at once.
 
 The Bs8State struct implements the AesOps trait, which contains methods for each of the 4 main steps
 of the AES algorithm. The types, T, each implement the AesBitValueOps trait, which containts methods
@@ -129,10 +130,12 @@ use std::default::Default;
 use cryptoutil::{read_u32v_le, write_u32_le};
 use simd::u32x4;
 use step_by::RangeExt;
-use symmetriccipher::{BlockEncryptor, BlockEncryptorX8, BlockDecryptor, BlockDecryptorX8};
+use symmetriccipher::{BlockEncryptor, BlockEncryptorX8, BlockEncryptorX16, BlockDecryptor,
+    BlockDecryptorX8};
 
 const U32X4_0: u32x4 = u32x4(0, 0, 0, 0);
 const U32X4_1: u32x4 = u32x4(0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff);
+const U32X4X2_0: u32x4x2 = u32x4x2(U32X4_0, U32X4_0);
 
 macro_rules! define_aes_struct(
     (
@@ -201,11 +204,37 @@ macro_rules! define_aes_dec(
     )
 );
 
+macro_rules! define_aes_round_keys(
+    (
+        $name:ident,
+        $rounds:expr
+    ) => (
+        impl $name {
+            /// Get a copy of the expanded round keys. These are the 4 * (rounds + 1) words, w[i],
+            /// of the FIPS-197 key expansion in order, so the round key for round r is words
+            /// 4 * r to 4 * r + 3. Each word holds one column of the round key with its first byte
+            /// in the most significant position, matching the notation used in FIPS-197.
+            pub fn round_keys(&self) -> Vec<u32> {
+                let mut words = Vec::with_capacity(($rounds + 1) * 4);
+                for bs in self.sk.iter() {
+                    let (a, b, c, d) = un_bit_slice_4x4_with_u16(bs);
+                    words.push(a.swap_bytes());
+                    words.push(b.swap_bytes());
+                    words.push(c.swap_bytes());
+                    words.push(d.swap_bytes());
+                }
+                words
+            }
+        }
+    )
+);
+
 define_aes_struct!(AesSafe128Encryptor, 10);
 define_aes_struct!(AesSafe128Decryptor, 10);
 define_aes_impl!(AesSafe128Encryptor, Encryption, 10, 16);
 define_aes_impl!(AesSafe128Decryptor, Decryption, 10, 16);
 define_aes_enc!(AesSafe128Encryptor, 10);
+define_aes_round_keys!(AesSafe128Encryptor, 10);
 define_aes_dec!(AesSafe128Decryptor, 10);
 
 define_aes_struct!(AesSafe192Encryptor, 12);
@@ -213,6 +242,7 @@ define_aes_struct!(AesSafe192Decryptor, 12);
 define_aes_impl!(AesSafe192Encryptor, Encryption, 12, 24);
 define_aes_impl!(AesSafe192Decryptor, Decryption, 12, 24);
 define_aes_enc!(AesSafe192Encryptor, 12);
+define_aes_round_keys!(AesSafe192Encryptor, 12);
 define_aes_dec!(AesSafe192Decryptor, 12);
 
 define_aes_struct!(AesSafe256Encryptor, 14);
@@ -220,6 +250,7 @@ define_aes_struct!(AesSafe256Decryptor, 14);
 define_aes_impl!(AesSafe256Encryptor, Encryption, 14, 32);
 define_aes_impl!(AesSafe256Decryptor, Decryption, 14, 32);
 define_aes_enc!(AesSafe256Encryptor, 14);
+define_aes_round_keys!(AesSafe256Encryptor, 14);
 define_aes_dec!(AesSafe256Decryptor, 14);
 
 macro_rules! define_aes_struct_x8(
@@ -324,6 +355,87 @@ define_aes_impl_x8!(AesSafe256DecryptorX8, Decryption, 14, 32);
 define_aes_enc_x8!(AesSafe256EncryptorX8, 14);
 define_aes_dec_x8!(AesSafe256DecryptorX8, 14);
 
+macro_rules! define_aes_struct_x16(
+    (
+        $name:ident,
+        $rounds:expr
+    ) => (
+        #[derive(Clone, Copy)]
+        pub struct $name {
+            sk: [Bs8State<u32x4x2>; ($rounds + 1)]
+        }
+    )
+);
+
+macro_rules! define_aes_impl_x16(
+    (
+        $name:ident,
+        $mode:ident,
+        $rounds:expr,
+        $key_size:expr
+    ) => (
+        impl $name {
+            pub fn new(key: &[u8]) -> $name {
+                let mut a =  $name {
+                    sk: [
+                        Bs8State(
+                            U32X4X2_0,
+                            U32X4X2_0,
+                            U32X4X2_0,
+                            U32X4X2_0,
+                            U32X4X2_0,
+                            U32X4X2_0,
+                            U32X4X2_0,
+                            U32X4X2_0);
+                        ($rounds + 1)]
+                };
+                let mut tmp = [[0u32; 4]; ($rounds + 1)];
+                create_round_keys(key, KeyType::$mode, &mut tmp);
+                for i in 0..$rounds + 1 {
+                    a.sk[i] = bit_slice_fill_4x4_with_u32x4x2(
+                        tmp[i][0],
+                        tmp[i][1],
+                        tmp[i][2],
+                        tmp[i][3]);
+                }
+                a
+            }
+        }
+    )
+);
+
+macro_rules! define_aes_enc_x16(
+    (
+        $name:ident,
+        $rounds:expr
+    ) => (
+        impl BlockEncryptorX16 for $name {
+            fn block_size(&self) -> usize { 16 }
+            fn encrypt_block_x16(&self, input: &[u8], output: &mut [u8]) {
+                encrypt_x16(&self.sk, input, output);
+            }
+        }
+    )
+);
+
+define_aes_struct_x16!(AesSafe128EncryptorX16, 10);
+define_aes_impl_x16!(AesSafe128EncryptorX16, Encryption, 10, 16);
+define_aes_enc_x16!(AesSafe128EncryptorX16, 10);
+
+define_aes_struct_x16!(AesSafe192EncryptorX16, 12);
+define_aes_impl_x16!(AesSafe192EncryptorX16, Encryption, 12, 24);
+define_aes_enc_x16!(AesSafe192EncryptorX16, 12);
+
+define_aes_struct_x16!(AesSafe256EncryptorX16, 14);
+define_aes_impl_x16!(AesSafe256EncryptorX16, Encryption, 14, 32);
+define_aes_enc_x16!(AesSafe256EncryptorX16, 14);
+
+fn encrypt_x16(sk: &[Bs8State<u32x4x2>], input: &[u8], output: &mut [u8]) {
+    let bs = bit_slice_1x256_with_u32x4x2(input);
+    let bs2 = encrypt_core(&bs, sk);
+    un_bit_slice_1x256_with_u32x4x2(bs2, output);
+}
+
 fn ffmulx(x: u32) -> u32 {
     let m1: u32 = 0x80808080;
     let m2: u32 = 0x7f7f7f7f;
@@ -907,6 +1019,46 @@ fn un_bit_slice_1x128_with_u32x4(bs: Bs8State<u32x4>, output: &mut [u8]) {
     write_row_major(x7, &mut output[112..128])
 }
 
+// Bit Slice a 256 byte array of sixteen 16 byte blocks. The first eight blocks are bit sliced into the
+// first u32x4 of each pair and the last eight into the second, exactly as bit_slice_1x128_with_u32x4
+// would do.
+fn bit_slice_1x256_with_u32x4x2(data: &[u8]) -> Bs8State<u32x4x2> {
+    let Bs8State(a0, a1, a2, a3, a4, a5, a6, a7) = bit_slice_1x128_with_u32x4(&data[0..128]);
+    let Bs8State(b0, b1, b2, b3, b4, b5, b6, b7) = bit_slice_1x128_with_u32x4(&data[128..256]);
+    Bs8State(
+        u32x4x2(a0, b0),
+        u32x4x2(a1, b1),
+        u32x4x2(a2, b2),
+        u32x4x2(a3, b3),
+        u32x4x2(a4, b4),
+        u32x4x2(a5, b5),
+        u32x4x2(a6, b6),
+        u32x4x2(a7, b7))
+}
+
+// Bit slice a set of 4 u32s into both halves of a Bs8State<u32x4x2>. This is used as part of bit
+// slicing the round keys.
+fn bit_slice_fill_4x4_with_u32x4x2(a: u32, b: u32, c: u32, d: u32) -> Bs8State<u32x4x2> {
+    let Bs8State(x0, x1, x2, x3, x4, x5, x6, x7) = bit_slice_fill_4x4_with_u32x4(a, b, c, d);
+    Bs8State(
+        u32x4x2(x0, x0),
+        u32x4x2(x1, x1),
+        u32x4x2(x2, x2),
+        u32x4x2(x3, x3),
+        u32x4x2(x4, x4),
+        u32x4x2(x5, x5),
+        u32x4x2(x6, x6),
+        u32x4x2(x7, x7))
+}
+
+// Un bit slice into a 256 byte buffer.
+fn un_bit_slice_1x256_with_u32x4x2(bs: Bs8State<u32x4x2>, output: &mut [u8]) {
+    let Bs8State(x0, x1, x2, x3, x4, x5, x6, x7) = bs;
+    let (out_a, out_b) = output.split_at_mut(128);
+    un_bit_slice_1x128_with_u32x4(Bs8State(x0.0, x1.0, x2.0, x3.0, x4.0, x5.0, x6.0, x7.0), out_a);
+    un_bit_slice_1x128_with_u32x4(Bs8State(x0.1, x1.1, x2.1, x3.1, x4.1, x5.1, x6.1, x7.1), out_b);
+}
+
 // The Gf2Ops, Gf4Ops, and Gf8Ops traits specify the functions needed to calculate the AES S-Box
 // values. This particuar implementation of those S-Box values is taken from [7], so that is where
 // to look for details on how all that all works. This includes the transformations matrices defined
@@ -1229,3 +1381,60 @@ impl AesBitValueOps for u32x4 {
         u32x4(a3, a0, a1, a2)
     }
 }
+
+// A pair of u32x4s that are operated on together, allowing 16 blocks to be processed at once.
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+struct u32x4x2(u32x4, u32x4);
+
+impl BitXor for u32x4x2 {
+    type Output = u32x4x2;
+
+    fn bitxor(self, rhs: u32x4x2) -> u32x4x2 {
+        u32x4x2(self.0 ^ rhs.0, self.1 ^ rhs.1)
+    }
+}
+
+impl BitAnd for u32x4x2 {
+    type Output = u32x4x2;
+
+    fn bitand(self, rhs: u32x4x2) -> u32x4x2 {
+        u32x4x2(self.0 & rhs.0, self.1 & rhs.1)
+    }
+}
+
+impl Not for u32x4x2 {
+    type Output = u32x4x2;
+
+    fn not(self) -> u32x4x2 {
+        u32x4x2(!self.0, !self.1)
+    }
+}
+
+impl Default for u32x4x2 {
+    fn default() -> u32x4x2 {
+        U32X4X2_0
+    }
+}
+
+impl AesBitValueOps for u32x4x2 {
+    fn shift_row(self) -> u32x4x2 {
+        u32x4x2(self.0.shift_row(), self.1.shift_row())
+    }
+
+    fn inv_shift_row(self) -> u32x4x2 {
+        u32x4x2(self.0.inv_shift_row(), self.1.inv_shift_row())
+    }
+
+    fn ror1(self) -> u32x4x2 {
+        u32x4x2(self.0.ror1(), self.1.ror1())
+    }
+
+    fn ror2(self) -> u32x4x2 {
+        u32x4x2(self.0.ror2(), self.1.ror2())
+    }
+
+    fn ror3(self) -> u32x4x2 {
+        u32x4x2(self.0.ror3(), self.1.ror3())
+    }
+}
@@ -0,0 +1,306 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * An implementation of the Ascon-128 authenticated encryption scheme, version 1.2, as selected in
+ * the NIST lightweight cryptography competition. Ascon-128 uses a 128-bit key, a 128-bit nonce and
+ * produces a 128-bit tag. The nonce must never be reused with the same key.
+ *
+ * For details see <https://ascon.iaik.tugraz.at/>.
+ */
+
+use aead::{AeadEncryptor, AeadDecryptor};
+use cryptoutil::{read_u64_be, write_u64_be};
+use symmetriccipher::SymmetricCipherError;
+use util::{fixed_time_eq, secure_memset};
+
+// The initialization vector, which encodes the key size, rate and number of rounds
+const IV: u64 = 0x80400c0600000000;
+
+// The number of bytes absorbed per permutation call
+const RATE: usize = 8;
+
+// The round constants of the 12 round permutation; the 6 round permutation uses the last 6
+static ROUND_CONSTANTS: [u64; 12] = [
+    0xf0, 0xe1, 0xd2, 0xc3, 0xb4, 0xa5, 0x96, 0x87, 0x78, 0x69, 0x5a, 0x4b
+];
+
+// Apply the last rounds rounds of the Ascon permutation to the state
+fn permute(s: &mut [u64; 5], rounds: usize) {
+    for &c in ROUND_CONSTANTS[12 - rounds..].iter() {
+        // Addition of the round constant
+        s[2] ^= c;
+
+        // Substitution layer, the 5-bit S-box applied in bitsliced form
+        s[0] ^= s[4];
+        s[4] ^= s[3];
+        s[2] ^= s[1];
+        let t0 = !s[0] & s[1];
+        let t1 = !s[1] & s[2];
+        let t2 = !s[2] & s[3];
+        let t3 = !s[3] & s[4];
+        let t4 = !s[4] & s[0];
+        s[0] ^= t1;
+        s[1] ^= t2;
+        s[2] ^= t3;
+        s[3] ^= t4;
+        s[4] ^= t0;
+        s[1] ^= s[0];
+        s[0] ^= s[4];
+        s[3] ^= s[2];
+        s[2] = !s[2];
+
+        // Linear diffusion layer
+        s[0] ^= s[0].rotate_right(19) ^ s[0].rotate_right(28);
+        s[1] ^= s[1].rotate_right(61) ^ s[1].rotate_right(39);
+        s[2] ^= s[2].rotate_right(1) ^ s[2].rotate_right(6);
+        s[3] ^= s[3].rotate_right(10) ^ s[3].rotate_right(17);
+        s[4] ^= s[4].rotate_right(7) ^ s[4].rotate_right(41);
+    }
+}
+
+// Read a final partial block of fewer than 8 bytes, padded with a single 1 bit and then zeros
+fn read_padded(input: &[u8]) -> u64 {
+    assert!(input.len() < RATE);
+    let mut block = [0u8; RATE];
+    block[..input.len()].copy_from_slice(input);
+    block[input.len()] = 0x80;
+    read_u64_be(&block)
+}
+
+/// The Ascon-128 AEAD. As with the other AEADs in this crate, an instance is bound to a single
+/// nonce and associated data and can either encrypt or decrypt one message.
+#[derive(Clone, Copy)]
+pub struct Ascon128 {
+    state: [u64; 5],
+    key: [u64; 2],
+    finished: bool
+}
+
+impl Ascon128 {
+    /// Create a new Ascon128 instance from a 16 byte key, a 16 byte nonce and the associated data.
+    pub fn new(key: &[u8], nonce: &[u8], aad: &[u8]) -> Ascon128 {
+        assert!(key.len() == 16);
+        assert!(nonce.len() == 16);
+
+        let k0 = read_u64_be(&key[0..8]);
+        let k1 = read_u64_be(&key[8..16]);
+        let mut state = [IV, k0, k1, read_u64_be(&nonce[0..8]), read_u64_be(&nonce[8..16])];
+        permute(&mut state, 12);
+        state[3] ^= k0;
+        state[4] ^= k1;
+
+        // Empty associated data is skipped entirely, not padded to a block
+        if !aad.is_empty() {
+            let mut blocks = aad.chunks(RATE);
+            let mut last = blocks.next_back().unwrap();
+            for block in blocks {
+                state[0] ^= read_u64_be(block);
+                permute(&mut state, 6);
+            }
+            if last.len() == RATE {
+                state[0] ^= read_u64_be(last);
+                permute(&mut state, 6);
+                last = &[];
+            }
+            state[0] ^= read_padded(last);
+            permute(&mut state, 6);
+        }
+
+        // Domain separation between the associated data and the message
+        state[4] ^= 1;
+
+        Ascon128 {
+            state,
+            key: [k0, k1],
+            finished: false
+        }
+    }
+
+    fn finalize(&mut self, tag: &mut [u8]) {
+        self.state[1] ^= self.key[0];
+        self.state[2] ^= self.key[1];
+        permute(&mut self.state, 12);
+        write_u64_be(&mut tag[0..8], self.state[3] ^ self.key[0]);
+        write_u64_be(&mut tag[8..16], self.state[4] ^ self.key[1]);
+    }
+}
+
+impl AeadEncryptor for Ascon128 {
+    fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]) {
+        assert!(input.len() == output.len());
+        assert!(tag.len() == 16);
+        assert!(!self.finished);
+        self.finished = true;
+
+        let full = input.len() - input.len() % RATE;
+        for (block, out) in input[..full].chunks(RATE).zip(output[..full].chunks_mut(RATE)) {
+            self.state[0] ^= read_u64_be(block);
+            write_u64_be(out, self.state[0]);
+            permute(&mut self.state, 6);
+        }
+
+        let rest = input.len() - full;
+        self.state[0] ^= read_padded(&input[full..]);
+        let mut last = [0u8; RATE];
+        write_u64_be(&mut last, self.state[0]);
+        output[full..].copy_from_slice(&last[..rest]);
+
+        self.finalize(tag);
+    }
+}
+
+impl AeadDecryptor for Ascon128 {
+    /// Decrypt and verify. Because Ascon produces the plaintext while computing the tag, output is
+    /// overwritten with zeros rather than left untouched if the tag doesn't match.
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+            -> Result<(), SymmetricCipherError> {
+        assert!(input.len() == output.len());
+        assert!(tag.len() == 16);
+        assert!(!self.finished);
+        self.finished = true;
+
+        let full = input.len() - input.len() % RATE;
+        for (block, out) in input[..full].chunks(RATE).zip(output[..full].chunks_mut(RATE)) {
+            let c = read_u64_be(block);
+            write_u64_be(out, self.state[0] ^ c);
+            self.state[0] = c;
+            permute(&mut self.state, 6);
+        }
+
+        // The ciphertext replaces the first rest bytes of the rate, and the padding is added to
+        // the bytes that remain
+        let rest = input.len() - full;
+        let mut last = [0u8; RATE];
+        write_u64_be(&mut last, self.state[0]);
+        for i in 0..rest {
+            output[full + i] = last[i] ^ input[full + i];
+            last[i] = input[full + i];
+        }
+        last[rest] ^= 0x80;
+        self.state[0] = read_u64_be(&last);
+
+        let mut calc_tag = [0u8; 16];
+        self.finalize(&mut calc_tag);
+        if fixed_time_eq(&calc_tag, tag) {
+            Ok(())
+        } else {
+            secure_memset(output, 0);
+            Err(SymmetricCipherError::AuthenticationFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aead::{AeadEncryptor, AeadDecryptor};
+    use ascon::Ascon128;
+    use serialize::hex::FromHex;
+    use symmetriccipher::SymmetricCipherError;
+
+    // Test vectors from LWC_AEAD_KAT_128_128.txt of the reference implementation. The key and
+    // nonce are always 000102...0f, and the message and associated data are prefixes of
+    // 000102... of the given lengths.
+    struct Test {
+        count: usize,
+        msg_len: usize,
+        ad_len: usize,
+        ct: &'static str
+    }
+
+    fn tests() -> Vec<Test> {
+        vec![
+            Test { count: 1, msg_len: 0, ad_len: 0, ct: "e355159f292911f794cb1432a0103a8a" },
+            Test { count: 2, msg_len: 0, ad_len: 1, ct: "944df887cd4901614c5dedbc42fc0da0" },
+            Test { count: 34, msg_len: 1, ad_len: 0, ct: "bc18c3f4e39eca7222490d967c79bffc92" },
+            Test { count: 35, msg_len: 1, ad_len: 1, ct: "bd4102b707775c3c155ae497b43bf834e5" },
+            Test { count: 100, msg_len: 3, ad_len: 0, ct: "bc820d5bca14147915031c69f6b27848a7ee29" },
+            Test { count: 1089, msg_len: 32, ad_len: 32, ct: "b96c78651b6246b0c3b1a5d373b0d516\
+                8dca4a96734cf0ddf5f92f8d15e30270279bf6a6cc3f2fc9350b915c292bdb8d" },
+        ]
+    }
+
+    #[test]
+    fn test_kat() {
+        let key: Vec<u8> = (0..16).collect();
+        let data: Vec<u8> = (0..32).collect();
+        for t in tests().iter() {
+            let expected = t.ct.from_hex().unwrap();
+            let (expected_ct, expected_tag) = expected.split_at(t.msg_len);
+            let msg = &data[..t.msg_len];
+            let ad = &data[..t.ad_len];
+
+            let mut ct = vec![0u8; t.msg_len];
+            let mut tag = [0u8; 16];
+            Ascon128::new(&key, &key, ad).encrypt(msg, &mut ct, &mut tag);
+            assert!(&ct[..] == expected_ct, "count {}", t.count);
+            assert!(&tag[..] == expected_tag, "count {}", t.count);
+
+            let mut pt = vec![0u8; t.msg_len];
+            Ascon128::new(&key, &key, ad).decrypt(&ct, &mut pt, &tag).unwrap();
+            assert!(&pt[..] == msg, "count {}", t.count);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_lengths() {
+        let key = [7u8; 16];
+        let nonce = [9u8; 16];
+        let data: Vec<u8> = (0..100).map(|i| (i * 3) as u8).collect();
+        for len in 0..data.len() {
+            let ad = &data[..len / 2];
+            let mut ct = vec![0u8; len];
+            let mut tag = [0u8; 16];
+            Ascon128::new(&key, &nonce, ad).encrypt(&data[..len], &mut ct, &mut tag);
+            let mut pt = vec![0u8; len];
+            Ascon128::new(&key, &nonce, ad).decrypt(&ct, &mut pt, &tag).unwrap();
+            assert!(pt[..] == data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_authentication_failure() {
+        let key = [7u8; 16];
+        let nonce = [9u8; 16];
+        let msg = b"attack at dawn";
+        let mut ct = [0u8; 14];
+        let mut tag = [0u8; 16];
+        Ascon128::new(&key, &nonce, b"header").encrypt(msg, &mut ct, &mut tag);
+
+        let mut pt = [0u8; 14];
+        let mut bad_ct = ct;
+        bad_ct[13] ^= 1;
+        match Ascon128::new(&key, &nonce, b"header").decrypt(&bad_ct, &mut pt, &tag) {
+            Err(SymmetricCipherError::AuthenticationFailed) => {}
+            _ => panic!("expected an authentication failure")
+        }
+        // The unauthenticated plaintext must not be released
+        assert_eq!(pt, [0u8; 14]);
+
+        assert!(Ascon128::new(&key, &nonce, b"Header").decrypt(&ct, &mut pt, &tag).is_err());
+        let mut bad_tag = tag;
+        bad_tag[0] ^= 0x80;
+        assert!(Ascon128::new(&key, &nonce, b"header").decrypt(&ct, &mut pt, &bad_tag).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use aead::AeadEncryptor;
+    use ascon::Ascon128;
+
+    #[bench]
+    pub fn ascon128_1k(bh: & mut Bencher) {
+        let input = [1u8; 1024];
+        let mut output = [0u8; 1024];
+        let mut tag = [0u8; 16];
+        bh.iter( || {
+            Ascon128::new(&[0; 16], &[0; 16], &[]).encrypt(&input, &mut output, &mut tag);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
@@ -8,25 +8,12 @@ use blowfish::Blowfish;
 use cryptoutil::{write_u32_be};
 use step_by::RangeExt;
 
-fn setup(cost: u32, salt: &[u8], key: &[u8]) -> Blowfish {
-    assert!(cost < 32);
-    let mut state = Blowfish::init_state();
-    
-    state.salted_expand_key(salt, key);
-    for _ in 0..1u32 << cost {
-        state.expand_key(key);
-        state.expand_key(salt);
-    }
-
-    state
-}
-
 pub fn bcrypt(cost: u32, salt: &[u8], password: &[u8], output: &mut [u8]) {
     assert!(salt.len() == 16);
     assert!(0 < password.len() && password.len() <= 72);
     assert!(output.len() == 24);
 
-    let state = setup(cost, salt, password);
+    let state = Blowfish::new_eks(cost, salt, password);
     // OrpheanBeholderScryDoubt
     let mut ctext = [0x4f727068, 0x65616e42, 0x65686f6c, 0x64657253, 0x63727944, 0x6f756274];
     for i in (0..6).step_up(2) {
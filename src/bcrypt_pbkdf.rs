@@ -73,22 +73,45 @@ pub fn bcrypt_pbkdf(password: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]
             for i in 0..out.len() {
                 out[i] ^= tmp[i];
             }
+        }
 
-            for i in 0..out.len() {
-                let idx = i * nblocks + (block-1);
-                if idx < output.len() {
-                    output[idx] = out[i];
-                }
+        for i in 0..out.len() {
+            let idx = i * nblocks + (block-1);
+            if idx < output.len() {
+                output[idx] = out[i];
             }
         }
     }
 }
 
+/**
+ * Derive key material from a passphrase the way OpenSSH does to encrypt private keys in its
+ * "openssh-key-v1" format, where the key and IV of the cipher are read from out. For example,
+ * aes256-ctr uses a 48 byte out: the first 32 bytes are the key and the remaining 16 are the IV.
+ *
+ * Each 32 byte bcrypt block is spread across out with a stride of the number of blocks, rather than
+ * being copied to consecutive bytes, so every byte of a longer output depends on every round.
+ *
+ * # Arguments
+ * * passphrase - The passphrase, which must not be empty.
+ * * salt - The salt from the key file's KDF options, which must be 1 byte to 1 MiB long.
+ * * rounds - The number of rounds from the key file's KDF options, which must be at least 1.
+ * * out - The buffer to fill, which must be 1 to 1024 bytes long.
+ *
+ */
+pub fn derive(passphrase: &[u8], salt: &[u8], rounds: u32, out: &mut [u8]) {
+    assert!(salt.len() <= 1 << 20);
+    bcrypt_pbkdf(passphrase, salt, rounds, out);
+}
+
 #[cfg(test)]
 mod test {
     use std::iter::repeat;
 
-    use bcrypt_pbkdf::{bcrypt_pbkdf, bcrypt_hash};
+    use aes;
+    use aes::KeySize;
+    use bcrypt_pbkdf::{bcrypt_pbkdf, bcrypt_hash, derive};
+    use serialize::hex::{FromHex, ToHex};
 
     #[test]
     fn test_bcrypt_hash() {
@@ -263,6 +286,34 @@ mod test {
             assert_eq!(out, t.out);
         }
     }
+
+    #[test]
+    fn test_openssh_derive() {
+        // The salts and rounds are from aes256-ctr encrypted ed25519 keys that were generated by
+        // ssh-keygen with the passphrase "password" (with -a 1 and -a 16). The first 8 bytes of each
+        // encrypted private section decrypt to the two matching check integers.
+        let tests = [
+            ("2b9347c480867e8c2065bc71d8c608a0", 1,
+             "125b2302dc16f517f6b85b501e31e5910efed4152f931852ab16566dc58fe862\
+              579766e62ea918a24e13983c64421684",
+             "5ec48eee1fc740a2"),
+            ("0664d734577e47a6f2bd0cca001a8710", 16,
+             "4896e3a7b7517c9251c2bf26c7c0a6aebda7e426a4bfb49c6044f50cab29c1f7\
+              94c00d18cb9f6d89b2a541c9602344d0",
+             "e8987852e2432fb2")
+        ];
+
+        for &(salt, rounds, expected, encrypted) in tests.iter() {
+            let mut out = [0u8; 48];
+            derive(b"password", &salt.from_hex().unwrap(), rounds, &mut out);
+            assert_eq!(out.to_hex(), expected);
+
+            let mut ctr = aes::ctr(KeySize::KeySize256, &out[..32], &out[32..]);
+            let mut check = [0u8; 8];
+            ctr.process(&encrypted.from_hex().unwrap(), &mut check);
+            assert!(check[..4] == check[4..]);
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
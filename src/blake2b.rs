@@ -4,6 +4,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::cmp::min;
 use std::iter::repeat;
 use cryptoutil::{copy_memory, read_u64v_le, write_u64v_le};
 use digest::Digest;
@@ -341,7 +342,7 @@ impl Digest for Blake2b {
     fn input(&mut self, msg: &[u8]) { self.update(msg); }
     fn result(&mut self, out: &mut [u8]) { self.finalize(out); }
     fn output_bits(&self) -> usize { 8 * (self.digest_length as usize) }
-    fn block_size(&self) -> usize { 8 * BLAKE2B_BLOCKBYTES }
+    fn block_size(&self) -> usize { BLAKE2B_BLOCKBYTES }
 }
 
 impl Mac for Blake2b {
@@ -387,6 +388,136 @@ impl Mac for Blake2b {
     fn output_bytes(&self) -> usize { self.digest_length as usize }
 }
 
+const BLAKE2BP_PARALLELISM : usize = 4;
+
+/// The Blake2bp tree hashing mode. The input is split into 128 byte blocks which are distributed
+/// round robin across 4 leaf Blake2b instances, and the digests of the leaves are then hashed by a
+/// root node. This produces different output than Blake2b. The leaves are independent and could be
+/// hashed in parallel, however this implementation processes them sequentially on the calling
+/// thread.
+#[derive(Copy, Clone)]
+pub struct Blake2bp {
+    leaves: [Blake2b; BLAKE2BP_PARALLELISM],
+    root: Blake2b,
+    position: usize, // offset into the current group of leaf blocks
+}
+
+impl Blake2bp {
+    fn tree_param(outlen: usize, keylen: usize, node_offset: u64, node_depth: u8) -> Blake2bParam {
+        Blake2bParam {
+            digest_length: outlen as u8,
+            key_length: keylen as u8,
+            fanout: BLAKE2BP_PARALLELISM as u8,
+            depth: 2,
+            leaf_length: 0,
+            node_offset,
+            node_depth,
+            inner_length: BLAKE2B_OUTBYTES as u8,
+            reserved: [0; 14],
+            salt: [0; BLAKE2B_SALTBYTES],
+            personal: [0; BLAKE2B_PERSONALBYTES],
+        }
+    }
+
+    fn init(outlen: usize, key: &[u8]) -> Blake2bp {
+        assert!(outlen > 0 && outlen <= BLAKE2B_OUTBYTES);
+        assert!(key.len() <= BLAKE2B_KEYBYTES);
+
+        // The leaves always output a full size digest, which the root absorbs
+        let mut leaves = [Blake2b::init0(Blake2bp::tree_param(outlen, key.len(), 0, 0),
+                                         BLAKE2B_OUTBYTES as u8, key); BLAKE2BP_PARALLELISM];
+        for (i, leaf) in leaves.iter_mut().enumerate() {
+            leaf.param.node_offset = i as u64;
+        }
+        let root = Blake2b::init0(Blake2bp::tree_param(outlen, key.len(), 0, 1), outlen as u8, &[]);
+
+        let mut b = Blake2bp {
+            leaves,
+            root,
+            position: 0,
+        };
+        b.reset();
+        b
+    }
+
+    pub fn new(outlen: usize) -> Blake2bp {
+        Blake2bp::init(outlen, &[])
+    }
+
+    pub fn new_keyed(outlen: usize, key: &[u8]) -> Blake2bp {
+        assert!(!key.is_empty());
+        Blake2bp::init(outlen, key)
+    }
+
+    fn update(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            let leaf = self.position / BLAKE2B_BLOCKBYTES;
+            let take = min(BLAKE2B_BLOCKBYTES - self.position % BLAKE2B_BLOCKBYTES, input.len());
+            self.leaves[leaf].update(&input[..take]);
+            self.position = (self.position + take) % (BLAKE2BP_PARALLELISM * BLAKE2B_BLOCKBYTES);
+            input = &input[take..];
+        }
+    }
+
+    fn finalize(&mut self, out: &mut [u8]) {
+        if !self.root.computed {
+            let mut leaf_out = [0u8; BLAKE2B_OUTBYTES];
+            for leaf in self.leaves.iter_mut() {
+                leaf.finalize(&mut leaf_out);
+                self.root.update(&leaf_out);
+            }
+        }
+        self.root.finalize(out);
+    }
+
+    pub fn reset(&mut self) {
+        for leaf in self.leaves.iter_mut() {
+            leaf.reset();
+        }
+        self.root.reset();
+        self.leaves[BLAKE2BP_PARALLELISM - 1].last_node = 1;
+        self.root.last_node = 1;
+        self.position = 0;
+    }
+
+    pub fn blake2bp(out: &mut[u8], input: &[u8], key: &[u8]) {
+        let mut hasher : Blake2bp = if !key.is_empty() { Blake2bp::new_keyed(out.len(), key) } else { Blake2bp::new(out.len()) };
+
+        hasher.update(input);
+        hasher.finalize(out);
+    }
+}
+
+impl Digest for Blake2bp {
+    fn reset(&mut self) { Blake2bp::reset(self); }
+    fn input(&mut self, msg: &[u8]) { self.update(msg); }
+    fn result(&mut self, out: &mut [u8]) { self.finalize(out); }
+    fn output_bits(&self) -> usize { 8 * (self.root.digest_length as usize) }
+    fn block_size(&self) -> usize { BLAKE2B_BLOCKBYTES }
+}
+
+impl Mac for Blake2bp {
+    fn input(&mut self, data: &[u8]) {
+        self.update(data);
+    }
+
+    fn reset(&mut self) {
+        Blake2bp::reset(self);
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut mac = vec![0; self.root.digest_length as usize];
+        self.raw_result(&mut mac);
+        MacResult::new_from_owned(mac)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        self.finalize(output);
+    }
+
+    fn output_bytes(&self) -> usize { self.root.digest_length as usize }
+}
+
 #[cfg(test)]
 mod digest_tests {
     //use cryptoutil::test::test_digest_1million_random;
@@ -497,6 +628,57 @@ mod digest_tests {
     }
 }
 
+#[cfg(test)]
+mod blake2bp_tests {
+    use blake2b::Blake2bp;
+    use digest::Digest;
+    use mac::Mac;
+    use serialize::hex::FromHex;
+
+    #[test]
+    fn test_blake2bp_empty() {
+        let mut sh = Blake2bp::new(64);
+        let mut out = [0u8; 64];
+        Digest::result(&mut sh, &mut out);
+        assert_eq!(out.to_vec(), "b5ef811a8038f70b628fa8b294daae7492b1ebe343a80eaabbf1f6ae664dd67b\
+                                  9d90b0120791eab81dc96985f28849f6a305186a85501b405114bfa678df9380".from_hex().unwrap());
+    }
+
+    #[test]
+    fn test_blake2bp_256_bytes() {
+        let input: Vec<u8> = (0..256).map(|i| i as u8).collect();
+        let expected = "ef1132d866055876c15959557d79cff0539b93b26f47bf4183748921df72c3ed\
+                        94b0a5e95e17a4bbc59437f34564e60d20923dd643420f5ca25b2ca7ec1ceda4".from_hex().unwrap();
+
+        let mut out = [0u8; 64];
+        Blake2bp::blake2bp(&mut out, &input, &[]);
+        assert_eq!(out.to_vec(), expected);
+
+        // The leaf a byte is assigned to must not depend on how the input is split up
+        for &chunk_size in [1usize, 3, 127, 128, 129, 200].iter() {
+            let mut sh = Blake2bp::new(64);
+            for chunk in input.chunks(chunk_size) {
+                Digest::input(&mut sh, chunk);
+            }
+            let mut out = [0u8; 64];
+            Digest::result(&mut sh, &mut out);
+            assert_eq!(out.to_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn test_blake2bp_keyed() {
+        // The first keyed Blake2bp test vector from the reference implementation
+        let key: Vec<u8> = (0..64).collect();
+        let mut m = Blake2bp::new_keyed(64, &key);
+        let expected = "9d9461073e4eb640a255357b839f394b838c6ff57c9b686a3f76107c1066728f\
+                        3c9956bd785cbc3bf79dc2ab578c5a0c063b9d9c405848de1dbe821cd05c940a".from_hex().unwrap();
+        assert_eq!(Mac::result(&mut m).code().to_vec(), expected);
+
+        Mac::reset(&mut m);
+        assert_eq!(Mac::result(&mut m).code().to_vec(), expected);
+    }
+}
 
 #[cfg(test)]
 mod mac_tests {
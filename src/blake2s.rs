@@ -331,7 +331,7 @@ impl Digest for Blake2s {
     fn input(&mut self, msg: &[u8]) { self.update(msg); }
     fn result(&mut self, out: &mut [u8]) { self.finalize(out); }
     fn output_bits(&self) -> usize { 8 * (self.digest_length as usize) }
-    fn block_size(&self) -> usize { 8 * BLAKE2S_BLOCKBYTES }
+    fn block_size(&self) -> usize { BLAKE2S_BLOCKBYTES }
 }
 
 impl Mac for Blake2s {
@@ -461,6 +461,28 @@ mod digest_tests {
 
         test_hash(&tests[..]);
     }
+
+    #[test]
+    fn test_blake2s_abc() {
+        let tests = vec![
+            // from: https://tools.ietf.org/html/rfc7693#appendix-B
+            Test {
+                input: b"abc".to_vec(),
+                output: vec![0x50, 0x8c, 0x5e, 0x8c, 0x32, 0x7c, 0x14, 0xe2, 0xe1, 0xa7, 0x2b,
+                             0xa3, 0x4e, 0xeb, 0x45, 0x2f, 0x37, 0x45, 0x8b, 0x20, 0x9e, 0xd6,
+                             0x3a, 0x29, 0x4d, 0x99, 0x9b, 0x4c, 0x86, 0x67, 0x59, 0x82],
+                key: None
+            },
+        ];
+
+        test_hash(&tests[..]);
+    }
+
+    #[test]
+    fn test_blake2s_block_size() {
+        // The block size is in bytes, which is what Hmac relies on
+        assert_eq!(Blake2s::new(32).block_size(), 64);
+    }
 }
 
 
@@ -482,6 +504,24 @@ mod mac_tests {
         ];
         assert_eq!(m.result().code().to_vec(), expected.to_vec());
     }
+
+    #[test]
+    fn test_blake2s_mac_empty_input() {
+        // from: https://github.com/BLAKE2/BLAKE2/blob/master/testvectors/blake2s-kat.txt
+        let key: Vec<u8> = (0..32).map(|i| i).collect();
+        let mut m = Blake2s::new_keyed(32, &key[..]);
+        let expected = [
+            0x48, 0xa8, 0x99, 0x7d, 0xa4, 0x07, 0x87, 0x6b,
+            0x3d, 0x79, 0xc0, 0xd9, 0x23, 0x25, 0xad, 0x3b,
+            0x89, 0xcb, 0xb7, 0x54, 0xd8, 0x6a, 0xb7, 0x1a,
+            0xee, 0x04, 0x7a, 0xd3, 0x45, 0xfd, 0x2c, 0x49
+        ];
+        assert_eq!(m.result().code().to_vec(), expected.to_vec());
+
+        // The key block must be processed again after a reset
+        m.reset();
+        assert_eq!(m.result().code().to_vec(), expected.to_vec());
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
@@ -9,14 +9,16 @@
 // TODO - I think padding could be done better. Maybe macros for BlockEngine would help this too.
 
 use std::cmp;
+#[cfg(debug_assertions)]
+use std::collections::HashSet;
 use std::iter::repeat;
 
 use buffer::{ReadBuffer, WriteBuffer, OwnedReadBuffer, OwnedWriteBuffer, BufferResult,
     RefReadBuffer, RefWriteBuffer};
 use buffer::BufferResult::{BufferUnderflow, BufferOverflow};
-use cryptoutil::{self, symm_enc_or_dec};
-use symmetriccipher::{BlockEncryptor, BlockEncryptorX8, Encryptor, BlockDecryptor, Decryptor,
-    SynchronousStreamCipher, SymmetricCipherError};
+use cryptoutil::{self, debug_assert_no_overlap, symm_enc_or_dec};
+use symmetriccipher::{BlockEncryptor, BlockEncryptorX8, BlockEncryptorX16, Encryptor,
+    BlockDecryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
 use symmetriccipher::SymmetricCipherError::{InvalidPadding, InvalidLength};
 
 /// The BlockProcessor trait is used to implement modes that require processing complete blocks of
@@ -252,8 +254,15 @@ impl <P: BlockProcessor, X: PaddingProcessor> BlockEngine<P, X> {
                     match self.state {
                         BlockEngineState::FastMode => {
                             // If FastMode completes but stays in the FastMode state, it means that
-                            // we've run out of input data.
-                            return Ok(BufferUnderflow);
+                            // we've run out of input data. FastMode never consumes the last block,
+                            // so this can only happen when no input was supplied - if that is the
+                            // end of the data, we still need to go through last block processing
+                            // so that padding gets added.
+                            if eof {
+                                self.state = BlockEngineState::NeedInput;
+                            } else {
+                                return Ok(BufferUnderflow);
+                            }
                         }
                         _ => {}
                     }
@@ -426,6 +435,11 @@ impl PaddingProcessor for NoPadding {
 }
 
 /// PKCS padding mode for ECB and CBC encryption
+///
+/// When decrypting, the final block must end with N bytes of value N, where N is between 1 and the
+/// block size, or the operation fails with SymmetricCipherError::InvalidPadding. Callers must not
+/// let an attacker distinguish that error from a MAC failure, or from any other decryption failure,
+/// since doing so creates a padding oracle.
 #[derive(Clone, Copy)]
 pub struct PkcsPadding;
 
@@ -449,6 +463,9 @@ impl PaddingProcessor for PkcsPadding {
         {
             let data = output_buffer.peek_remaining();
             last_byte = *data.last().unwrap();
+            if last_byte == 0 || last_byte as usize > data.len() {
+                return false;
+            }
             for &x in data.iter().rev().take(last_byte as usize) {
                 if x != last_byte {
                     return false;
@@ -460,6 +477,71 @@ impl PaddingProcessor for PkcsPadding {
     }
 }
 
+/// ISO/IEC 7816-4 padding mode for ECB and CBC encryption: a single 0x80 byte followed by as many
+/// zero bytes as are needed to fill the last block. As with PkcsPadding, a full block of padding is
+/// added if the input is a multiple of the block size.
+#[derive(Clone, Copy)]
+pub struct Iso7816Padding;
+
+impl PaddingProcessor for Iso7816Padding {
+    fn pad_input<W: WriteBuffer>(&mut self, input_buffer: &mut W) {
+        let rem = input_buffer.remaining();
+        assert!(rem != 0);
+        let padding = input_buffer.take_remaining();
+        padding[0] = 0x80;
+        for v in padding[1..].iter_mut() {
+            *v = 0;
+        }
+    }
+    fn strip_output<R: ReadBuffer>(&mut self, output_buffer: &mut R) -> bool {
+        let pad_len: usize;
+        {
+            let data = output_buffer.peek_remaining();
+            match data.iter().rposition(|&x| x != 0) {
+                Some(pos) if data[pos] == 0x80 => pad_len = data.len() - pos,
+                _ => return false
+            }
+        }
+        output_buffer.truncate(pad_len);
+        true
+    }
+}
+
+/// ANSI X9.23 padding mode for ECB and CBC encryption: zero bytes followed by a final byte holding
+/// the total number of padding bytes. As with PkcsPadding, a full block of padding is added if the
+/// input is a multiple of the block size.
+#[derive(Clone, Copy)]
+pub struct AnsiX923Padding;
+
+impl PaddingProcessor for AnsiX923Padding {
+    fn pad_input<W: WriteBuffer>(&mut self, input_buffer: &mut W) {
+        let rem = input_buffer.remaining();
+        assert!(rem != 0 && rem <= 255);
+        let padding = input_buffer.take_remaining();
+        for v in padding.iter_mut() {
+            *v = 0;
+        }
+        padding[rem - 1] = rem as u8;
+    }
+    fn strip_output<R: ReadBuffer>(&mut self, output_buffer: &mut R) -> bool {
+        let last_byte: u8;
+        {
+            let data = output_buffer.peek_remaining();
+            last_byte = *data.last().unwrap();
+            if last_byte == 0 || last_byte as usize > data.len() {
+                return false;
+            }
+            for &x in data.iter().rev().skip(1).take(last_byte as usize - 1) {
+                if x != 0 {
+                    return false;
+                }
+            }
+        }
+        output_buffer.truncate(last_byte as usize);
+        true
+    }
+}
+
 /// Wraps a PaddingProcessor so that only pad_input() will actually be called.
 pub struct EncPadding<X> {
     padding: X
@@ -489,16 +571,44 @@ impl <X: PaddingProcessor> PaddingProcessor for DecPadding<X> {
 }
 
 struct EcbEncryptorProcessor<T> {
-    algo: T
+    algo: T,
+    // The plaintext blocks seen so far, to detect repeated blocks in debug builds
+    #[cfg(debug_assertions)]
+    seen: HashSet<Vec<u8>>,
+    repeated: bool
+}
+
+impl <T> EcbEncryptorProcessor<T> {
+    #[cfg(debug_assertions)]
+    fn check_repeated(&mut self, input: &[u8]) {
+        if !self.repeated && !self.seen.insert(input.to_vec()) {
+            self.repeated = true;
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn check_repeated(&mut self, _: &[u8]) { }
+
+    fn reset(&mut self) {
+        #[cfg(debug_assertions)]
+        self.seen.clear();
+        self.repeated = false;
+    }
 }
 
 impl <T: BlockEncryptor> BlockProcessor for EcbEncryptorProcessor<T> {
     fn process_block(&mut self, _: &[u8], _: &[u8], input: &[u8], output: &mut [u8]) {
+        self.check_repeated(input);
         self.algo.encrypt_block(input, output);
     }
 }
 
 /// ECB Encryption mode
+///
+/// ECB encrypts every block independently, so equal plaintext blocks produce equal ciphertext
+/// blocks and the structure of the data shows through the encryption. It is only provided for
+/// interoperability and as a building block; new protocols should use CBC, CTR or an
+/// authenticated mode instead.
 pub struct EcbEncryptor<T, X> {
     block_engine: BlockEngine<EcbEncryptorProcessor<T>, X>
 }
@@ -508,15 +618,27 @@ impl <T: BlockEncryptor, X: PaddingProcessor> EcbEncryptor<T, X> {
     pub fn new(algo: T, padding: X) -> EcbEncryptor<T, EncPadding<X>> {
         let block_size = algo.block_size();
         let processor = EcbEncryptorProcessor {
-            algo: algo
+            algo,
+            #[cfg(debug_assertions)]
+            seen: HashSet::new(),
+            repeated: false
         };
         EcbEncryptor {
             block_engine: BlockEngine::new(processor, EncPadding::wrap(padding), block_size)
         }
     }
     pub fn reset(&mut self) {
+        self.block_engine.processor.reset();
         self.block_engine.reset();
     }
+    /// Whether the same plaintext block, including any padding block, has been encrypted more than
+    /// once since this object was created or last reset. This is the classic weakness of ECB: the
+    /// repeated block is visible in the ciphertext. Tracking the blocks costs memory proportional
+    /// to the data, so it is only done in builds with debug assertions enabled; in release builds
+    /// this always returns false.
+    pub fn repeated_blocks(&self) -> bool {
+        self.block_engine.processor.repeated
+    }
 }
 
 impl <T: BlockEncryptor, X: PaddingProcessor> Encryptor for EcbEncryptor<T, X> {
@@ -600,9 +722,27 @@ impl <T: BlockEncryptor, X: PaddingProcessor> CbcEncryptor<T, X> {
                 iv)
         }
     }
+    /// Create a CBC encryption mode object that continues a stream which was previously stopped.
+    /// last_cipher_block is the value of current_iv() when the stream was stopped, or the original
+    /// IV if no ciphertext had been produced yet.
+    pub fn from_state(algo: T, padding: X, last_cipher_block: &[u8])
+            -> CbcEncryptor<T, EncPadding<X>> {
+        CbcEncryptor::new(algo, padding, last_cipher_block.to_vec())
+    }
     pub fn reset(&mut self, iv: &[u8]) {
         self.block_engine.reset_with_history(&[], iv);
     }
+    /// Get the IV for the next block of plaintext - the last block of ciphertext that was produced,
+    /// or the original IV if no ciphertext has been produced yet.
+    ///
+    /// The final block of input is held back until more input or the end of the data arrives, so
+    /// this only describes a resumable state after a call to encrypt() that returned
+    /// BufferUnderflow. At that point all of the ciphertext produced so far has been written to the
+    /// output, and encryption may be resumed with from_state() starting at the plaintext byte
+    /// whose offset equals the number of ciphertext bytes written.
+    pub fn current_iv(&self) -> &[u8] {
+        &self.block_engine.out_hist[..]
+    }
 }
 
 impl <T: BlockEncryptor, X: PaddingProcessor> Encryptor for CbcEncryptor<T, X> {
@@ -660,6 +800,160 @@ impl <T: BlockDecryptor, X: PaddingProcessor> Decryptor for CbcDecryptor<T, X> {
     }
 }
 
+// Multiply the XEX tweak by the primitive element alpha of GF(2^128). As in XTS, the tweak is stored
+// little endian and reduced by x^128 + x^7 + x^2 + x + 1.
+fn xex_mul_alpha(tweak: &mut [u8]) {
+    let mut carry = 0;
+    for b in tweak.iter_mut() {
+        let next_carry = *b >> 7;
+        *b = (*b << 1) | carry;
+        carry = next_carry;
+    }
+    tweak[0] ^= 0x87 & (0u8.wrapping_sub(carry));
+}
+
+// Compute the tweak for the first block by encrypting the caller's tweak value.
+fn xex_initial_tweak<C: BlockEncryptor>(tweak_cipher: &C, tweak: &[u8]) -> Vec<u8> {
+    assert!(tweak_cipher.block_size() == 16);
+    assert!(tweak.len() == 16);
+    let mut t: Vec<u8> = vec![0; 16];
+    tweak_cipher.encrypt_block(tweak, &mut t);
+    t
+}
+
+struct XexEncryptorProcessor<T> {
+    algo: T,
+    tweak: Vec<u8>,
+    temp: Vec<u8>
+}
+
+impl <T: BlockEncryptor> BlockProcessor for XexEncryptorProcessor<T> {
+    fn process_block(&mut self, _: &[u8], _: &[u8], input: &[u8], output: &mut [u8]) {
+        for ((&x, &t), o) in input.iter().zip(self.tweak.iter()).zip(self.temp.iter_mut()) {
+            *o = x ^ t;
+        }
+        self.algo.encrypt_block(&self.temp[..], output);
+        for (o, &t) in output.iter_mut().zip(self.tweak.iter()) {
+            *o ^= t;
+        }
+        xex_mul_alpha(&mut self.tweak);
+    }
+}
+
+/// XEX (XOR-Encrypt-XOR) tweakable encryption mode, as used by XTS for disk encryption. Block i of
+/// the data is encrypted as E(P ^ T_i) ^ T_i, where T_0 is the tweak (usually the sector number)
+/// encrypted with a second key and T_i+1 is T_i multiplied by alpha in GF(2^128).
+///
+/// The data must be a whole number of 16 byte blocks; anything else fails with InvalidLength. XTS
+/// is this mode plus ciphertext stealing for a partial final block.
+pub struct XexEncryptor<T> {
+    block_engine: BlockEngine<XexEncryptorProcessor<T>, NoPadding>
+}
+
+impl <T: BlockEncryptor> XexEncryptor<T> {
+    /// Create a new XEX encryption mode object. algo encrypts the data and tweak_cipher, which
+    /// should use an independent key, encrypts the 16 byte tweak.
+    pub fn new<C: BlockEncryptor>(algo: T, tweak_cipher: &C, tweak: &[u8]) -> XexEncryptor<T> {
+        assert!(algo.block_size() == 16);
+        let processor = XexEncryptorProcessor {
+            algo,
+            tweak: xex_initial_tweak(tweak_cipher, tweak),
+            temp: vec![0; 16]
+        };
+        XexEncryptor {
+            block_engine: BlockEngine::new(processor, NoPadding, 16)
+        }
+    }
+    /// Start encrypting a new data unit, such as the next sector, with a new tweak.
+    pub fn reset<C: BlockEncryptor>(&mut self, tweak_cipher: &C, tweak: &[u8]) {
+        self.block_engine.processor.tweak = xex_initial_tweak(tweak_cipher, tweak);
+        self.block_engine.reset();
+    }
+}
+
+impl <T: BlockEncryptor> Encryptor for XexEncryptor<T> {
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        self.block_engine.process(input, output, eof)
+    }
+}
+
+struct XexDecryptorProcessor<T> {
+    algo: T,
+    tweak: Vec<u8>,
+    temp: Vec<u8>
+}
+
+impl <T: BlockDecryptor> BlockProcessor for XexDecryptorProcessor<T> {
+    fn process_block(&mut self, _: &[u8], _: &[u8], input: &[u8], output: &mut [u8]) {
+        for ((&x, &t), o) in input.iter().zip(self.tweak.iter()).zip(self.temp.iter_mut()) {
+            *o = x ^ t;
+        }
+        self.algo.decrypt_block(&self.temp[..], output);
+        for (o, &t) in output.iter_mut().zip(self.tweak.iter()) {
+            *o ^= t;
+        }
+        xex_mul_alpha(&mut self.tweak);
+    }
+}
+
+/// XEX (XOR-Encrypt-XOR) tweakable decryption mode. See XexEncryptor.
+pub struct XexDecryptor<T> {
+    block_engine: BlockEngine<XexDecryptorProcessor<T>, NoPadding>
+}
+
+impl <T: BlockDecryptor> XexDecryptor<T> {
+    /// Create a new XEX decryption mode object. algo decrypts the data and tweak_cipher encrypts
+    /// the 16 byte tweak, exactly as when encrypting.
+    pub fn new<C: BlockEncryptor>(algo: T, tweak_cipher: &C, tweak: &[u8]) -> XexDecryptor<T> {
+        assert!(algo.block_size() == 16);
+        let processor = XexDecryptorProcessor {
+            algo,
+            tweak: xex_initial_tweak(tweak_cipher, tweak),
+            temp: vec![0; 16]
+        };
+        XexDecryptor {
+            block_engine: BlockEngine::new(processor, NoPadding, 16)
+        }
+    }
+    /// Start decrypting a new data unit, such as the next sector, with a new tweak.
+    pub fn reset<C: BlockEncryptor>(&mut self, tweak_cipher: &C, tweak: &[u8]) {
+        self.block_engine.processor.tweak = xex_initial_tweak(tweak_cipher, tweak);
+        self.block_engine.reset();
+    }
+}
+
+impl <T: BlockDecryptor> Decryptor for XexDecryptor<T> {
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        self.block_engine.process(input, output, eof)
+    }
+}
+
+/// Which part of the counter block CTR mode increments after each block of keystream. Bytes
+/// outside of the counter are left unchanged, so the counter wraps around to zero without carrying
+/// into them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterMode {
+    /// The whole block is a single big-endian counter. This is the default.
+    Whole128BE,
+    /// Only the last 4 bytes of the block are a big-endian counter.
+    Low32BE,
+    /// Only the last 8 bytes of the block are a big-endian counter.
+    Low64BE
+}
+
+impl CounterMode {
+    fn counter_bytes<'a>(&self, ctr: &'a mut [u8]) -> &'a mut [u8] {
+        let len = ctr.len();
+        match *self {
+            CounterMode::Whole128BE => ctr,
+            CounterMode::Low32BE => &mut ctr[len - 4..],
+            CounterMode::Low64BE => &mut ctr[len - 8..]
+        }
+    }
+}
+
 fn add_ctr(ctr: &mut [u8], mut ammount: u8) {
     for i in ctr.iter_mut().rev() {
         let prev = *i;
@@ -675,32 +969,55 @@ fn add_ctr(ctr: &mut [u8], mut ammount: u8) {
 pub struct CtrMode<A> {
     algo: A,
     ctr: Vec<u8>,
+    mode: CounterMode,
     bytes: OwnedReadBuffer
 }
 
 impl <A: BlockEncryptor> CtrMode<A> {
     /// Create a new CTR object
     pub fn new(algo: A, ctr: Vec<u8>) -> CtrMode<A> {
+        CtrMode::new_with_counter_mode(algo, ctr, CounterMode::Whole128BE)
+    }
+    /// Create a new CTR object that increments the part of the counter block selected by mode
+    pub fn new_with_counter_mode(algo: A, ctr: Vec<u8>, mode: CounterMode) -> CtrMode<A> {
         let block_size = algo.block_size();
         CtrMode {
-            algo: algo,
-            ctr: ctr,
-            bytes: OwnedReadBuffer::new_with_len(repeat(0).take(block_size).collect(), 0)
+            algo,
+            ctr,
+            mode,
+            bytes: OwnedReadBuffer::new_with_len(vec![0; block_size], 0)
         }
     }
     pub fn reset(&mut self, ctr: &[u8]) {
         cryptoutil::copy_memory(ctr, &mut self.ctr);
-        self.bytes.reset();
+        // Discard any buffered keystream so that the next block is generated from the new counter
+        self.bytes.take_remaining();
+    }
+    /// Get the counter block that will be used to generate the next block of keystream. The
+    /// underlying cipher must have a 16 byte block size.
+    pub fn counter(&self) -> [u8; 16] {
+        assert!(self.ctr.len() == 16);
+        let mut ctr = [0u8; 16];
+        cryptoutil::copy_memory(&self.ctr, &mut ctr);
+        ctr
+    }
+    /// Set the counter block, discarding any buffered keystream. The next call to process() starts
+    /// at a block boundary using the new counter. The underlying cipher must have a 16 byte block
+    /// size.
+    pub fn set_counter(&mut self, ctr: &[u8; 16]) {
+        assert!(self.ctr.len() == 16);
+        self.reset(ctr);
     }
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
         let len = input.len();
         let mut i = 0;
         while i < len {
             if self.bytes.is_empty() {
                 let mut wb = self.bytes.borrow_write_buffer();
                 self.algo.encrypt_block(&self.ctr[..], wb.take_remaining());
-                add_ctr(&mut self.ctr, 1);
+                add_ctr(self.mode.counter_bytes(&mut self.ctr), 1);
             }
             let count = cmp::min(self.bytes.remaining(), len - i);
             let bytes_it = self.bytes.take_next(count).iter();
@@ -738,35 +1055,59 @@ impl <A: BlockEncryptor> Decryptor for CtrMode<A> {
 pub struct CtrModeX8<A> {
     algo: A,
     ctr_x8: Vec<u8>,
+    mode: CounterMode,
     bytes: OwnedReadBuffer
 }
 
-fn construct_ctr_x8(in_ctr: &[u8], out_ctr_x8: &mut [u8]) {
+fn construct_ctr_parallel(in_ctr: &[u8], out_ctr_x8: &mut [u8], mode: CounterMode) {
     for (i, ctr_i) in out_ctr_x8.chunks_mut(in_ctr.len()).enumerate() {
         cryptoutil::copy_memory(in_ctr, ctr_i);
-        add_ctr(ctr_i, i as u8);
+        add_ctr(mode.counter_bytes(ctr_i), i as u8);
     }
 }
 
 impl <A: BlockEncryptorX8> CtrModeX8<A> {
     /// Create a new CTR object that operates on 8 blocks at a time
     pub fn new(algo: A, ctr: &[u8]) -> CtrModeX8<A> {
+        CtrModeX8::new_with_counter_mode(algo, ctr, CounterMode::Whole128BE)
+    }
+    /// Create a new CTR object that operates on 8 blocks at a time and increments the part of the
+    /// counter block selected by mode
+    pub fn new_with_counter_mode(algo: A, ctr: &[u8], mode: CounterMode) -> CtrModeX8<A> {
         let block_size = algo.block_size();
-        let mut ctr_x8: Vec<u8> = repeat(0).take(block_size * 8).collect();
-        construct_ctr_x8(ctr, &mut ctr_x8);
+        let mut ctr_x8 = vec![0; block_size * 8];
+        construct_ctr_parallel(ctr, &mut ctr_x8, mode);
         CtrModeX8 {
-            algo: algo,
-            ctr_x8: ctr_x8,
-            bytes: OwnedReadBuffer::new_with_len(repeat(0).take(block_size * 8).collect(), 0)
+            algo,
+            ctr_x8,
+            mode,
+            bytes: OwnedReadBuffer::new_with_len(vec![0; block_size * 8], 0)
         }
     }
     pub fn reset(&mut self, ctr: &[u8]) {
-        construct_ctr_x8(ctr, &mut self.ctr_x8);
-        self.bytes.reset();
+        construct_ctr_parallel(ctr, &mut self.ctr_x8, self.mode);
+        // Discard any buffered keystream so that the next block is generated from the new counter
+        self.bytes.take_remaining();
+    }
+    /// Get the counter block that will be used to generate the next block of keystream. The
+    /// underlying cipher must have a 16 byte block size.
+    pub fn counter(&self) -> [u8; 16] {
+        assert!(self.algo.block_size() == 16);
+        let mut ctr = [0u8; 16];
+        cryptoutil::copy_memory(&self.ctr_x8[..16], &mut ctr);
+        ctr
+    }
+    /// Set the counter block, discarding any buffered keystream. The next call to process() starts
+    /// at a block boundary using the new counter. The underlying cipher must have a 16 byte block
+    /// size.
+    pub fn set_counter(&mut self, ctr: &[u8; 16]) {
+        assert!(self.algo.block_size() == 16);
+        self.reset(ctr);
     }
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         // TODO - Can some of this be combined with regular CtrMode?
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
         let len = input.len();
         let mut i = 0;
         while i < len {
@@ -774,7 +1115,7 @@ impl <A: BlockEncryptorX8> CtrModeX8<A> {
                 let mut wb = self.bytes.borrow_write_buffer();
                 self.algo.encrypt_block_x8(&self.ctr_x8[..], wb.take_remaining());
                 for ctr_i in &mut self.ctr_x8.chunks_mut(self.algo.block_size()) {
-                    add_ctr(ctr_i, 8);
+                    add_ctr(self.mode.counter_bytes(ctr_i), 8);
                 }
             }
             let count = cmp::min(self.bytes.remaining(), len - i);
@@ -809,19 +1150,98 @@ impl <A: BlockEncryptorX8> Decryptor for CtrModeX8<A> {
     }
 }
 
+/// CTR Mode that operates on 16 blocks at a time
+pub struct CtrModeX16<A> {
+    algo: A,
+    ctr_x16: Vec<u8>,
+    mode: CounterMode,
+    bytes: OwnedReadBuffer
+}
+
+impl <A: BlockEncryptorX16> CtrModeX16<A> {
+    /// Create a new CTR object that operates on 16 blocks at a time
+    pub fn new(algo: A, ctr: &[u8]) -> CtrModeX16<A> {
+        CtrModeX16::new_with_counter_mode(algo, ctr, CounterMode::Whole128BE)
+    }
+    /// Create a new CTR object that operates on 16 blocks at a time and increments the part of the
+    /// counter block selected by mode
+    pub fn new_with_counter_mode(algo: A, ctr: &[u8], mode: CounterMode) -> CtrModeX16<A> {
+        let block_size = algo.block_size();
+        let mut ctr_x16 = vec![0; block_size * 16];
+        construct_ctr_parallel(ctr, &mut ctr_x16, mode);
+        CtrModeX16 {
+            algo,
+            ctr_x16,
+            mode,
+            bytes: OwnedReadBuffer::new_with_len(vec![0; block_size * 16], 0)
+        }
+    }
+    pub fn reset(&mut self, ctr: &[u8]) {
+        construct_ctr_parallel(ctr, &mut self.ctr_x16, self.mode);
+        // Discard any buffered keystream so that the next block is generated from the new counter
+        self.bytes.take_remaining();
+    }
+    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
+        let len = input.len();
+        let mut i = 0;
+        while i < len {
+            if self.bytes.is_empty() {
+                let mut wb = self.bytes.borrow_write_buffer();
+                self.algo.encrypt_block_x16(&self.ctr_x16[..], wb.take_remaining());
+                for ctr_i in &mut self.ctr_x16.chunks_mut(self.algo.block_size()) {
+                    add_ctr(self.mode.counter_bytes(ctr_i), 16);
+                }
+            }
+            let count = cmp::min(self.bytes.remaining(), len - i);
+            let bytes_it = self.bytes.take_next(count).iter();
+            let in_it = input[i..].iter();
+            let out_it = &mut output[i..];
+            for ((&x, &y), o) in bytes_it.zip(in_it).zip(out_it.iter_mut()) {
+                *o = x ^ y;
+            }
+            i += count;
+        }
+    }
+}
+
+impl <A: BlockEncryptorX16> SynchronousStreamCipher for CtrModeX16<A> {
+    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+        self.process(input, output);
+    }
+}
+
+impl <A: BlockEncryptorX16> Encryptor for CtrModeX16<A> {
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+impl <A: BlockEncryptorX16> Decryptor for CtrModeX16<A> {
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::iter::repeat;
 
     use aessafe;
     use blockmodes::{EcbEncryptor, EcbDecryptor, CbcEncryptor, CbcDecryptor, CtrMode, CtrModeX8,
-        NoPadding, PkcsPadding};
+        CtrModeX16, CounterMode, NoPadding, PkcsPadding, Iso7816Padding, AnsiX923Padding,
+        PaddingProcessor, XexEncryptor, XexDecryptor};
     use buffer::{ReadBuffer, WriteBuffer, RefReadBuffer, RefWriteBuffer, BufferResult};
     use buffer::BufferResult::{BufferUnderflow, BufferOverflow};
-    use symmetriccipher::{Encryptor, Decryptor};
-    use symmetriccipher::SymmetricCipherError::{self, InvalidLength, InvalidPadding};
+    use symmetriccipher::{BlockEncryptor, Encryptor, Decryptor};
+    use symmetriccipher::SymmetricCipherError::{self, AuthenticationFailed, InvalidLength,
+        InvalidPadding};
 
     use std::cmp;
+    use serialize::hex::FromHex;
 
     trait CipherTest {
         fn get_plain<'a>(&'a self) -> &'a [u8];
@@ -859,6 +1279,23 @@ mod test {
         }
     }
 
+    struct XexTest {
+        key: Vec<u8>,
+        tweak_key: Vec<u8>,
+        tweak: Vec<u8>,
+        plain: Vec<u8>,
+        cipher: Vec<u8>
+    }
+
+    impl CipherTest for XexTest {
+        fn get_plain(&self) -> &[u8] {
+            &self.plain[..]
+        }
+        fn get_cipher(&self) -> &[u8] {
+            &self.cipher[..]
+        }
+    }
+
     struct CtrTest {
         key: Vec<u8>,
         ctr: Vec<u8>,
@@ -1089,7 +1526,8 @@ mod test {
                     out_pos += tmp_out.position();
                 }
                 Err(InvalidPadding) => panic!("Invalid Padding"),
-                Err(InvalidLength) => panic!("Invalid Length")
+                Err(InvalidLength) => panic!("Invalid Length"),
+                Err(AuthenticationFailed) => panic!("Authentication Failed")
             }
         }
 
@@ -1113,7 +1551,8 @@ mod test {
                     out_pos += tmp_out.position();
                 }
                 Err(InvalidPadding) => panic!("Invalid Padding"),
-                Err(InvalidLength) => panic!("Invalid Length")
+                Err(InvalidLength) => panic!("Invalid Length"),
+                Err(AuthenticationFailed) => panic!("Authentication Failed")
             }
         }
     }
@@ -1252,6 +1691,265 @@ mod test {
         }
     }
 
+    #[cfg(debug_assertions)]
+    #[test]
+    fn aes_ecb_repeated_blocks() {
+        fn encrypt<X: PaddingProcessor>(
+                enc: &mut EcbEncryptor<aessafe::AesSafe128Encryptor, X>, data: &[u8], eof: bool) {
+            let mut output = [0u8; 64];
+            enc.encrypt(&mut RefReadBuffer::new(data), &mut RefWriteBuffer::new(&mut output), eof)
+                .unwrap();
+        }
+
+        let key = [0u8; 16];
+        let mut distinct = [0u8; 48];
+        for (i, x) in distinct.iter_mut().enumerate() {
+            *x = i as u8;
+        }
+        let mut repeated = distinct;
+        repeated[32..].copy_from_slice(&distinct[..16]);
+
+        let mut enc = EcbEncryptor::new(aessafe::AesSafe128Encryptor::new(&key), NoPadding);
+        encrypt(&mut enc, &distinct, true);
+        assert!(!enc.repeated_blocks());
+
+        enc.reset();
+        encrypt(&mut enc, &repeated, true);
+        assert!(enc.repeated_blocks());
+
+        // Resetting starts a new encryption
+        enc.reset();
+        assert!(!enc.repeated_blocks());
+
+        // Repeats are found across calls to encrypt, and the padding block counts too: 16 bytes of
+        // 0x10 followed by PKCS padding is two identical blocks.
+        let mut enc = EcbEncryptor::new(aessafe::AesSafe128Encryptor::new(&key), PkcsPadding);
+        encrypt(&mut enc, &[0x10; 8], false);
+        encrypt(&mut enc, &[0x10; 8], false);
+        assert!(!enc.repeated_blocks());
+        encrypt(&mut enc, &[], true);
+        assert!(enc.repeated_blocks());
+    }
+
+    // Encrypt a single block with no padding and then try to decrypt it with the given padding
+    fn decrypt_padded_block<X: PaddingProcessor>(plain: &[u8], padding: X)
+            -> Result<Vec<u8>, SymmetricCipherError> {
+        let key = [0u8; 16];
+        let mut cipher = [0u8; 16];
+        aessafe::AesSafe128Encryptor::new(&key).encrypt_block(plain, &mut cipher);
+
+        let mut dec = EcbDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), padding);
+        let mut output = [0u8; 16];
+        let len = {
+            let mut read_buffer = RefReadBuffer::new(&cipher);
+            let mut write_buffer = RefWriteBuffer::new(&mut output);
+            dec.decrypt(&mut read_buffer, &mut write_buffer, true)?;
+            write_buffer.position()
+        };
+        Ok(output[..len].to_vec())
+    }
+
+    fn is_invalid_padding<X: PaddingProcessor>(plain: &[u8], padding: X) -> bool {
+        match decrypt_padded_block(plain, padding) {
+            Err(InvalidPadding) => true,
+            _ => false
+        }
+    }
+
+    #[test]
+    fn aes_ecb_pkcs_padding_validation() {
+        let mut block = [7u8; 16];
+        block[..12].copy_from_slice(b"hello world!");
+        block[12..].copy_from_slice(&[4, 4, 4, 4]);
+        assert!(decrypt_padded_block(&block, PkcsPadding).unwrap() == b"hello world!");
+
+        // A full block of padding
+        assert!(decrypt_padded_block(&[16u8; 16], PkcsPadding).unwrap().is_empty());
+
+        // A wrong byte within the padding
+        block[13] = 3;
+        assert!(is_invalid_padding(&block, PkcsPadding));
+
+        // A pad length of 0
+        block[15] = 0;
+        assert!(is_invalid_padding(&block, PkcsPadding));
+
+        // A pad length greater than the block size
+        assert!(is_invalid_padding(&[17u8; 16], PkcsPadding));
+        assert!(is_invalid_padding(&[0xffu8; 16], PkcsPadding));
+    }
+
+    // Decrypt 20 bytes of ciphertext, which isn't a multiple of the block size, split into two calls
+    // at split. The final flag is only set on the second call.
+    fn decrypt_partial_block<D: Decryptor>(dec: &mut D, split: usize)
+            -> Result<BufferResult, SymmetricCipherError> {
+        let cipher = [0x5au8; 20];
+        let mut output = [0u8; 64];
+        let mut write_buffer = RefWriteBuffer::new(&mut output);
+        dec.decrypt(&mut RefReadBuffer::new(&cipher[..split]), &mut write_buffer, false)?;
+        dec.decrypt(&mut RefReadBuffer::new(&cipher[split..]), &mut write_buffer, true)
+    }
+
+    #[test]
+    fn aes_decrypt_partial_block() {
+        let key = [0u8; 16];
+        let iv = vec![0u8; 16];
+        for &split in [0, 4, 16, 20].iter() {
+            let mut dec = EcbDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), NoPadding);
+            assert!(matches!(decrypt_partial_block(&mut dec, split), Err(InvalidLength)));
+            // The error is sticky until the decryptor is reset
+            let mut output = [0u8; 16];
+            assert!(matches!(dec.decrypt(&mut RefReadBuffer::new(&[]),
+                &mut RefWriteBuffer::new(&mut output), true), Err(InvalidLength)));
+
+            let mut dec = EcbDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), PkcsPadding);
+            assert!(matches!(decrypt_partial_block(&mut dec, split), Err(InvalidLength)));
+
+            let mut dec = CbcDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), NoPadding,
+                iv.clone());
+            assert!(matches!(decrypt_partial_block(&mut dec, split), Err(InvalidLength)));
+
+            let mut dec = CbcDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), PkcsPadding,
+                iv.clone());
+            assert!(matches!(decrypt_partial_block(&mut dec, split), Err(InvalidLength)));
+        }
+    }
+
+    // Encrypt and decrypt every message length up to a little over two blocks with ECB and CBC mode
+    // and check the padded length of the ciphertext.
+    fn padding_round_trip<X: PaddingProcessor + Copy>(padding: X) {
+        let key = [1u8; 16];
+        let iv = [2u8; 16];
+        for len in 0..40 {
+            let plain: Vec<u8> = (0..len as u8).collect();
+            let padded_len = (len / 16 + 1) * 16;
+            let mut cipher = vec![0u8; padded_len];
+            let mut output = vec![0u8; padded_len];
+
+            let mut enc = EcbEncryptor::new(aessafe::AesSafe128Encryptor::new(&key), padding);
+            let mut dec = EcbDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), padding);
+            run_padded_round_trip(&plain, &mut cipher, &mut output, &mut enc, &mut dec);
+
+            let mut enc = CbcEncryptor::new(aessafe::AesSafe128Encryptor::new(&key), padding,
+                iv.to_vec());
+            let mut dec = CbcDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), padding,
+                iv.to_vec());
+            run_padded_round_trip(&plain, &mut cipher, &mut output, &mut enc, &mut dec);
+        }
+    }
+
+    fn run_padded_round_trip<E: Encryptor, D: Decryptor>(
+            plain: &[u8],
+            cipher: &mut [u8],
+            output: &mut [u8],
+            enc: &mut E,
+            dec: &mut D) {
+        {
+            let mut read_buffer = RefReadBuffer::new(plain);
+            let mut write_buffer = RefWriteBuffer::new(cipher);
+            enc.encrypt(&mut read_buffer, &mut write_buffer, true).unwrap();
+            assert!(write_buffer.is_full());
+        }
+        let len = {
+            let mut read_buffer = RefReadBuffer::new(cipher);
+            let mut write_buffer = RefWriteBuffer::new(output);
+            dec.decrypt(&mut read_buffer, &mut write_buffer, true).unwrap();
+            write_buffer.position()
+        };
+        assert!(&output[..len] == plain);
+    }
+
+    #[test]
+    fn aes_iso7816_padding() {
+        padding_round_trip(Iso7816Padding);
+
+        let mut block = [0u8; 16];
+        block[..12].copy_from_slice(b"hello world!");
+        block[12] = 0x80;
+        assert!(decrypt_padded_block(&block, Iso7816Padding).unwrap() == b"hello world!");
+
+        // The marker must be 0x80
+        block[12] = 0x81;
+        assert!(is_invalid_padding(&block, Iso7816Padding));
+
+        // The bytes after the marker must be zero
+        block[12] = 0x80;
+        block[14] = 1;
+        assert!(is_invalid_padding(&block, Iso7816Padding));
+
+        // There must be a marker at all
+        assert!(is_invalid_padding(&[0u8; 16], Iso7816Padding));
+    }
+
+    #[test]
+    fn aes_ansi_x923_padding() {
+        padding_round_trip(AnsiX923Padding);
+
+        let mut block = [0u8; 16];
+        block[..12].copy_from_slice(b"hello world!");
+        block[15] = 4;
+        assert!(decrypt_padded_block(&block, AnsiX923Padding).unwrap() == b"hello world!");
+
+        // The padding bytes before the length must be zero
+        block[13] = 4;
+        assert!(is_invalid_padding(&block, AnsiX923Padding));
+
+        // A pad length of 0
+        block[13] = 0;
+        block[15] = 0;
+        assert!(is_invalid_padding(&block, AnsiX923Padding));
+
+        // A pad length greater than the block size
+        let mut block = [0u8; 16];
+        block[15] = 17;
+        assert!(is_invalid_padding(&block, AnsiX923Padding));
+    }
+
+    #[test]
+    fn aes_cbc_resume() {
+        let key = [1u8; 16];
+        let iv = [2u8; 16];
+        let plain: Vec<u8> = (0..100).collect();
+
+        let mut expected = vec![0u8; 112];
+        {
+            let mut enc = CbcEncryptor::new(aessafe::AesSafe128Encryptor::new(&key), PkcsPadding,
+                iv.to_vec());
+            let mut read_buffer = RefReadBuffer::new(&plain);
+            let mut write_buffer = RefWriteBuffer::new(&mut expected);
+            enc.encrypt(&mut read_buffer, &mut write_buffer, true).unwrap();
+            assert!(write_buffer.is_full());
+        }
+
+        for &split in [0, 10, 16, 48, 50, 99].iter() {
+            let mut output = vec![0u8; 112];
+
+            // Encrypt the first part of the message and save the state. Encryption is then resumed
+            // from the plaintext corresponding to the ciphertext that was written.
+            let (written, saved_iv) = {
+                let mut enc = CbcEncryptor::new(aessafe::AesSafe128Encryptor::new(&key),
+                    PkcsPadding, iv.to_vec());
+                assert!(enc.current_iv() == iv);
+                let mut read_buffer = RefReadBuffer::new(&plain[..split]);
+                let mut write_buffer = RefWriteBuffer::new(&mut output);
+                match enc.encrypt(&mut read_buffer, &mut write_buffer, false) {
+                    Ok(BufferUnderflow) => {}
+                    _ => panic!("Expected BufferUnderflow")
+                }
+                (write_buffer.position(), enc.current_iv().to_vec())
+            };
+            assert!(written % 16 == 0);
+
+            let mut enc = CbcEncryptor::from_state(aessafe::AesSafe128Encryptor::new(&key),
+                PkcsPadding, &saved_iv);
+            let mut read_buffer = RefReadBuffer::new(&plain[written..]);
+            let mut write_buffer = RefWriteBuffer::new(&mut output[written..]);
+            enc.encrypt(&mut read_buffer, &mut write_buffer, true).unwrap();
+            assert!(write_buffer.is_full());
+            assert!(output == expected);
+        }
+    }
+
     #[test]
     fn aes_cbc_no_padding() {
         let tests = aes_cbc_no_padding_tests();
@@ -1269,6 +1967,90 @@ mod test {
         }
     }
 
+    // The ciphertexts are the XTS-AES-128 encryptions of the same data. For block aligned data,
+    // XTS is exactly XEX. The first two are vectors 1 and 2 of IEEE 1619-2007.
+    fn aes_xex_tests() -> Vec<XexTest> {
+        vec![
+            XexTest {
+                key: vec![0; 16],
+                tweak_key: vec![0; 16],
+                tweak: vec![0; 16],
+                plain: vec![0; 32],
+                cipher: "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e"
+                    .from_hex().unwrap()
+            },
+            XexTest {
+                key: vec![0x11; 16],
+                tweak_key: vec![0x22; 16],
+                tweak: "33333333330000000000000000000000".from_hex().unwrap(),
+                plain: vec![0x44; 32],
+                cipher: "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0"
+                    .from_hex().unwrap()
+            },
+            XexTest {
+                key: (0..16).collect(),
+                tweak_key: (16..32).collect(),
+                tweak: "9a785634120000000000000000000000".from_hex().unwrap(),
+                plain: (0..128).map(|i| (i * 7) as u8).collect(),
+                cipher: "201ba422981f3569fc228f18402489ec81afd14c85d7f9695404cbdfae213528\
+                         37a9ef6e3ef5bb4961eb2b80fccf073e08638440458d40d5dbdb554ffca1081a\
+                         64880a836132357ee1cc2464943eac2e574f884400777a9ceed8214c9293d8ee\
+                         5592222c9f119b19a4fe3fc2e72c379d3459017ab4f8d2446c3822f3b29dafbb"
+                    .from_hex().unwrap()
+            }
+        ]
+    }
+
+    #[test]
+    fn aes_xex() {
+        for test in aes_xex_tests().iter() {
+            let tweak_cipher = aessafe::AesSafe128Encryptor::new(&test.tweak_key[..]);
+            run_test(
+                test,
+                || {
+                    let aes_enc = aessafe::AesSafe128Encryptor::new(&test.key[..]);
+                    XexEncryptor::new(aes_enc, &tweak_cipher, &test.tweak[..])
+                },
+                || {
+                    let aes_dec = aessafe::AesSafe128Decryptor::new(&test.key[..]);
+                    XexDecryptor::new(aes_dec, &tweak_cipher, &test.tweak[..])
+                });
+        }
+    }
+
+    #[test]
+    fn aes_xex_reset() {
+        let tests = aes_xex_tests();
+        let (first, second) = (&tests[1], &tests[2]);
+        let mut enc = XexEncryptor::new(
+            aessafe::AesSafe128Encryptor::new(&second.key[..]),
+            &aessafe::AesSafe128Encryptor::new(&second.tweak_key[..]),
+            &first.tweak[..]);
+        let mut output = [0u8; 128];
+        enc.encrypt(&mut RefReadBuffer::new(&first.plain), &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+
+        // Resetting starts over with the new tweak
+        enc.reset(&aessafe::AesSafe128Encryptor::new(&second.tweak_key[..]), &second.tweak[..]);
+        {
+            let mut write_buffer = RefWriteBuffer::new(&mut output);
+            enc.encrypt(&mut RefReadBuffer::new(&second.plain), &mut write_buffer, true).unwrap();
+            assert!(write_buffer.is_full());
+        }
+        assert!(output[..] == second.cipher[..]);
+    }
+
+    #[test]
+    fn aes_xex_partial_block() {
+        let key = [0u8; 16];
+        let tweak_cipher = aessafe::AesSafe128Encryptor::new(&key);
+        for &split in [0, 4, 16, 20].iter() {
+            let mut dec = XexDecryptor::new(aessafe::AesSafe128Decryptor::new(&key), &tweak_cipher,
+                &key);
+            assert!(matches!(decrypt_partial_block(&mut dec, split), Err(InvalidLength)));
+        }
+    }
+
     #[test]
     fn aes_cbc_pkcs_padding() {
         let tests = aes_cbc_pkcs_padding_tests();
@@ -1286,6 +2068,66 @@ mod test {
         }
     }
 
+    #[test]
+    fn aes_encrypt_all_length() {
+        // Over-allocate as callers who don't know the padded length do, and check that the
+        // reported length is exactly the padded ciphertext, and then exactly the plaintext
+        for test in aes_ecb_pkcs_padding_tests().iter() {
+            let mut enc = EcbEncryptor::new(
+                aessafe::AesSafe128Encryptor::new(&test.key[..]), PkcsPadding);
+            let mut cipher_out = vec![0; test.plain.len() * 4];
+            let cipher_len = enc.encrypt_all(&test.plain, &mut cipher_out).unwrap();
+            assert_eq!(cipher_len, test.cipher.len());
+            assert!(cipher_out[..cipher_len] == test.cipher[..]);
+
+            let mut dec = EcbDecryptor::new(
+                aessafe::AesSafe128Decryptor::new(&test.key[..]), PkcsPadding);
+            let mut plain_out = vec![0; cipher_len];
+            let plain_len = dec.decrypt_all(&cipher_out[..cipher_len], &mut plain_out).unwrap();
+            assert_eq!(plain_len, test.plain.len());
+            assert!(plain_out[..plain_len] == test.plain[..]);
+        }
+
+        for test in aes_cbc_pkcs_padding_tests().iter() {
+            let mut enc = CbcEncryptor::new(
+                aessafe::AesSafe128Encryptor::new(&test.key[..]), PkcsPadding, test.iv.clone());
+            let mut cipher_out = vec![0; test.plain.len() + 16];
+            let cipher_len = enc.encrypt_all(&test.plain, &mut cipher_out).unwrap();
+            assert_eq!(cipher_len, test.cipher.len());
+            assert!(cipher_out[..cipher_len] == test.cipher[..]);
+
+            let mut dec = CbcDecryptor::new(
+                aessafe::AesSafe128Decryptor::new(&test.key[..]), PkcsPadding, test.iv.clone());
+            let mut plain_out = vec![0; cipher_len];
+            let plain_len = dec.decrypt_all(&cipher_out[..cipher_len], &mut plain_out).unwrap();
+            assert_eq!(plain_len, test.plain.len());
+            assert!(plain_out[..plain_len] == test.plain[..]);
+        }
+
+        // A partial final block without padding is still an error
+        let mut enc = EcbEncryptor::new(aessafe::AesSafe128Encryptor::new(&[0; 16]), NoPadding);
+        let mut out = [0u8; 64];
+        assert!(enc.encrypt_all(&[0; 17], &mut out).is_err());
+    }
+
+    #[test]
+    fn aes_encrypt_all_output_too_small() {
+        // The padding needs a third block
+        let mut enc = EcbEncryptor::new(aessafe::AesSafe128Encryptor::new(&[0; 16]), PkcsPadding);
+        let mut out = [0u8; 32];
+        match enc.encrypt_all(&[0; 32], &mut out) {
+            Err(InvalidLength) => {}
+            _ => panic!("Expected InvalidLength")
+        }
+
+        let mut dec = EcbDecryptor::new(aessafe::AesSafe128Decryptor::new(&[0; 16]), NoPadding);
+        let mut out = [0u8; 16];
+        match dec.decrypt_all(&[0; 32], &mut out) {
+            Err(InvalidLength) => {}
+            _ => panic!("Expected InvalidLength")
+        }
+    }
+
     #[test]
     fn aes_ctr() {
         let tests = aes_ctr_tests();
@@ -1319,6 +2161,138 @@ mod test {
                 });
         }
     }
+
+    #[test]
+    fn aes_ctr_x16() {
+        let tests = aes_ctr_tests();
+        for test in tests.iter() {
+            run_test(
+                test,
+                || {
+                    let aes_enc = aessafe::AesSafe128EncryptorX16::new(&test.key[..]);
+                    CtrModeX16::new(aes_enc, &test.ctr[..])
+                },
+                || {
+                    let aes_enc = aessafe::AesSafe128EncryptorX16::new(&test.key[..]);
+                    CtrModeX16::new(aes_enc, &test.ctr[..])
+                });
+        }
+    }
+
+    #[test]
+    fn aes_ctr_set_counter() {
+        let key = [1u8; 16];
+        let ctr = [3u8; 16];
+        let input = [2u8; 33];
+
+        let mut expected = [0u8; 33];
+        CtrMode::new(aessafe::AesSafe128Encryptor::new(&key), ctr.to_vec())
+            .process(&input, &mut expected);
+
+        // Leave part of a block of keystream buffered before resetting the counter
+        let mut ctr_mode = CtrMode::new(aessafe::AesSafe128Encryptor::new(&key), ctr.to_vec());
+        let mut output = [0u8; 33];
+        ctr_mode.process(&input[..20], &mut output[..20]);
+        ctr_mode.set_counter(&ctr);
+        assert!(ctr_mode.counter() == ctr);
+        ctr_mode.process(&input, &mut output);
+        assert!(output[..] == expected[..]);
+
+        // The counter advances by one for every block of keystream generated
+        let mut next = ctr;
+        next[15] += 3;
+        assert!(ctr_mode.counter() == next);
+    }
+
+    #[test]
+    fn aes_ctr_x8_set_counter() {
+        let key = [1u8; 16];
+        let ctr = [3u8; 16];
+        let input = [2u8; 300];
+
+        let mut expected = [0u8; 300];
+        CtrModeX8::new(aessafe::AesSafe128EncryptorX8::new(&key), &ctr)
+            .process(&input, &mut expected);
+
+        let mut ctr_mode = CtrModeX8::new(aessafe::AesSafe128EncryptorX8::new(&key), &ctr);
+        let mut output = [0u8; 300];
+        ctr_mode.process(&input[..20], &mut output[..20]);
+        ctr_mode.set_counter(&ctr);
+        assert!(ctr_mode.counter() == ctr);
+        ctr_mode.process(&input, &mut output);
+        assert!(output[..] == expected[..]);
+    }
+
+    // Start from a counter block whose last 8 bytes are all 0xff and check the counter block used
+    // for the second block of keystream.
+    fn ctr_wraparound(mode: CounterMode, expected_next: [u8; 16]) {
+        let key = [1u8; 16];
+        let mut ctr = [0x11u8; 16];
+        for b in ctr[8..].iter_mut() {
+            *b = 0xff;
+        }
+
+        let aes_enc = aessafe::AesSafe128Encryptor::new(&key);
+        let mut expected = [0u8; 16];
+        aes_enc.encrypt_block(&expected_next, &mut expected);
+
+        let mut ctr_mode = CtrMode::new_with_counter_mode(aes_enc, ctr.to_vec(), mode);
+        let mut output = [0u8; 32];
+        ctr_mode.process(&[0u8; 32], &mut output);
+        assert!(output[16..] == expected[..]);
+
+        // CtrModeX8 and CtrModeX16 must wrap at the same boundary, including within a batch of
+        // blocks
+        let input = [0u8; 256];
+        let mut output = [0u8; 256];
+        let mut output_x8 = [0u8; 256];
+        let mut start = ctr;
+        start[15] = 0xfd;
+        CtrMode::new_with_counter_mode(aessafe::AesSafe128Encryptor::new(&key), start.to_vec(),
+            mode).process(&input, &mut output);
+        CtrModeX8::new_with_counter_mode(aessafe::AesSafe128EncryptorX8::new(&key), &start, mode)
+            .process(&input, &mut output_x8);
+        assert!(output[..] == output_x8[..]);
+
+        let mut output_x16 = [0u8; 256];
+        CtrModeX16::new_with_counter_mode(aessafe::AesSafe128EncryptorX16::new(&key), &start, mode)
+            .process(&input, &mut output_x16);
+        assert!(output[..] == output_x16[..]);
+    }
+
+    #[test]
+    fn aes_ctr_whole_128_be_wraparound() {
+        // The carry propagates into the upper half of the block
+        let mut next = [0u8; 16];
+        for b in next[..8].iter_mut() {
+            *b = 0x11;
+        }
+        next[7] = 0x12;
+        ctr_wraparound(CounterMode::Whole128BE, next);
+    }
+
+    #[test]
+    fn aes_ctr_low_64_be_wraparound() {
+        // The low 64 bits wrap to zero without changing the upper half of the block
+        let mut next = [0u8; 16];
+        for b in next[..8].iter_mut() {
+            *b = 0x11;
+        }
+        ctr_wraparound(CounterMode::Low64BE, next);
+    }
+
+    #[test]
+    fn aes_ctr_low_32_be_wraparound() {
+        // The low 32 bits wrap to zero without carrying into bytes 8 to 11
+        let mut next = [0u8; 16];
+        for b in next[..8].iter_mut() {
+            *b = 0x11;
+        }
+        for b in next[8..12].iter_mut() {
+            *b = 0xff;
+        }
+        ctr_wraparound(CounterMode::Low32BE, next);
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
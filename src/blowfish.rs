@@ -34,6 +34,23 @@ impl Blowfish {
         blowfish
     }
 
+    /// Create a Blowfish state using the expensive key schedule ("EksBlowfish") from "A
+    /// Future-Adaptable Password Scheme" by Niels Provos and David Mazieres, which is the core of
+    /// bcrypt. After the salted key expansion, the key and then the salt are expanded into the
+    /// state 2^cost times.
+    pub fn new_eks(cost: u32, salt: &[u8], key: &[u8]) -> Blowfish {
+        assert!(cost < 32);
+        let mut state = Blowfish::init_state();
+
+        state.salted_expand_key(salt, key);
+        for _ in 0..1u32 << cost {
+            state.expand_key(key);
+            state.expand_key(salt);
+        }
+
+        state
+    }
+
     // For bcrypt. Use Blowfish::new instead.
     pub fn init_state() -> Blowfish {
         Blowfish {
@@ -534,6 +551,29 @@ mod test {
         }
     }
 
+    #[test]
+    fn eks_blowfish() {
+        // The first openwall bcrypt test vector. Encrypting "OrpheanBeholderScryDoubt" 64 times
+        // with the EksBlowfish state must produce the bcrypt hash, which only includes 23 bytes.
+        let key = [0x55u8, 0x2a, 0x55, 0x00];
+        let salt = [0x10u8, 0x41, 0x04, 0x10, 0x41, 0x04, 0x10, 0x41,
+                    0x04, 0x10, 0x41, 0x04, 0x10, 0x41, 0x04, 0x10];
+        let expected = [0x1bu8, 0xb6, 0x91, 0x43, 0xf9, 0xa8, 0xd3, 0x04,
+                        0xc8, 0xd2, 0x3d, 0x99, 0xab, 0x04, 0x9a, 0x77,
+                        0xa6, 0x8e, 0x2c, 0xcc, 0x74, 0x42, 0x06];
+
+        let state = Blowfish::new_eks(5, &salt, &key);
+        let mut output = *b"OrpheanBeholderScryDoubt";
+        for block in output.chunks_mut(8) {
+            for _ in 0..64 {
+                let mut tmp = [0u8; 8];
+                state.encrypt_block(block, &mut tmp);
+                block.copy_from_slice(&tmp);
+            }
+        }
+        assert!(output[..23] == expected[..]);
+    }
+
     #[test]
     fn decrypt_eay_test_vectors() {
         let tests = eay_test_vectors();
@@ -164,6 +164,20 @@ impl <'a> RefWriteBuffer<'a> {
             pos: 0
         }
     }
+
+    /// Like take_read_buffer(), but only takes the first count bytes that have been written,
+    /// clamped to position(). The written bytes after those are moved to the start of the buffer
+    /// and stay written, so the next writes append to them.
+    pub fn take_read_buffer_up_to<'b>(&'b mut self, count: usize) -> RefReadBuffer<'b> {
+        let count = cmp::min(count, self.pos);
+        let kept = self.pos - count;
+
+        // Rotate the taken bytes behind the kept ones so that both stay intact: the kept bytes
+        // become the new start of the buffer and the taken bytes sit where the next writes go.
+        self.buff[..self.pos].rotate_left(count);
+        self.pos = kept;
+        RefReadBuffer::new(&self.buff[kept..kept + count])
+    }
 }
 
 impl <'a> WriteBuffer for RefWriteBuffer<'a> {
@@ -286,3 +300,44 @@ impl WriteBuffer for OwnedWriteBuffer {
         r
     }
 }
+
+#[cfg(test)]
+mod test {
+    use buffer::{ReadBuffer, WriteBuffer, RefWriteBuffer};
+
+    #[test]
+    fn test_take_read_buffer_up_to() {
+        let mut storage = [0u8; 8];
+        let mut out = Vec::new();
+        let mut next = 0u8;
+        {
+            let mut wb = RefWriteBuffer::new(&mut storage);
+            for &(written, taken) in [(5, 2), (3, 4), (4, 1), (0, 3), (2, 10)].iter() {
+                for b in wb.take_next(written).iter_mut() {
+                    *b = next;
+                    next += 1;
+                }
+                let before = wb.position();
+                let mut rb = wb.take_read_buffer_up_to(taken);
+                assert_eq!(rb.remaining(), if taken < before { taken } else { before });
+                out.extend_from_slice(rb.take_remaining());
+            }
+            assert!(wb.is_empty());
+        }
+        let expected: Vec<u8> = (0..next).collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_take_read_buffer_up_to_keeps_remaining_space() {
+        let mut storage = [0u8; 4];
+        let mut wb = RefWriteBuffer::new(&mut storage);
+        wb.take_next(3).copy_from_slice(&[1, 2, 3]);
+        assert!(wb.take_read_buffer_up_to(1).take_remaining() == [1]);
+        assert_eq!(wb.position(), 2);
+        assert_eq!(wb.remaining(), 2);
+        wb.take_next(2).copy_from_slice(&[4, 5]);
+        assert!(wb.is_full());
+        assert!(wb.take_read_buffer().take_remaining() == [2, 3, 4, 5]);
+    }
+}
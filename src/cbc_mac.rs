@@ -0,0 +1,208 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements CBC-MAC - a Message Authentication Code computed by encrypting the message
+ * with a block cipher in CBC mode using an all zero IV and taking the final ciphertext block.
+ *
+ * # Security
+ *
+ * CBC-MAC is only secure when every message authenticated under a given key has the same length,
+ * or when the length of the message is unambiguously encoded at its start (for example, by a fixed
+ * size length prefix). Used with variable length messages it is trivially forgeable: given the tags
+ * of two messages an attacker can compute a valid tag for their concatenation without knowing the
+ * key. Unless an existing protocol requires CBC-MAC, CMAC should be preferred since it is secure
+ * for messages of any length.
+ *
+ * If the message is not a multiple of the block size, the final block is padded with zero bytes
+ * (ISO/IEC 9797-1 padding method 1), and an empty message is treated as a single zero block. Since
+ * this padding is ambiguous, it is also only safe if the length of the message is otherwise fixed
+ * or encoded.
+ */
+
+use std::cmp;
+
+use cryptoutil::{copy_memory, zero};
+use mac::{Mac, MacResult};
+use symmetriccipher::BlockEncryptor;
+
+/**
+ * The CbcMac struct represents a CBC-MAC function over a BlockEncryptor. See the module
+ * documentation for the restrictions on the messages that it may safely be used with.
+ */
+pub struct CbcMac<C> {
+    cipher: C,
+    state: Vec<u8>,
+    buffer: Vec<u8>,
+    buffer_len: usize,
+    processed_any: bool,
+    finished: bool
+}
+
+impl <C: BlockEncryptor> CbcMac<C> {
+    /**
+     * Create a new CbcMac instance.
+     *
+     * # Arguments
+     * * cipher - The BlockEncryptor to use. It should already be initialized with the key.
+     *
+     */
+    pub fn new(cipher: C) -> CbcMac<C> {
+        let block_size = cipher.block_size();
+        CbcMac {
+            cipher,
+            state: vec![0; block_size],
+            buffer: vec![0; block_size],
+            buffer_len: 0,
+            processed_any: false,
+            finished: false
+        }
+    }
+
+    // XOR the buffered block into the chaining value and encrypt it.
+    fn process_block(&mut self) {
+        for (s, &b) in self.state.iter_mut().zip(self.buffer.iter()) {
+            *s ^= b;
+        }
+        let mut output = vec![0; self.state.len()];
+        self.cipher.encrypt_block(&self.state, &mut output);
+        copy_memory(&output, &mut self.state);
+        self.buffer_len = 0;
+        self.processed_any = true;
+    }
+}
+
+impl <C: BlockEncryptor> Mac for CbcMac<C> {
+    fn input(&mut self, data: &[u8]) {
+        assert!(!self.finished);
+        let block_size = self.buffer.len();
+        let mut data = data;
+        while !data.is_empty() {
+            let count = cmp::min(block_size - self.buffer_len, data.len());
+            copy_memory(&data[..count], &mut self.buffer[self.buffer_len..self.buffer_len + count]);
+            self.buffer_len += count;
+            data = &data[count..];
+            if self.buffer_len == block_size {
+                self.process_block();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        zero(&mut self.state);
+        zero(&mut self.buffer);
+        self.buffer_len = 0;
+        self.processed_any = false;
+        self.finished = false;
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut code = vec![0; self.output_bytes()];
+
+        self.raw_result(&mut code);
+
+        MacResult::new_from_owned(code)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        if !self.finished {
+            if self.buffer_len > 0 || !self.processed_any {
+                zero(&mut self.buffer[self.buffer_len..]);
+                self.process_block();
+            }
+            self.finished = true;
+        }
+
+        copy_memory(&self.state, output);
+    }
+
+    fn output_bytes(&self) -> usize { self.state.len() }
+}
+
+#[cfg(test)]
+mod test {
+    use aessafe::AesSafe128Encryptor;
+    use cbc_mac::CbcMac;
+    use mac::{Mac, MacResult};
+    use rc5::Rc5;
+    use symmetriccipher::BlockEncryptor;
+
+    // The CBC-MAC of a message is the final block of its CBC encryption under a zero IV, so these
+    // vectors are derived from the AES-128 CBC example in NIST SP 800-38A, F.2.1, by XORing its IV
+    // into the first plaintext block.
+    const KEY: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6,
+        0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c ];
+
+    const MESSAGE: [u8; 32] = [
+        0x6b, 0xc0, 0xbc, 0xe1, 0x2a, 0x45, 0x99, 0x91,
+        0xe1, 0x34, 0x74, 0x1a, 0x7f, 0x9e, 0x19, 0x25,
+        0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c,
+        0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf, 0x8e, 0x51 ];
+
+    const ONE_BLOCK_MAC: [u8; 16] = [
+        0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46,
+        0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9, 0x19, 0x7d ];
+
+    const TWO_BLOCK_MAC: [u8; 16] = [
+        0x50, 0x86, 0xcb, 0x9b, 0x50, 0x72, 0x19, 0xee,
+        0x95, 0xdb, 0x11, 0x3a, 0x91, 0x76, 0x78, 0xb2 ];
+
+    #[test]
+    fn test_cbc_mac_aes_one_block() {
+        let mut mac = CbcMac::new(AesSafe128Encryptor::new(&KEY));
+        mac.input(&MESSAGE[..16]);
+        assert!(mac.result() == MacResult::new(&ONE_BLOCK_MAC));
+    }
+
+    #[test]
+    fn test_cbc_mac_aes_two_blocks() {
+        let mut mac = CbcMac::new(AesSafe128Encryptor::new(&KEY));
+        mac.input(&MESSAGE);
+        assert!(mac.result() == MacResult::new(&TWO_BLOCK_MAC));
+
+        // Calling result() again must return the same value
+        assert!(mac.result() == MacResult::new(&TWO_BLOCK_MAC));
+
+        mac.reset();
+        for chunk in MESSAGE.chunks(3) {
+            mac.input(chunk);
+        }
+        assert!(mac.result() == MacResult::new(&TWO_BLOCK_MAC));
+    }
+
+    #[test]
+    fn test_cbc_mac_zero_padding() {
+        let cipher = AesSafe128Encryptor::new(&KEY);
+
+        // An empty message is MACed as a single zero block
+        let mut expected = [0u8; 16];
+        cipher.encrypt_block(&[0u8; 16], &mut expected);
+        let mut mac = CbcMac::new(cipher);
+        assert!(mac.result() == MacResult::new(&expected));
+
+        // A partial final block is padded with zeros
+        let mut padded = [0u8; 32];
+        padded[..20].copy_from_slice(&MESSAGE[..20]);
+        mac.reset();
+        mac.input(&padded);
+        let expected = mac.result();
+        mac.reset();
+        mac.input(&MESSAGE[..20]);
+        assert!(mac.result() == expected);
+    }
+
+    #[test]
+    fn test_cbc_mac_64_bit_block() {
+        // Any BlockEncryptor may be used, and the tag is a single block of that cipher
+        let mut mac = CbcMac::new(Rc5::new(&[0u8; 16]));
+        assert_eq!(mac.output_bytes(), 8);
+        mac.input(&[0u8; 8]);
+        let mut code = [0u8; 8];
+        mac.raw_result(&mut code);
+        assert_eq!(code, [0x21, 0xa5, 0xdb, 0xee, 0x15, 0x4b, 0x8f, 0x6d]);
+    }
+}
@@ -0,0 +1,337 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the CCM (Counter with CBC-MAC) authenticated encryption mode as specified
+ * by RFC 3610 and NIST SP 800-38C, over a block cipher with a 128 bit block size such as AES.
+ *
+ * The tag is the CBC-MAC of a first block B0, which encodes the nonce, the tag length, and the
+ * message length, followed by the length prefixed associated data and the message. The message is
+ * encrypted in CTR mode starting from counter 1, and the tag with counter block 0.
+ *
+ * The nonce is 7 to 13 bytes long. A shorter nonce leaves more room for the message length: with
+ * an n byte nonce the message may be up to 2^(8 * (15 - n)) - 1 bytes. The nonce must never be
+ * reused with the same key. The tag length, which is an even number from 4 to 16, is fixed when
+ * the Ccm is created since it is part of B0.
+ */
+
+use aead::{AeadEncryptor, AeadDecryptor};
+use blockmodes::CtrMode;
+use cbc_mac::CbcMac;
+use mac::Mac;
+use symmetriccipher::{BlockEncryptor, SymmetricCipherError, SynchronousStreamCipher};
+use util::{fixed_time_eq, secure_memset};
+
+const BLOCK_SIZE: usize = 16;
+
+/**
+ * The Ccm struct encrypts or decrypts a single message with the CCM mode.
+ */
+pub struct Ccm<C: BlockEncryptor + Clone> {
+    cipher: C,
+    nonce: Vec<u8>,
+    aad: Vec<u8>,
+    tag_len: usize,
+    finished: bool
+}
+
+impl <C: BlockEncryptor + Clone> Ccm<C> {
+    /**
+     * Create a new Ccm instance.
+     *
+     * # Arguments
+     * * cipher - The BlockEncryptor to use. It should already be initialized with the key.
+     * * nonce - The nonce, from 7 to 13 bytes long.
+     * * aad - The associated data, which is authenticated but not encrypted.
+     * * tag_len - The length of the tag in bytes: 4, 6, 8, 10, 12, 14 or 16.
+     *
+     */
+    pub fn new(cipher: C, nonce: &[u8], aad: &[u8], tag_len: usize) -> Ccm<C> {
+        assert!(cipher.block_size() == BLOCK_SIZE);
+        assert!((7..=13).contains(&nonce.len()));
+        assert!((4..=16).contains(&tag_len) && tag_len.is_multiple_of(2));
+        Ccm {
+            cipher,
+            nonce: nonce.to_vec(),
+            aad: aad.to_vec(),
+            tag_len,
+            finished: false
+        }
+    }
+
+    // The size of the message length field, L, which is also the size of the counter
+    fn length_size(&self) -> usize {
+        BLOCK_SIZE - 1 - self.nonce.len()
+    }
+
+    // The counter block A_i = flags || nonce || [i]_L, with the flags just holding L - 1
+    fn counter_block(&self, i: u64) -> [u8; BLOCK_SIZE] {
+        let l = self.length_size();
+        let mut block = [0u8; BLOCK_SIZE];
+        block[0] = (l - 1) as u8;
+        block[1..1 + self.nonce.len()].copy_from_slice(&self.nonce);
+        let counter = i.to_be_bytes();
+        let n = l.min(8);
+        block[BLOCK_SIZE - n..].copy_from_slice(&counter[8 - n..]);
+        block
+    }
+
+    // The CBC-MAC of B0, the encoded associated data, and the message, encrypted with A_0 and
+    // truncated to the tag length.
+    fn tag(&self, msg: &[u8]) -> Vec<u8> {
+        let l = self.length_size();
+
+        // B0 = flags || nonce || [len(msg)]_L
+        let mut b0 = [0u8; BLOCK_SIZE];
+        let adata = if self.aad.is_empty() { 0 } else { 0x40 };
+        b0[0] = adata | (((self.tag_len - 2) / 2) << 3) as u8 | (l - 1) as u8;
+        b0[1..1 + self.nonce.len()].copy_from_slice(&self.nonce);
+        let msg_len = (msg.len() as u64).to_be_bytes();
+        let n = l.min(8);
+        b0[BLOCK_SIZE - n..].copy_from_slice(&msg_len[8 - n..]);
+
+        let mut mac = CbcMac::new(self.cipher.clone());
+        mac.input(&b0);
+
+        // The associated data is prefixed with its length, in 2, 6 or 10 bytes depending on its
+        // size, and padded with zeros to a whole number of blocks.
+        if !self.aad.is_empty() {
+            let aad_len = self.aad.len() as u64;
+            let prefix = if aad_len < 0xff00 {
+                (aad_len as u16).to_be_bytes().to_vec()
+            } else if aad_len <= u32::MAX as u64 {
+                let mut prefix = vec![0xff, 0xfe];
+                prefix.extend_from_slice(&(aad_len as u32).to_be_bytes());
+                prefix
+            } else {
+                let mut prefix = vec![0xff, 0xff];
+                prefix.extend_from_slice(&aad_len.to_be_bytes());
+                prefix
+            };
+            mac.input(&prefix);
+            mac.input(&self.aad);
+            let used = (prefix.len() + self.aad.len()) % BLOCK_SIZE;
+            if used != 0 {
+                mac.input(&[0u8; BLOCK_SIZE][used..]);
+            }
+        }
+
+        // CbcMac pads the final partial block of the message with zeros, as CCM requires
+        mac.input(msg);
+        let mut t = [0u8; BLOCK_SIZE];
+        mac.raw_result(&mut t);
+
+        let mut s0 = [0u8; BLOCK_SIZE];
+        self.cipher.encrypt_block(&self.counter_block(0), &mut s0);
+        t.iter().zip(s0.iter()).take(self.tag_len).map(|(&t, &s)| t ^ s).collect()
+    }
+
+    // Encrypt or decrypt the message with the counter blocks from A_1.
+    fn ctr(&self, input: &[u8], output: &mut [u8]) {
+        let l = self.length_size();
+        assert!(l >= 8 || (input.len() as u64) < 1 << (8 * l), "message too long for the nonce");
+        let mut ctr = CtrMode::new(self.cipher.clone(), self.counter_block(1).to_vec());
+        ctr.process(input, output);
+    }
+}
+
+impl <C: BlockEncryptor + Clone> AeadEncryptor for Ccm<C> {
+    fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]) {
+        assert!(input.len() == output.len());
+        assert!(tag.len() == self.tag_len);
+        assert!(!self.finished);
+        self.finished = true;
+
+        self.ctr(input, output);
+        tag.copy_from_slice(&self.tag(input));
+    }
+}
+
+impl <C: BlockEncryptor + Clone> AeadDecryptor for Ccm<C> {
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+            -> Result<(), SymmetricCipherError> {
+        assert!(input.len() == output.len());
+        assert!(!self.finished);
+        self.finished = true;
+
+        // The tag covers the plaintext, so decrypt into a temporary buffer and only release it
+        // once the tag has been checked
+        let mut plaintext = vec![0; input.len()];
+        self.ctr(input, &mut plaintext);
+        let result = if tag.len() == self.tag_len && fixed_time_eq(&self.tag(&plaintext), tag) {
+            output.copy_from_slice(&plaintext);
+            Ok(())
+        } else {
+            Err(SymmetricCipherError::AuthenticationFailed)
+        };
+        secure_memset(&mut plaintext, 0);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aead::{AeadEncryptor, AeadDecryptor};
+    use aessafe::AesSafe128Encryptor;
+    use ccm::Ccm;
+    use serialize::hex::FromHex;
+    use symmetriccipher::SymmetricCipherError;
+
+    struct Test {
+        key: &'static str,
+        nonce: &'static str,
+        aad: &'static str,
+        msg: &'static str,
+        tag_len: usize,
+        cipher: &'static str
+    }
+
+    // Packet vectors 1 to 3 from section 8 of RFC 3610, followed by vectors with the shortest
+    // tag and no associated data or message, and the longest tag without associated data. The
+    // cipher field is the ciphertext followed by the tag.
+    const TESTS: [Test; 5] = [
+        Test {
+            key: "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf",
+            nonce: "00000003020100a0a1a2a3a4a5",
+            aad: "0001020304050607",
+            msg: "08090a0b0c0d0e0f101112131415161718191a1b1c1d1e",
+            tag_len: 8,
+            cipher: "588c979a61c663d2f066d0c2c0f989806d5f6b61dac38417e8d12cfdf926e0"
+        },
+        Test {
+            key: "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf",
+            nonce: "00000004030201a0a1a2a3a4a5",
+            aad: "0001020304050607",
+            msg: "08090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            tag_len: 8,
+            cipher: "72c91a36e135f8cf291ca894085c87e3cc15c439c9e43a3ba091d56e10400916"
+        },
+        Test {
+            key: "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf",
+            nonce: "00000005040302a0a1a2a3a4a5",
+            aad: "0001020304050607",
+            msg: "08090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+            tag_len: 8,
+            cipher: "51b1e5f44a197d1da46b0f8e2d282ae871e838bb64da8596574adaa76fbd9fb0c5"
+        },
+        Test {
+            key: "000102030405060708090a0b0c0d0e0f",
+            nonce: "202122232425262728292a2b",
+            aad: "",
+            msg: "",
+            tag_len: 4,
+            cipher: "2c1e95b0"
+        },
+        Test {
+            key: "000102030405060708090a0b0c0d0e0f",
+            nonce: "202122232425262728292a2b",
+            aad: "",
+            msg: "000d1a2734414e5b6875828f9ca9b6c3d0ddeaf704111e2b3845525f6c798693",
+            tag_len: 16,
+            cipher: "42473e2341e43202f390848a7301e03f2e3587ae3882b4aa51e71e7ed3a299d5\
+                     557d1b96f3cefe74f2a8335b593c6eed"
+        },
+    ];
+
+    fn new_ccm(test: &Test) -> Ccm<AesSafe128Encryptor> {
+        let key = test.key.from_hex().unwrap();
+        Ccm::new(AesSafe128Encryptor::new(&key), &test.nonce.from_hex().unwrap(),
+            &test.aad.from_hex().unwrap(), test.tag_len)
+    }
+
+    #[test]
+    fn test_ccm_encrypt() {
+        for test in TESTS.iter() {
+            let msg = test.msg.from_hex().unwrap();
+            let expected = test.cipher.from_hex().unwrap();
+            let mut output = vec![0; msg.len()];
+            let mut tag = vec![0; test.tag_len];
+            new_ccm(test).encrypt(&msg, &mut output, &mut tag);
+            assert!(output[..] == expected[..msg.len()]);
+            assert!(tag[..] == expected[msg.len()..]);
+        }
+    }
+
+    #[test]
+    fn test_ccm_decrypt() {
+        for test in TESTS.iter() {
+            let msg = test.msg.from_hex().unwrap();
+            let cipher = test.cipher.from_hex().unwrap();
+            let (ciphertext, tag) = cipher.split_at(msg.len());
+            let mut output = vec![0; msg.len()];
+            assert!(new_ccm(test).decrypt(ciphertext, &mut output, tag).is_ok());
+            assert!(output == msg);
+        }
+    }
+
+    #[test]
+    fn test_ccm_decrypt_fail() {
+        for test in TESTS.iter() {
+            let msg = test.msg.from_hex().unwrap();
+            let mut cipher = test.cipher.from_hex().unwrap();
+            let (ciphertext, tag) = cipher.split_at(msg.len());
+            let mut output = vec![0; msg.len()];
+
+            // Unlike EAX, a truncated tag is rejected since the tag length is part of B0
+            match new_ccm(test).decrypt(ciphertext, &mut output, &tag[..tag.len() - 2]) {
+                Err(SymmetricCipherError::AuthenticationFailed) => {}
+                _ => panic!("Expected AuthenticationFailed")
+            }
+
+            let last = cipher.len() - 1;
+            cipher[last] ^= 1;
+            let (ciphertext, tag) = cipher.split_at(msg.len());
+            match new_ccm(test).decrypt(ciphertext, &mut output, tag) {
+                Err(SymmetricCipherError::AuthenticationFailed) => {}
+                _ => panic!("Expected AuthenticationFailed")
+            }
+            // Nothing is decrypted if the tag doesn't match
+            assert!(output.iter().all(|&x| x == 0));
+        }
+    }
+
+    #[test]
+    fn test_ccm_long_aad() {
+        // Associated data of 0xff00 bytes or more is prefixed with 0xfffe and a 32-bit length,
+        // and a 7 byte nonce leaves an 8 byte length field. The expected value was computed with
+        // an independent implementation.
+        let key: Vec<u8> = (0..16).collect();
+        let nonce: Vec<u8> = (0x10..0x17).collect();
+        let aad: Vec<u8> = (0..0xff00).map(|i| (i * 7) as u8).collect();
+        let msg: Vec<u8> = (0..40).map(|i| (i * 13) as u8).collect();
+        let expected = "aa632775a1a8bf1c8e76754181474383ab659ff1bb284c0a480a11b02608dd7f\
+                        6ec1e0a71e6d72bd6703c66c5f0a3ecf9fa81814926202a5".from_hex().unwrap();
+
+        let mut output = vec![0; msg.len()];
+        let mut tag = [0u8; 16];
+        Ccm::new(AesSafe128Encryptor::new(&key), &nonce, &aad, 16)
+            .encrypt(&msg, &mut output, &mut tag);
+        assert!(output[..] == expected[..msg.len()]);
+        assert!(tag[..] == expected[msg.len()..]);
+
+        let mut decrypted = vec![0; msg.len()];
+        assert!(Ccm::new(AesSafe128Encryptor::new(&key), &nonce, &aad, 16)
+            .decrypt(&output, &mut decrypted, &tag).is_ok());
+        assert!(decrypted == msg);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_ccm_odd_tag_len() {
+        Ccm::new(AesSafe128Encryptor::new(&[0; 16]), &[0; 12], &[], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "message too long")]
+    fn test_ccm_message_too_long() {
+        // A 13 byte nonce leaves a 2 byte length field
+        let msg = vec![0; 0x10000];
+        let mut output = vec![0; msg.len()];
+        let mut tag = [0u8; 8];
+        Ccm::new(AesSafe128Encryptor::new(&[0; 16]), &[0; 13], &[], 8)
+            .encrypt(&msg, &mut output, &mut tag);
+    }
+}
@@ -7,7 +7,8 @@ use std::cmp;
 
 use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
 use symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
-use cryptoutil::{read_u32_le, symm_enc_or_dec, write_u32_le, xor_keystream};
+use cryptoutil::{debug_assert_no_overlap, read_u32_le, symm_enc_or_dec, write_u32_le, xor_keystream,
+    zero};
 use simd::u32x4;
 
 #[derive(Clone,Copy)]
@@ -23,6 +24,8 @@ pub struct ChaCha20 {
     state  : ChaChaState,
     output : [u8; 64],
     offset : usize,
+    // Set once a call to encrypt() or decrypt() with eof set has consumed all of its input
+    finished : bool,
 }
 
 impl Clone for ChaCha20 { fn clone(&self) -> ChaCha20 { *self } }
@@ -89,7 +92,24 @@ impl ChaCha20 {
         assert!(key.len() == 16 || key.len() == 32);
         assert!(nonce.len() == 8 || nonce.len() == 12);
 
-        ChaCha20{ state: ChaCha20::expand(key, nonce), output: [0u8; 64], offset: 64 }
+        ChaCha20 {
+            state: ChaCha20::expand(key, nonce),
+            output: [0u8; 64],
+            offset: 64,
+            finished: false
+        }
+    }
+
+    /// Replace the key and nonce in place, as if a new ChaCha20 had been constructed with them.
+    /// Any keystream left over from the previous key is discarded.
+    pub fn re_key(&mut self, key: &[u8], nonce: &[u8]) {
+        assert!(key.len() == 16 || key.len() == 32);
+        assert!(nonce.len() == 8 || nonce.len() == 12);
+
+        self.state = ChaCha20::expand(key, nonce);
+        zero(&mut self.output);
+        self.offset = 64;
+        self.finished = false;
     }
 
     pub fn new_xchacha20(key: &[u8], nonce: &[u8]) -> ChaCha20 {
@@ -102,7 +122,7 @@ impl ChaCha20 {
         //  * (x0, x1, x2, x3) is the ChaCha20 constant.
         //  * (x4, x5, ... x11) is a 256 bit key.
         //  * (x12, x13, x14, x15) is a 128 bit nonce.
-        let mut xchacha20 = ChaCha20{ state: ChaCha20::expand(key, &nonce[0..16]), output: [0u8; 64], offset: 64 };
+        let mut xchacha20 = ChaCha20{ state: ChaCha20::expand(key, &nonce[0..16]), output: [0u8; 64], offset: 64, finished: false };
 
         // Use HChaCha to derive the subkey, and initialize a ChaCha20 instance
         // with the subkey and the remaining 8 bytes of the nonce.
@@ -242,7 +262,9 @@ impl ChaCha20 {
 impl SynchronousStreamCipher for ChaCha20 {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
         let len = input.len();
+        assert!(len == 0 || !self.finished, "keystream used after the final call to encrypt");
         let mut i = 0;
         while i < len {
             // If there is no keystream available in the output buffer,
@@ -258,19 +280,55 @@ impl SynchronousStreamCipher for ChaCha20 {
             self.offset += count;
         }
     }
+
+    fn process_in_place(&mut self, data: &mut [u8]) {
+        let len = data.len();
+        assert!(len == 0 || !self.finished, "keystream used after the final call to encrypt");
+        let mut i = 0;
+        while i < len {
+            if self.offset == 64 {
+                self.update();
+            }
+
+            let count = cmp::min(64 - self.offset, len - i);
+            for (d, &k) in data[i..i+count].iter_mut().zip(self.output[self.offset..].iter()) {
+                *d ^= k;
+            }
+            i += count;
+            self.offset += count;
+        }
+    }
+}
+
+impl ChaCha20 {
+    // Once a call with eof set has consumed all of its input the message is complete, and any
+    // further data would be processed with keystream that continues the finished message.
+    fn process_buffers(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        let result = symm_enc_or_dec(self, input, output);
+        if let Ok(BufferResult::BufferUnderflow) = result {
+            self.finished |= eof;
+        }
+        result
+    }
 }
 
+/// Once encrypt() has been called with eof set and has consumed all of its input, processing any
+/// more data panics, since it would continue the keystream of a finished message. Empty calls are
+/// still allowed. Use re_key() to start a new message.
 impl Encryptor for ChaCha20 {
-    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
             -> Result<BufferResult, SymmetricCipherError> {
-        symm_enc_or_dec(self, input, output)
+        self.process_buffers(input, output, eof)
     }
 }
 
+/// Like encrypt(), processing any data after a call to decrypt() with eof set has consumed all of
+/// its input panics.
 impl Decryptor for ChaCha20 {
-    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
             -> Result<BufferResult, SymmetricCipherError> {
-        symm_enc_or_dec(self, input, output)
+        self.process_buffers(input, output, eof)
     }
 }
 
@@ -278,8 +336,9 @@ impl Decryptor for ChaCha20 {
 mod test {
     use std::iter::repeat;
 
+    use buffer::{RefReadBuffer, RefWriteBuffer};
     use chacha20::ChaCha20;
-    use symmetriccipher::SynchronousStreamCipher;
+    use symmetriccipher::{Decryptor, Encryptor, SynchronousStreamCipher};
 
     #[test]
     fn test_chacha20_256_tls_vectors() {
@@ -416,6 +475,112 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_chacha20_process_in_place() {
+        let key = [0x1fu8; 32];
+        let nonce = [0x2eu8; 8];
+        let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut expected = vec![0u8; plaintext.len()];
+        ChaCha20::new(&key, &nonce).process(&plaintext, &mut expected);
+
+        // Encrypt in pieces that don't line up with the block size
+        let mut data = plaintext.clone();
+        let mut cipher = ChaCha20::new(&key, &nonce);
+        cipher.process_in_place(&mut data[..3]);
+        cipher.process_in_place(&mut data[3..130]);
+        cipher.process_in_place(&mut data[130..]);
+        assert!(data == expected);
+
+        ChaCha20::new(&key, &nonce).process_in_place(&mut data);
+        assert!(data == plaintext);
+    }
+
+    #[test]
+    fn test_chacha20_keystream() {
+        let key = [0x1fu8; 32];
+        let nonce = [0x2eu8; 8];
+        let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut expected = vec![0u8; plaintext.len()];
+        ChaCha20::new(&key, &nonce).process(&plaintext, &mut expected);
+
+        // Request the keystream in pieces that don't line up with the block size
+        let mut cipher = ChaCha20::new(&key, &nonce);
+        let mut keystream = vec![0u8; plaintext.len()];
+        cipher.keystream(&mut keystream[..3]);
+        cipher.keystream(&mut keystream[3..130]);
+        cipher.keystream(&mut keystream[130..]);
+
+        let xored: Vec<u8> = plaintext.iter().zip(keystream.iter()).map(|(&p, &k)| p ^ k).collect();
+        assert!(xored == expected);
+    }
+
+    #[test]
+    fn test_chacha20_re_key() {
+        let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let mut cipher = ChaCha20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        // Leave part of a block of keystream buffered
+        let mut scratch = [0u8; 70];
+        cipher.process(&plaintext[..70], &mut scratch);
+
+        for &(key, nonce) in [(&[0x3du8; 32][..], &[0x4cu8; 8][..]),
+                              (&[0x5bu8; 16][..], &[0x6au8; 12][..])].iter() {
+            let mut expected = vec![0u8; plaintext.len()];
+            ChaCha20::new(key, nonce).process(&plaintext, &mut expected);
+
+            cipher.re_key(key, nonce);
+            let mut output = vec![0u8; plaintext.len()];
+            cipher.process(&plaintext, &mut output);
+            assert!(output == expected);
+        }
+    }
+
+    #[test]
+    fn test_chacha20_final() {
+        let plaintext: Vec<u8> = (0..100).map(|i| i as u8).collect();
+        let mut expected = vec![0u8; plaintext.len()];
+        ChaCha20::new(&[0x1fu8; 32], &[0x2eu8; 8]).process(&plaintext, &mut expected);
+
+        // A final call that runs out of output space can be continued until it has consumed all
+        // of its input, and empty calls afterwards are harmless.
+        let mut cipher = ChaCha20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        let mut output = vec![0u8; plaintext.len()];
+        let mut read_buffer = RefReadBuffer::new(&plaintext);
+        for chunk in output.chunks_mut(30) {
+            cipher.encrypt(&mut read_buffer, &mut RefWriteBuffer::new(chunk), true).unwrap();
+        }
+        cipher.encrypt(&mut RefReadBuffer::new(&[]), &mut RefWriteBuffer::new(&mut []), true)
+            .unwrap();
+        assert!(output == expected);
+
+        // Re-keying starts a new message
+        cipher.re_key(&[0x1fu8; 32], &[0x2eu8; 8]);
+        cipher.process(&plaintext, &mut output);
+        assert!(output == expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chacha20_encrypt_after_final() {
+        let mut cipher = ChaCha20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        let mut output = [0u8; 10];
+        cipher.encrypt(&mut RefReadBuffer::new(&[1; 10]), &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        cipher.encrypt(&mut RefReadBuffer::new(&[2; 10]), &mut RefWriteBuffer::new(&mut output),
+            false).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chacha20_process_after_final() {
+        let mut cipher = ChaCha20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        let mut output = [0u8; 10];
+        cipher.decrypt(&mut RefReadBuffer::new(&[1; 10]), &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        cipher.process(&[2; 10], &mut output);
+    }
+
     #[test]
     fn test_xchacha20_basic() {
         // There aren't any convenient test vectors for XChaCha/20,
@@ -7,7 +7,7 @@
 use aead::{AeadEncryptor,AeadDecryptor};
 
 use chacha20::ChaCha20;
-use symmetriccipher::SynchronousStreamCipher;
+use symmetriccipher::{SymmetricCipherError, SynchronousStreamCipher};
 use poly1305::Poly1305;
 use mac::Mac;
 use cryptoutil::{write_u64_le};
@@ -30,6 +30,10 @@ impl ChaCha20Poly1305 {
       let zero_key = [0u8; 64];
       cipher.process(&zero_key, &mut mac_key);
 
+      // This is the original ChaCha20-Poly1305 construction with a 64-bit nonce, in which the
+      // AAD and ciphertext are each followed directly by their 64-bit lengths without any padding
+      // to a 16 byte boundary. The Poly1305 key always comes from the first keystream block, so
+      // an empty plaintext simply authenticates the AAD alone.
       let mut mac = Poly1305::new(&mac_key[..32]);
       mac.input(aad);
       let mut aad_len = [0u8; 8];
@@ -61,7 +65,8 @@ impl AeadEncryptor for ChaCha20Poly1305 {
 }
 
 impl AeadDecryptor for ChaCha20Poly1305 {
-    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8]) -> bool {
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+            -> Result<(), SymmetricCipherError> {
         assert!(input.len() == output.len());
         assert!(self.finished == false);
 
@@ -79,18 +84,104 @@ impl AeadDecryptor for ChaCha20Poly1305 {
         self.mac.raw_result(&mut calc_tag);
         if fixed_time_eq(&calc_tag, tag) {
             self.cipher.process(input, output);
-            true
+            Ok(())
         } else {
-            false
+            Err(SymmetricCipherError::AuthenticationFailed)
         }
     }
 }
+
+/// ChaCha20-Poly1305 with a 24 byte nonce, as implemented by libsodium's
+/// crypto_aead_xchacha20poly1305_ietf functions. A subkey is derived from the key and the first 16
+/// bytes of the nonce with HChaCha20, and then used with the remaining 8 bytes of the nonce in the
+/// IETF ChaCha20-Poly1305 construction (RFC 7539). The nonce is large enough that it may be chosen
+/// at random.
+#[derive(Clone, Copy)]
+pub struct XChaCha20Poly1305 {
+  cipher: ChaCha20,
+  mac: Poly1305,
+  finished: bool,
+  aad_len: usize
+}
+
+// Poly1305 input in the IETF construction is padded with zeros to a multiple of 16 bytes
+fn pad16(mac: &mut Poly1305, len: usize) {
+  if len % 16 != 0 {
+    let zeros = [0u8; 16];
+    mac.input(&zeros[..16 - len % 16]);
+  }
+}
+
+impl XChaCha20Poly1305 {
+  pub fn new(key: &[u8], nonce: &[u8], aad: &[u8]) -> XChaCha20Poly1305 {
+    assert!(key.len() == 32);
+    assert!(nonce.len() == 24);
+
+    // The first block of keystream is used for the Poly1305 key, and encryption starts with
+    // the second.
+    let mut cipher = ChaCha20::new_xchacha20(key, nonce);
+    let mut mac_key = [0u8; 64];
+    let zero_key = [0u8; 64];
+    cipher.process(&zero_key, &mut mac_key);
+
+    let mut mac = Poly1305::new(&mac_key[..32]);
+    mac.input(aad);
+    pad16(&mut mac, aad.len());
+    XChaCha20Poly1305 {
+      cipher,
+      mac,
+      finished: false,
+      aad_len: aad.len()
+    }
+  }
+
+  fn finish(&mut self, ciphertext: &[u8], tag: &mut [u8]) {
+    self.mac.input(ciphertext);
+    pad16(&mut self.mac, ciphertext.len());
+    let mut len_buf = [0u8; 16];
+    write_u64_le(&mut len_buf[..8], self.aad_len as u64);
+    write_u64_le(&mut len_buf[8..], ciphertext.len() as u64);
+    self.mac.input(&len_buf);
+    self.mac.raw_result(tag);
+  }
+}
+
+impl AeadEncryptor for XChaCha20Poly1305 {
+  fn encrypt(&mut self, input: &[u8], output: &mut [u8], out_tag: &mut [u8]) {
+    assert!(input.len() == output.len());
+    assert!(!self.finished);
+    self.finished = true;
+    self.cipher.process(input, output);
+    self.finish(output, out_tag);
+  }
+}
+
+impl AeadDecryptor for XChaCha20Poly1305 {
+  fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+      -> Result<(), SymmetricCipherError> {
+    assert!(input.len() == output.len());
+    assert!(!self.finished);
+    self.finished = true;
+
+    let mut calc_tag = [0u8; 16];
+    self.finish(input, &mut calc_tag);
+    if fixed_time_eq(&calc_tag, tag) {
+      self.cipher.process(input, output);
+      Ok(())
+    } else {
+      Err(SymmetricCipherError::AuthenticationFailed)
+    }
+  }
+}
+
 #[cfg(test)]
 mod test {
   use std::iter::repeat;
 
-  use chacha20poly1305::ChaCha20Poly1305;
+  use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
   use aead::{AeadEncryptor,AeadDecryptor};
+  use symmetriccipher::SymmetricCipherError;
+
   struct TestVector {
     key:   [u8; 32],
     nonce: [u8; 8],
@@ -126,9 +217,73 @@ mod test {
       let mut output: Vec<u8> = repeat(0).take(tv.plain_text.len()).collect();
       let result = c.decrypt(&tv.cipher_text[..], &mut output[..], &tv.tag[..]);
       assert_eq!(output, tv.plain_text);
-      assert!(result);
+      assert!(result.is_ok());
     }
   }
+  #[test]
+  fn test_chacha20_256_poly1305_aad_only() {
+    // Authenticating only associated data, with no plaintext, is a pure MAC use of the AEAD
+    let key: Vec<u8> = (0x80..0xa0).collect();
+    let nonce = [0x07, 0x00, 0x00, 0x00, 0x40, 0x41, 0x42, 0x43];
+    let aad = [0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5,
+               0xc6, 0xc7, 0xd0, 0xd1, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7];
+    let expected_tag = [0xa5, 0xcf, 0x82, 0xa7, 0x57, 0xa8, 0x0c, 0x14,
+                        0xc7, 0x44, 0x6c, 0xa0, 0x7e, 0x65, 0x66, 0xda];
+
+    let mut c = ChaCha20Poly1305::new(&key, &nonce, &aad);
+    let mut tag = [0u8; 16];
+    c.encrypt(&[], &mut [], &mut tag);
+    assert_eq!(tag, expected_tag);
+
+    let mut c = ChaCha20Poly1305::new(&key, &nonce, &aad);
+    assert!(c.decrypt(&[], &mut [], &tag).is_ok());
+
+    let mut bad_aad = aad;
+    bad_aad[19] ^= 1;
+    let mut c = ChaCha20Poly1305::new(&key, &nonce, &bad_aad);
+    assert!(matches!(c.decrypt(&[], &mut [], &tag),
+                     Err(SymmetricCipherError::AuthenticationFailed)));
+  }
+
+  #[test]
+  fn test_xchacha20_poly1305() {
+    // From draft-irtf-cfrg-xchacha-03, Appendix A.3.1. libsodium's
+    // crypto_aead_xchacha20poly1305_ietf_encrypt produces the same output.
+    let key: Vec<u8> = (0x80..0xa0).collect();
+    let nonce: Vec<u8> = (0x40..0x58).collect();
+    let aad = [0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7];
+    let plain_text = b"Ladies and Gentlemen of the class of '99: If I could offer you only one \
+                       tip for the future, sunscreen would be it.";
+    let cipher_text = [
+      0xbd, 0x6d, 0x17, 0x9d, 0x3e, 0x83, 0xd4, 0x3b, 0x95, 0x76, 0x57, 0x94, 0x93, 0xc0, 0xe9, 0x39,
+      0x57, 0x2a, 0x17, 0x00, 0x25, 0x2b, 0xfa, 0xcc, 0xbe, 0xd2, 0x90, 0x2c, 0x21, 0x39, 0x6c, 0xbb,
+      0x73, 0x1c, 0x7f, 0x1b, 0x0b, 0x4a, 0xa6, 0x44, 0x0b, 0xf3, 0xa8, 0x2f, 0x4e, 0xda, 0x7e, 0x39,
+      0xae, 0x64, 0xc6, 0x70, 0x8c, 0x54, 0xc2, 0x16, 0xcb, 0x96, 0xb7, 0x2e, 0x12, 0x13, 0xb4, 0x52,
+      0x2f, 0x8c, 0x9b, 0xa4, 0x0d, 0xb5, 0xd9, 0x45, 0xb1, 0x1b, 0x69, 0xb9, 0x82, 0xc1, 0xbb, 0x9e,
+      0x3f, 0x3f, 0xac, 0x2b, 0xc3, 0x69, 0x48, 0x8f, 0x76, 0xb2, 0x38, 0x35, 0x65, 0xd3, 0xff, 0xf9,
+      0x21, 0xf9, 0x66, 0x4c, 0x97, 0x63, 0x7d, 0xa9, 0x76, 0x88, 0x12, 0xf6, 0x15, 0xc6, 0x8b, 0x13,
+      0xb5, 0x2e ];
+    let expected_tag = [0xc0, 0x87, 0x59, 0x24, 0xc1, 0xc7, 0x98, 0x79,
+                        0x47, 0xde, 0xaf, 0xd8, 0x78, 0x0a, 0xcf, 0x49];
+
+    let mut c = XChaCha20Poly1305::new(&key, &nonce, &aad);
+    let mut output = vec![0u8; plain_text.len()];
+    let mut tag = [0u8; 16];
+    c.encrypt(&plain_text[..], &mut output, &mut tag);
+    assert_eq!(&output[..], &cipher_text[..]);
+    assert_eq!(tag, expected_tag);
+
+    let mut c = XChaCha20Poly1305::new(&key, &nonce, &aad);
+    assert!(c.decrypt(&cipher_text, &mut output, &tag).is_ok());
+    assert_eq!(&output[..], &plain_text[..]);
+
+    let mut bad_tag = tag;
+    bad_tag[0] ^= 1;
+    let mut c = XChaCha20Poly1305::new(&key, &nonce, &aad);
+    assert!(matches!(c.decrypt(&cipher_text, &mut output, &bad_tag),
+                     Err(SymmetricCipherError::AuthenticationFailed)));
+  }
+
   fn get_test_vectors()-> Vec<TestVector>{
     vec!(
       TestVector {
@@ -753,7 +908,7 @@ mod bench {
           let mut tag = [0u8; 16];
           let mut output2 = [0u8; 10];
           cipher.encrypt(&input, &mut output, &mut tag);
-          decipher.decrypt(&output, &mut output2, &tag);
+          decipher.decrypt(&output, &mut output2, &tag).unwrap();
 
         });
         bh.bytes = 10u64;
@@ -773,7 +928,7 @@ mod bench {
         let mut output2 = [0u8; 1024];
 
         cipher.encrypt(&input, &mut output, &mut tag);
-        decipher.decrypt(&output, &mut output2, &tag);
+        decipher.decrypt(&output, &mut output2, &tag).unwrap();
         });
       bh.bytes = 1024u64;
 
@@ -792,7 +947,7 @@ mod bench {
           let mut output2 = [0u8; 65536];
 
           cipher.encrypt(&input, &mut output, &mut tag);
-          decipher.decrypt(&output, &mut output2, &tag);
+          decipher.decrypt(&output, &mut output2, &tag).unwrap();
 
         });
          bh.bytes = 65536u64;
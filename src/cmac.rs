@@ -0,0 +1,339 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the CMAC function - a Message Authentication Code using symmetric
+ * encryption as specified by NIST SP 800-38B (it is also known as OMAC1).
+ */
+
+use std::cmp::min;
+
+use cryptoutil::{copy_memory, gf128_double};
+use mac::{Mac, MacResult};
+use symmetriccipher::BlockEncryptor;
+
+/**
+ * The Cmac struct represents a CMAC function over a BlockEncryptor with a 64 or 128 bit block
+ * size. The message may be passed to input() in any number of pieces.
+ */
+pub struct Cmac<C: BlockEncryptor> {
+    cipher: C,
+    key_one: Vec<u8>,
+    key_two: Vec<u8>,
+    // The CBC-MAC of every block before the one in buffer, and the final code once finished
+    state: Vec<u8>,
+    // The most recent block of input. It can't be processed until more input arrives, since the
+    // last block of the message is tweaked with a subkey.
+    buffer: Vec<u8>,
+    buffer_len: usize,
+    finished: bool
+}
+
+fn xor(a: &mut [u8], b: &[u8]) {
+    for (x, &y) in a.iter_mut().zip(b.iter()) {
+        *x ^= y;
+    }
+}
+
+// Derive the next subkey by doubling key in GF(2^64) or GF(2^128).
+fn generate_subkey(key: &[u8], block_size: usize) -> Vec<u8> {
+    match block_size {
+        8 => {
+            // The constant R_64 from SP 800-38B, section 5.3
+            let mut block = [0u8; 8];
+            block.copy_from_slice(key);
+            let v = u64::from_be_bytes(block);
+            let msb = (v >> 63).wrapping_neg();
+            ((v << 1) ^ (0x1b & msb)).to_be_bytes().to_vec()
+        }
+        16 => {
+            let mut block = [0u8; 16];
+            block.copy_from_slice(key);
+            gf128_double(&block).to_vec()
+        }
+        _ => panic!("CMAC requires a block size of 64 or 128 bits")
+    }
+}
+
+// Derive the OMAC2 second subkey by dividing key by x in GF(2^64) or GF(2^128): a right shift by one
+// bit that, if the bit shifted out was set, is corrected by XORing in the reduction polynomial
+// shifted right by one bit.
+fn generate_subkey_inverse(key: &[u8], block_size: usize) -> Vec<u8> {
+    match block_size {
+        8 => {
+            let mut block = [0u8; 8];
+            block.copy_from_slice(key);
+            let v = u64::from_be_bytes(block);
+            let lsb = (v & 1).wrapping_neg();
+            ((v >> 1) ^ (0x800000000000000d & lsb)).to_be_bytes().to_vec()
+        }
+        16 => {
+            let mut block = [0u8; 16];
+            block.copy_from_slice(key);
+            let v = u128::from_be_bytes(block);
+            let lsb = (v & 1).wrapping_neg();
+            ((v >> 1) ^ ((1 << 127 | 0x43) & lsb)).to_be_bytes().to_vec()
+        }
+        _ => panic!("CMAC requires a block size of 64 or 128 bits")
+    }
+}
+
+/// The variants of OMAC, which only differ in how the second subkey is derived from L, the
+/// encryption of the zero block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OmacVariant {
+    /// OMAC1, which is the same as CMAC. The second subkey is L * x^2.
+    Omac1,
+    /// OMAC2, where the second subkey is L * x^-1.
+    Omac2
+}
+
+impl <C: BlockEncryptor> Cmac<C> {
+    /**
+     * Create a new Cmac instance.
+     *
+     * # Arguments
+     * * cipher - The BlockEncryptor to use. It should already be initialized with the key.
+     *
+     */
+    pub fn new(cipher: C) -> Cmac<C> {
+        Cmac::new_with_variant(cipher, OmacVariant::Omac1)
+    }
+
+    /**
+     * Create a new instance of the given OMAC variant.
+     *
+     * # Arguments
+     * * cipher - The BlockEncryptor to use. It should already be initialized with the key.
+     * * variant - Whether to compute OMAC1 (CMAC) or OMAC2.
+     *
+     */
+    pub fn new_with_variant(cipher: C, variant: OmacVariant) -> Cmac<C> {
+        let block_size = cipher.block_size();
+        let zeros = vec![0; block_size];
+        let mut l = vec![0; block_size];
+        cipher.encrypt_block(&zeros, &mut l);
+
+        let key_one = generate_subkey(&l, block_size);
+        let key_two = match variant {
+            OmacVariant::Omac1 => generate_subkey(&key_one, block_size),
+            OmacVariant::Omac2 => generate_subkey_inverse(&l, block_size)
+        };
+
+        Cmac {
+            cipher,
+            key_one,
+            key_two,
+            state: zeros.clone(),
+            buffer: zeros,
+            buffer_len: 0,
+            finished: false
+        }
+    }
+
+    // Add the block in buffer to the CBC-MAC state
+    fn process_buffer(&mut self) {
+        xor(&mut self.state, &self.buffer);
+        let block = self.state.clone();
+        self.cipher.encrypt_block(&block, &mut self.state);
+    }
+
+    // The last block is complete if the message is a non-empty multiple of the block size, in
+    // which case it is tweaked with the first subkey; otherwise it is padded and tweaked with the
+    // second.
+    fn finish(&mut self) {
+        let block_size = self.buffer.len();
+        if self.buffer_len == block_size {
+            xor(&mut self.buffer, &self.key_one);
+        } else {
+            for x in self.buffer[self.buffer_len..].iter_mut() {
+                *x = 0;
+            }
+            self.buffer[self.buffer_len] = 0x80;
+            xor(&mut self.buffer, &self.key_two);
+        }
+        self.process_buffer();
+        self.finished = true;
+    }
+}
+
+impl <C: BlockEncryptor> Mac for Cmac<C> {
+    fn input(&mut self, mut data: &[u8]) {
+        assert!(!self.finished);
+        let block_size = self.buffer.len();
+        while !data.is_empty() {
+            // Only process the buffered block once it's known not to be the last one
+            if self.buffer_len == block_size {
+                self.process_buffer();
+                self.buffer_len = 0;
+            }
+            let n = min(block_size - self.buffer_len, data.len());
+            copy_memory(&data[..n], &mut self.buffer[self.buffer_len..]);
+            self.buffer_len += n;
+            data = &data[n..];
+        }
+    }
+
+    fn reset(&mut self) {
+        for x in self.state.iter_mut() {
+            *x = 0;
+        }
+        self.buffer_len = 0;
+        self.finished = false;
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut code = vec![0; self.output_bytes()];
+
+        self.raw_result(&mut code);
+
+        MacResult::new_from_owned(code)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        assert!(output.len() == self.output_bytes(),
+            "the CMAC output buffer must be exactly output_bytes() long");
+        if !self.finished {
+            self.finish();
+        }
+
+        copy_memory(&self.state, output);
+    }
+
+    fn output_bytes(&self) -> usize { self.cipher.block_size() }
+}
+
+#[cfg(test)]
+mod test {
+    use aessafe::AesSafe128Encryptor;
+    use cmac::{generate_subkey, generate_subkey_inverse, Cmac, OmacVariant};
+    use mac::{Mac, MacResult};
+    use serialize::hex::FromHex;
+
+    // Test vectors from NIST SP 800-38B, Appendix D.1 - AES-128
+    const KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+
+    const MESSAGE: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                           30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    fn check(message_len: usize, expected: &str) {
+        let key = KEY.from_hex().unwrap();
+        let message = MESSAGE.from_hex().unwrap();
+        let mut cmac = Cmac::new(AesSafe128Encryptor::new(&key));
+        cmac.input(&message[..message_len]);
+        assert!(cmac.result() == MacResult::new(&expected.from_hex().unwrap()));
+
+        cmac.reset();
+        cmac.input(&message[..message_len]);
+        assert!(cmac.result() == MacResult::new(&expected.from_hex().unwrap()));
+    }
+
+    #[test]
+    fn test_cmac_aes128_empty() {
+        check(0, "bb1d6929e95937287fa37d129b756746");
+
+        // result() without any input is the CMAC of the empty message
+        let key = KEY.from_hex().unwrap();
+        let mut cmac = Cmac::new(AesSafe128Encryptor::new(&key));
+        assert!(cmac.result() ==
+            MacResult::new(&"bb1d6929e95937287fa37d129b756746".from_hex().unwrap()));
+    }
+
+    #[test]
+    fn test_cmac_aes128_one_block() {
+        check(16, "070a16b46b4d4144f79bdd9dd04a287c");
+    }
+
+    #[test]
+    fn test_cmac_aes128_partial_block() {
+        check(40, "dfa66747de9ae63030ca32611497c827");
+    }
+
+    #[test]
+    fn test_cmac_aes128_four_blocks() {
+        check(64, "51f0bebf7e3b9d92fc49741779363cfe");
+    }
+
+    #[test]
+    fn test_cmac_aes128_incremental() {
+        let key = KEY.from_hex().unwrap();
+        let message = MESSAGE.from_hex().unwrap();
+        let vectors = [(16, "070a16b46b4d4144f79bdd9dd04a287c"),
+                       (40, "dfa66747de9ae63030ca32611497c827"),
+                       (64, "51f0bebf7e3b9d92fc49741779363cfe")];
+        for &(len, expected) in vectors.iter() {
+            let expected = MacResult::new(&expected.from_hex().unwrap());
+            for &piece in [1usize, 5, 15, 16, 17, 33].iter() {
+                let mut cmac = Cmac::new(AesSafe128Encryptor::new(&key));
+                for chunk in message[..len].chunks(piece) {
+                    cmac.input(chunk);
+                    // Empty input must not change anything
+                    cmac.input(&[]);
+                }
+                assert!(cmac.result() == expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_omac2_aes128() {
+        // OMAC2 only differs from OMAC1 when the last block is partial
+        let key = KEY.from_hex().unwrap();
+        let message = MESSAGE.from_hex().unwrap();
+        let vectors = [(0, "f6bc6a41f4f84593809e59b719299cfe"),
+                       (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+                       (40, "23fdaa0831cd314491ce4b25acb6023b"),
+                       (64, "51f0bebf7e3b9d92fc49741779363cfe")];
+        for &(len, expected) in vectors.iter() {
+            let mut omac = Cmac::new_with_variant(AesSafe128Encryptor::new(&key), OmacVariant::Omac2);
+            omac.input(&message[..len]);
+            assert!(omac.result() == MacResult::new(&expected.from_hex().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_subkey_inverse() {
+        // Halving undoes doubling in both field sizes
+        for &block_size in [8usize, 16].iter() {
+            for &first in [0x00u8, 0x80, 0x5a, 0xff].iter() {
+                for &last in [0x00u8, 0x01, 0xa5, 0xff].iter() {
+                    let mut key = vec![0x3cu8; block_size];
+                    key[0] = first;
+                    key[block_size - 1] = last;
+                    let doubled = generate_subkey(&key, block_size);
+                    assert_eq!(generate_subkey_inverse(&doubled, block_size), key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cmac_raw_result() {
+        let key = KEY.from_hex().unwrap();
+        let mut cmac = Cmac::new(AesSafe128Encryptor::new(&key));
+        let mut out = [0u8; 16];
+        cmac.raw_result(&mut out);
+        assert_eq!(out.to_vec(), "bb1d6929e95937287fa37d129b756746".from_hex().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "exactly output_bytes() long")]
+    fn test_cmac_raw_result_short() {
+        let key = KEY.from_hex().unwrap();
+        let mut cmac = Cmac::new(AesSafe128Encryptor::new(&key));
+        cmac.input(b"message");
+        cmac.raw_result(&mut [0u8; 15]);
+    }
+
+    #[test]
+    #[should_panic(expected = "exactly output_bytes() long")]
+    fn test_cmac_raw_result_long() {
+        // Checked before anything is computed, so an unfinished Cmac fails the same way
+        let key = KEY.from_hex().unwrap();
+        let mut cmac = Cmac::new(AesSafe128Encryptor::new(&key));
+        cmac.raw_result(&mut [0u8; 17]);
+    }
+}
@@ -0,0 +1,138 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the CRC-32 (ISO-HDLC, as used by zlib and Ethernet) and CRC-32C
+ * (Castagnoli, as used by iSCSI and SCTP) checksums.
+ *
+ * # Security
+ *
+ * These are NOT cryptographic functions. A CRC only detects accidental corruption: anyone can
+ * modify data without changing its CRC, or compute the CRC of data of their choosing. Use a MAC,
+ * such as HMAC, to protect data against deliberate modification.
+ */
+
+// The bit-reflected generator polynomials
+const CRC32_POLY: u32 = 0xedb88320;
+const CRC32C_POLY: u32 = 0x82f63b78;
+
+const fn make_table(poly: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ poly } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = make_table(CRC32_POLY);
+static CRC32C_TABLE: [u32; 256] = make_table(CRC32C_POLY);
+
+/**
+ * The Crc32 struct computes a CRC-32 or CRC-32C checksum incrementally.
+ */
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+    table: &'static [u32; 256],
+    crc: u32
+}
+
+impl Crc32 {
+    /// Create a new Crc32 that computes the CRC-32 checksum.
+    pub fn new() -> Crc32 {
+        Crc32 { table: &CRC32_TABLE, crc: !0 }
+    }
+
+    /// Create a new Crc32 that computes the CRC-32C (Castagnoli) checksum.
+    pub fn new_castagnoli() -> Crc32 {
+        Crc32 { table: &CRC32C_TABLE, crc: !0 }
+    }
+
+    /// Process input data.
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.crc;
+        for &b in data.iter() {
+            crc = self.table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+        self.crc = crc;
+    }
+
+    /// Get the checksum of all of the data processed so far. More data may be processed afterwards.
+    pub fn finalize(&self) -> u32 {
+        !self.crc
+    }
+
+    /// Reset the Crc32 to begin processing another input stream.
+    pub fn reset(&mut self) {
+        self.crc = !0;
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+/// Compute the CRC-32 checksum of data.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+/// Compute the CRC-32C (Castagnoli) checksum of data.
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new_castagnoli();
+    crc.update(data);
+    crc.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use crc::{crc32, crc32c, Crc32};
+
+    #[test]
+    fn test_crc32_check() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414fa339);
+    }
+
+    #[test]
+    fn test_crc32c_check() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+        // From RFC 3720, Appendix B.4 - 32 bytes of zeros and of ones
+        assert_eq!(crc32c(&[0u8; 32]), 0x8a9136aa);
+        assert_eq!(crc32c(&[0xffu8; 32]), 0x62a8ab43);
+    }
+
+    #[test]
+    fn test_crc32_incremental() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+        for &(mut crc, expected) in [(Crc32::new(), crc32(data)),
+                                     (Crc32::new_castagnoli(), crc32c(data))].iter() {
+            for chunk in data.chunks(5) {
+                crc.update(chunk);
+            }
+            assert_eq!(crc.finalize(), expected);
+
+            crc.reset();
+            crc.update(&data[..10]);
+            crc.update(&[]);
+            crc.update(&data[10..]);
+            assert_eq!(crc.finalize(), expected);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * Adapters that connect the buffer based Encryptor and Decryptor interfaces to the std::io traits.
+ */
+
+use std::io::{self, Read, Write};
+
+use buffer::{ReadBuffer, WriteBuffer, RefReadBuffer, RefWriteBuffer, BufferResult};
+use symmetriccipher::{Encryptor, SymmetricCipherError};
+
+const BUFFER_SIZE: usize = 4096;
+
+fn cipher_error(err: SymmetricCipherError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("encryption failed: {:?}", err))
+}
+
+/// A CryptoWriter encrypts all of the data written to it and writes the resulting ciphertext to
+/// an inner Write implementation.
+///
+/// Block modes hold back the last block of input until they know that no more data will follow, so
+/// the ciphertext is not complete until finish() is called. finish() consumes the CryptoWriter, so
+/// no further data may be written once the final block has been produced.
+pub struct CryptoWriter<W: Write, E: Encryptor> {
+    writer: W,
+    encryptor: E,
+    buffer: Vec<u8>
+}
+
+impl <W: Write, E: Encryptor> CryptoWriter<W, E> {
+    /// Create a new CryptoWriter that writes the data encrypted by encryptor into writer.
+    pub fn new(writer: W, encryptor: E) -> CryptoWriter<W, E> {
+        CryptoWriter {
+            writer,
+            encryptor,
+            buffer: vec![0; BUFFER_SIZE]
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    // Run the encryptor over all of input, writing everything it produces to the inner writer.
+    fn encrypt_all(&mut self, input: &[u8], eof: bool) -> io::Result<()> {
+        let mut read_buffer = RefReadBuffer::new(input);
+        loop {
+            let mut write_buffer = RefWriteBuffer::new(&mut self.buffer);
+            let result = self.encryptor.encrypt(&mut read_buffer, &mut write_buffer, eof)
+                .map_err(cipher_error)?;
+            self.writer.write_all(write_buffer.take_read_buffer().take_remaining())?;
+            match result {
+                BufferResult::BufferUnderflow => return Ok(()),
+                BufferResult::BufferOverflow => { }
+            }
+        }
+    }
+
+    /// Signal the end of the plaintext to the encryptor, write any remaining ciphertext (including
+    /// padding) to the inner writer, flush it, and then return it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encrypt_all(&[], true)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl <W: Write, E: Encryptor> Write for CryptoWriter<W, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encrypt_all(buf, false)?;
+        Ok(buf.len())
+    }
+
+    /// Flush the inner writer. Data that the encryptor is holding back until it has a complete
+    /// block is not written; use finish() for that.
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A ReaderBuffer is a ReadBuffer over data pulled from an inner Read implementation, so that an
+/// Encryptor or Decryptor can consume directly from a file or socket.
+///
+/// The ReadBuffer methods only see the window of data that has already been read into the internal
+/// buffer, since reading can fail and they have no way to report an error. Call fill() to pull more
+/// data whenever the window runs low. fill() discards the data that has already been consumed, so
+/// rewind() and reset() can only go back as far as the start of the window from the latest fill().
+pub struct ReaderBuffer<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    len: usize,
+    pos: usize,
+    eof: bool
+}
+
+impl <R: Read> ReaderBuffer<R> {
+    /// Create a new ReaderBuffer that reads from reader.
+    pub fn new(reader: R) -> ReaderBuffer<R> {
+        ReaderBuffer::with_capacity(reader, BUFFER_SIZE)
+    }
+
+    /// Create a new ReaderBuffer that reads from reader and buffers at most capacity bytes at a
+    /// time. capacity must not be zero.
+    pub fn with_capacity(reader: R, capacity: usize) -> ReaderBuffer<R> {
+        assert!(capacity > 0);
+        ReaderBuffer {
+            reader,
+            buffer: vec![0; capacity],
+            len: 0,
+            pos: 0,
+            eof: false
+        }
+    }
+
+    /// Move the data that has not been consumed yet to the start of the internal buffer and then
+    /// read from the inner reader until the buffer is full or the reader reaches its end. Returns
+    /// the number of bytes that were read, which is 0 if the buffer was already full as well as at
+    /// the end of the reader; use is_eof() to tell the two apart.
+    pub fn fill(&mut self) -> io::Result<usize> {
+        let remaining = self.len - self.pos;
+        let pos = self.pos;
+        self.buffer.copy_within(pos..pos + remaining, 0);
+        self.pos = 0;
+        self.len = remaining;
+
+        let start = self.len;
+        while !self.eof && self.len < self.buffer.len() {
+            match self.reader.read(&mut self.buffer[self.len..]) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.len += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => { }
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(self.len - start)
+    }
+
+    /// Run f with a RefReadBuffer over the window, for consumers such as the Encryptor and
+    /// Decryptor traits that take a RefReadBuffer rather than any ReadBuffer. Whatever f consumes,
+    /// rewinds, or truncates is applied to this ReaderBuffer afterwards.
+    pub fn with_read_buffer<T, F: FnOnce(&mut RefReadBuffer) -> T>(&mut self, f: F) -> T {
+        let (result, pos, len) = {
+            let mut read_buffer = RefReadBuffer::new(&self.buffer[..self.len]);
+            read_buffer.take_next(self.pos);
+            let result = f(&mut read_buffer);
+            (result, read_buffer.position(), read_buffer.capacity())
+        };
+        self.pos = pos;
+        self.len = len;
+        result
+    }
+
+    /// Whether the inner reader has reached its end, meaning that all of the remaining data is in
+    /// the window. This is the value to pass as the eof argument to an Encryptor or Decryptor.
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Return the inner reader. Data that has been read into the window but not consumed is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl <R: Read> ReadBuffer for ReaderBuffer<R> {
+    fn is_empty(&self) -> bool { self.pos == self.len }
+    fn is_full(&self) -> bool { self.pos == 0 }
+    fn remaining(&self) -> usize { self.len - self.pos }
+    fn capacity(&self) -> usize { self.len }
+
+    fn rewind(&mut self, distance: usize) {
+        assert!(distance <= self.pos, "cannot rewind past the start of the buffered window");
+        self.pos -= distance;
+    }
+    fn truncate(&mut self, amount: usize) {
+        assert!(amount <= self.len - self.pos);
+        self.len -= amount;
+    }
+    fn reset(&mut self) { self.pos = 0; }
+
+    fn peek_next(&self, count: usize) -> &[u8] { &self.buffer[self.pos..self.pos + count] }
+
+    fn take_next(&mut self, count: usize) -> &[u8] {
+        assert!(count <= self.len - self.pos);
+        let r = &self.buffer[self.pos..self.pos + count];
+        self.pos += count;
+        r
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use aes;
+    use aes::KeySize::KeySize128;
+    use aessafe;
+    use blockmodes::{CbcEncryptor, PkcsPadding};
+    use buffer::{ReadBuffer, WriteBuffer, RefReadBuffer, RefWriteBuffer, BufferResult};
+    use cryptoio::{CryptoWriter, ReaderBuffer};
+
+    fn cbc_decrypt(key: &[u8], iv: &[u8], input: &[u8]) -> Vec<u8> {
+        let mut dec = aes::cbc_decryptor(KeySize128, key, iv, PkcsPadding);
+        let mut result = Vec::new();
+        let mut read_buffer = RefReadBuffer::new(input);
+        let mut buffer = [0u8; 64];
+        let mut write_buffer = RefWriteBuffer::new(&mut buffer);
+        loop {
+            let r = dec.decrypt(&mut read_buffer, &mut write_buffer, true).unwrap();
+            result.extend_from_slice(write_buffer.take_read_buffer().take_remaining());
+            match r {
+                BufferResult::BufferUnderflow => break,
+                BufferResult::BufferOverflow => { }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_crypto_writer_cbc_round_trip() {
+        let key = [1u8; 16];
+        let iv = [3u8; 16];
+
+        for &len in [0usize, 1, 15, 16, 17, 100, 5000].iter() {
+            let plain: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let enc = CbcEncryptor::new(aessafe::AesSafe128Encryptor::new(&key), PkcsPadding,
+                iv.to_vec());
+            let mut writer = CryptoWriter::new(Vec::new(), enc);
+            for chunk in plain.chunks(7) {
+                writer.write_all(chunk).unwrap();
+            }
+            let cipher = writer.finish().unwrap();
+
+            // PKCS padding always adds between 1 and 16 bytes
+            assert_eq!(cipher.len(), (len / 16 + 1) * 16);
+            assert_eq!(cbc_decrypt(&key, &iv, &cipher), plain);
+        }
+    }
+
+    #[test]
+    fn test_crypto_writer_boxed_encryptor() {
+        let key = [7u8; 16];
+        let iv = [9u8; 16];
+        let plain = b"The quick brown fox jumps over the lazy dog";
+
+        let enc = aes::cbc_encryptor(KeySize128, &key, &iv, PkcsPadding);
+        let mut writer = CryptoWriter::new(Vec::new(), enc);
+        writer.write_all(plain).unwrap();
+        let cipher = writer.finish().unwrap();
+
+        assert_eq!(cbc_decrypt(&key, &iv, &cipher), &plain[..]);
+    }
+
+    fn encrypt(key: &[u8], iv: &[u8], plain: &[u8]) -> Vec<u8> {
+        let enc = aes::cbc_encryptor(KeySize128, key, iv, PkcsPadding);
+        let mut writer = CryptoWriter::new(Vec::new(), enc);
+        writer.write_all(plain).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_reader_buffer_cbc_decrypt() {
+        let key = [5u8; 16];
+        let iv = [6u8; 16];
+
+        // Capacities that are smaller than, not a multiple of, and larger than the block size
+        for &capacity in [1usize, 7, 16, 20, 4096].iter() {
+            for &len in [0usize, 1, 15, 16, 17, 100, 1000].iter() {
+                let plain: Vec<u8> = (0..len).map(|i| (i * 3) as u8).collect();
+                let cipher = encrypt(&key, &iv, &plain);
+
+                let mut dec = aes::cbc_decryptor(KeySize128, &key, &iv, PkcsPadding);
+                let mut reader = ReaderBuffer::with_capacity(Cursor::new(cipher), capacity);
+                let mut result = Vec::new();
+                let mut buffer = [0u8; 32];
+                loop {
+                    reader.fill().unwrap();
+                    let eof = reader.is_eof();
+                    loop {
+                        let mut write_buffer = RefWriteBuffer::new(&mut buffer);
+                        let r = reader.with_read_buffer(|read_buffer| {
+                            dec.decrypt(read_buffer, &mut write_buffer, eof)
+                        }).unwrap();
+                        result.extend_from_slice(write_buffer.take_read_buffer().take_remaining());
+                        match r {
+                            BufferResult::BufferUnderflow => break,
+                            BufferResult::BufferOverflow => { }
+                        }
+                    }
+                    if eof {
+                        break;
+                    }
+                }
+                assert!(reader.is_empty());
+                assert_eq!(result, plain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_buffer_window() {
+        let mut reader = ReaderBuffer::with_capacity(Cursor::new((0..10).collect::<Vec<u8>>()), 4);
+        assert!(reader.is_empty());
+        assert_eq!(reader.fill().unwrap(), 4);
+        assert!(!reader.is_eof());
+        assert!(reader.take_next(3) == [0, 1, 2]);
+        reader.rewind(1);
+        assert!(reader.peek_next(2) == [2, 3]);
+
+        // Refilling keeps the unconsumed bytes and drops the consumed ones
+        assert_eq!(reader.fill().unwrap(), 2);
+        assert_eq!(reader.position(), 0);
+        assert!(reader.take_remaining() == [2, 3, 4, 5]);
+        assert_eq!(reader.fill().unwrap(), 4);
+        assert!(reader.take_remaining() == [6, 7, 8, 9]);
+        assert_eq!(reader.fill().unwrap(), 0);
+        assert!(reader.is_eof());
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reader_buffer_rewind_past_window() {
+        let mut reader = ReaderBuffer::with_capacity(Cursor::new(vec![1u8; 8]), 4);
+        reader.fill().unwrap();
+        reader.take_next(4);
+        reader.fill().unwrap();
+        reader.rewind(1);
+    }
+}
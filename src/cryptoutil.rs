@@ -38,6 +38,28 @@ pub fn write_u64_le(dst: &mut[u8], mut input: u64) {
     }
 }
 
+/// Write a u128 into a vector, which must be 16 bytes long. The value is written in big-endian
+/// format.
+pub fn write_u128_be(dst: &mut[u8], mut input: u128) {
+    assert!(dst.len() == 16);
+    input = input.to_be();
+    unsafe {
+        let tmp = &input as *const _ as *const u8;
+        ptr::copy_nonoverlapping(tmp, dst.get_unchecked_mut(0), 16);
+    }
+}
+
+/// Write a u128 into a vector, which must be 16 bytes long. The value is written in little-endian
+/// format.
+pub fn write_u128_le(dst: &mut[u8], mut input: u128) {
+    assert!(dst.len() == 16);
+    input = input.to_le();
+    unsafe {
+        let tmp = &input as *const _ as *const u8;
+        ptr::copy_nonoverlapping(tmp, dst.get_unchecked_mut(0), 16);
+    }
+}
+
 /// Write a vector of u64s into a vector of bytes. The values are written in little-endian format.
 pub fn write_u64v_le(dst: &mut[u8], input: &[u64]) {
     assert!(dst.len() == 8 * input.len());
@@ -174,6 +196,36 @@ pub fn read_u32_be(input: &[u8]) -> u32 {
     }
 }
 
+/// Read the value of a vector of bytes as a u64 value in big-endian format.
+pub fn read_u64_be(input: &[u8]) -> u64 {
+    assert!(input.len() == 8);
+    let mut tmp: u64 = 0;
+    unsafe {
+        ptr::copy_nonoverlapping(input.get_unchecked(0), &mut tmp as *mut _ as *mut u8, 8);
+    }
+    u64::from_be(tmp)
+}
+
+/// Read the value of a vector of bytes as a u128 value in little-endian format.
+pub fn read_u128_le(input: &[u8]) -> u128 {
+    assert!(input.len() == 16);
+    let mut tmp: u128 = 0;
+    unsafe {
+        ptr::copy_nonoverlapping(input.get_unchecked(0), &mut tmp as *mut _ as *mut u8, 16);
+    }
+    u128::from_le(tmp)
+}
+
+/// Read the value of a vector of bytes as a u128 value in big-endian format.
+pub fn read_u128_be(input: &[u8]) -> u128 {
+    assert!(input.len() == 16);
+    let mut tmp: u128 = 0;
+    unsafe {
+        ptr::copy_nonoverlapping(input.get_unchecked(0), &mut tmp as *mut _ as *mut u8, 16);
+    }
+    u128::from_be(tmp)
+}
+
 /// XOR plaintext and keystream, storing the result in dst.
 pub fn xor_keystream(dst: &mut[u8], plaintext: &[u8], keystream: &[u8]) {
     assert!(dst.len() == plaintext.len());
@@ -188,6 +240,26 @@ pub fn xor_keystream(dst: &mut[u8], plaintext: &[u8], keystream: &[u8]) {
     }
 }
 
+/// In debug builds, panic if the input and output of a stream cipher's process() partially
+/// overlap, since the output would then overwrite input that hasn't been read yet. Any aliasing
+/// between the `&[u8]` input and the `&mut [u8]` output is undefined behaviour, even when the two
+/// slices are identical, so this is only a last line of defence against callers that build them
+/// with unsafe code or FFI. Identical slices are not reported, but are no less undefined; to
+/// encrypt or decrypt a buffer in place, use SynchronousStreamCipher::process_in_place instead.
+#[inline]
+pub fn debug_assert_no_overlap(input: &[u8], output: &[u8]) {
+    debug_assert!(
+        !partially_overlap(input.as_ptr() as usize, input.len(),
+                           output.as_ptr() as usize, output.len()),
+        "input and output of process() partially overlap");
+}
+
+// Whether the byte ranges [a, a + a_len) and [b, b + b_len) share a byte without starting at the
+// same address
+fn partially_overlap(a: usize, a_len: usize, b: usize, b_len: usize) -> bool {
+    a != b && a < b + b_len && b < a + a_len
+}
+
 /// Copy bytes from src to dest
 #[inline]
 pub fn copy_memory(src: &[u8], dst: &mut [u8]) {
@@ -207,6 +279,107 @@ pub fn zero(dst: &mut [u8]) {
     }
 }
 
+/// Swap the contents of a and b if swap is 1 and leave them unchanged if swap is 0, without any
+/// branches or memory accesses that depend on swap. swap must be exactly 0 or 1; any other value
+/// will corrupt both buffers.
+pub fn ct_swap(swap: u8, a: &mut [u8], b: &mut [u8]) {
+    assert!(a.len() == b.len());
+    // 0x00 if swap is 0 and 0xff if swap is 1
+    let mask = swap.wrapping_neg();
+    for (x, y) in a.iter_mut().zip(b.iter_mut()) {
+        let t = mask & (*x ^ *y);
+        *x ^= t;
+        *y ^= t;
+    }
+}
+
+/// Multiply a and b in GF(2^128) using the bit-reflected representation of GCM, in which the first
+/// bit of a block is the coefficient of x^0, with the field polynomial x^128 + x^7 + x^2 + x + 1.
+/// This follows algorithm 1 of NIST SP 800-38D and takes the same time for every input.
+pub fn gf128_mul(a: &[u8; 16], b: &[u8; 16]) -> [u8; 16] {
+    // x^7 + x^2 + x + 1 in the reflected representation
+    const R: u128 = 0xe1 << 120;
+    let x = u128::from_be_bytes(*a);
+    let mut v = u128::from_be_bytes(*b);
+    let mut z = 0u128;
+    for i in (0..128).rev() {
+        // All ones if the bit is set and all zeros otherwise
+        let bit = ((x >> i) & 1).wrapping_neg();
+        z ^= v & bit;
+        let lsb = (v & 1).wrapping_neg();
+        v = (v >> 1) ^ (R & lsb);
+    }
+    z.to_be_bytes()
+}
+
+/// Multiply x by the generator in GF(2^128), using the big-endian representation and field
+/// polynomial x^128 + x^7 + x^2 + x + 1 of CMAC and SIV (a left shift by one bit that is reduced
+/// by XORing 0x87 into the last byte if the top bit was set). This takes the same time for every
+/// input.
+pub fn gf128_double(x: &[u8; 16]) -> [u8; 16] {
+    let v = u128::from_be_bytes(*x);
+    let msb = (v >> 127).wrapping_neg();
+    ((v << 1) ^ (0x87 & msb)).to_be_bytes()
+}
+
+// Convert a value between 0 and 15 to a lower case hex digit without branches or table lookups.
+#[inline]
+fn nibble_to_hex_ct(n: u8) -> u8 {
+    let n = n as i16;
+    // 0 if n <= 9 and -1 (all bits set) if n > 9
+    let alpha = (9 - n) >> 8;
+    (48 + n + (alpha & 39)) as u8
+}
+
+// Convert a hex digit, of either case, to its value. The second element of the result is 0 if c is
+// a hex digit and 0xff if it is not.
+#[inline]
+fn hex_to_nibble_ct(c: u8) -> (u8, u8) {
+    let c = c as i16;
+    let lower = c | 0x20;
+    // -1 (all bits set) if c is in the range '0'..'9' or 'a'..'f' respectively, otherwise 0
+    let digit = ((47 - c) & (c - 58)) >> 8;
+    let alpha = ((96 - lower) & (lower - 103)) >> 8;
+    let value = (digit & (c - 48)) | (alpha & (lower - 87));
+    (value as u8, !(digit | alpha) as u8)
+}
+
+/// Encode data as a lower case hex string. Unlike rustc_serialize's ToHex, this doesn't use a
+/// lookup table, so it doesn't leak the encoded bytes through data dependent memory accesses. It
+/// is intended for secret values such as keys.
+pub fn to_hex_ct(data: &[u8]) -> String {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    for &b in data.iter() {
+        out.push(nibble_to_hex_ct(b >> 4));
+        out.push(nibble_to_hex_ct(b & 0x0f));
+    }
+    String::from_utf8(out).unwrap()
+}
+
+/// Decode a hex string, which may use upper or lower case digits, without any branches or table
+/// lookups that depend on its contents. An error is returned if the string has an odd length or
+/// contains anything other than hex digits; in that case no information about where the invalid
+/// character was found is revealed.
+pub fn from_hex_ct(hex: &str) -> Result<Vec<u8>, &'static str> {
+    let hex = hex.as_bytes();
+    if !hex.len().is_multiple_of(2) {
+        return Err("hex string has an odd length");
+    }
+    let mut out = Vec::with_capacity(hex.len() / 2);
+    let mut invalid = 0u8;
+    for pair in hex.chunks(2) {
+        let (hi, hi_invalid) = hex_to_nibble_ct(pair[0]);
+        let (lo, lo_invalid) = hex_to_nibble_ct(pair[1]);
+        invalid |= hi_invalid | lo_invalid;
+        out.push((hi << 4) | lo);
+    }
+    if invalid != 0 {
+        zero(&mut out);
+        return Err("hex string contains an invalid character");
+    }
+    Ok(out)
+}
+
 /// An extension trait to implement a few useful serialization
 /// methods on types that implement Write
 pub trait WriteExt {
@@ -215,6 +388,10 @@ pub trait WriteExt {
     fn write_u32_be(&mut self, val: u32) -> io::Result<()>;
     fn write_u64_le(&mut self, val: u64) -> io::Result<()>;
     fn write_u64_be(&mut self, val: u64) -> io::Result<()>;
+    /// Write the nbytes low-order bytes of val in little-endian order. nbytes must be at most 8.
+    fn write_uint_le(&mut self, val: u64, nbytes: usize) -> io::Result<()>;
+    /// Write the nbytes low-order bytes of val in big-endian order. nbytes must be at most 8.
+    fn write_uint_be(&mut self, val: u64, nbytes: usize) -> io::Result<()>;
 }
 
 impl <T> WriteExt for T where T: io::Write {
@@ -242,6 +419,18 @@ impl <T> WriteExt for T where T: io::Write {
         write_u64_be(&mut buff, val);
         self.write_all(&buff)
     }
+    fn write_uint_le(&mut self, val: u64, nbytes: usize) -> io::Result<()> {
+        assert!(nbytes <= 8);
+        let mut buff = [0u8; 8];
+        write_u64_le(&mut buff, val);
+        self.write_all(&buff[..nbytes])
+    }
+    fn write_uint_be(&mut self, val: u64, nbytes: usize) -> io::Result<()> {
+        assert!(nbytes <= 8);
+        let mut buff = [0u8; 8];
+        write_u64_be(&mut buff, val);
+        self.write_all(&buff[8 - nbytes..])
+    }
 }
 
 /// symm_enc_or_dec() implements the necessary functionality to turn a SynchronousStreamCipher into
@@ -359,127 +548,111 @@ pub trait FixedBuffer {
     fn size(&self) -> usize;
 }
 
-macro_rules! impl_fixed_buffer( ($name:ident, $size:expr) => (
-    impl FixedBuffer for $name {
-        fn input<F: FnMut(&[u8])>(&mut self, input: &[u8], mut func: F) {
-            let mut i = 0;
-
-            // FIXME: #6304 - This local variable shouldn't be necessary.
-            let size = $size;
-
-            // If there is already data in the buffer, copy as much as we can into it and process
-            // the data if the buffer becomes full.
-            if self.buffer_idx != 0 {
-                let buffer_remaining = size - self.buffer_idx;
-                if input.len() >= buffer_remaining {
-                        copy_memory(
-                            &input[..buffer_remaining],
-                            &mut self.buffer[self.buffer_idx..size]);
-                    self.buffer_idx = 0;
-                    func(&self.buffer);
-                    i += buffer_remaining;
-                } else {
-                    copy_memory(
-                        input,
-                        &mut self.buffer[self.buffer_idx..self.buffer_idx + input.len()]);
-                    self.buffer_idx += input.len();
-                    return;
-                }
-            }
-
-            // While we have at least a full buffer size chunks's worth of data, process that data
-            // without copying it into the buffer
-            while input.len() - i >= size {
-                func(&input[i..i + size]);
-                i += size;
-            }
-
-            // Copy any input data into the buffer. At this point in the method, the ammount of
-            // data left in the input vector will be less than the buffer size and the buffer will
-            // be empty.
-            let input_remaining = input.len() - i;
-            copy_memory(
-                &input[i..],
-                &mut self.buffer[0..input_remaining]);
-            self.buffer_idx += input_remaining;
-        }
+/// A fixed size buffer of N bytes useful for cryptographic operations.
+#[derive(Copy)]
+pub struct FixedBufferN<const N: usize> {
+    buffer: [u8; N],
+    buffer_idx: usize,
+}
 
-        fn reset(&mut self) {
-            self.buffer_idx = 0;
-        }
+impl <const N: usize> Clone for FixedBufferN<N> { fn clone(&self) -> FixedBufferN<N> { *self } }
 
-        fn zero_until(&mut self, idx: usize) {
-            assert!(idx >= self.buffer_idx);
-            zero(&mut self.buffer[self.buffer_idx..idx]);
-            self.buffer_idx = idx;
+impl <const N: usize> FixedBufferN<N> {
+    /// Create a new buffer
+    pub fn new() -> FixedBufferN<N> {
+        FixedBufferN {
+            buffer: [0u8; N],
+            buffer_idx: 0
         }
+    }
+}
 
-        fn next<'s>(&'s mut self, len: usize) -> &'s mut [u8] {
-            self.buffer_idx += len;
-            &mut self.buffer[self.buffer_idx - len..self.buffer_idx]
-        }
+impl <const N: usize> Default for FixedBufferN<N> {
+    fn default() -> FixedBufferN<N> {
+        FixedBufferN::new()
+    }
+}
 
-        fn full_buffer<'s>(&'s mut self) -> &'s [u8] {
-            assert!(self.buffer_idx == $size);
-            self.buffer_idx = 0;
-            &self.buffer[..$size]
+impl <const N: usize> FixedBuffer for FixedBufferN<N> {
+    fn input<F: FnMut(&[u8])>(&mut self, input: &[u8], mut func: F) {
+        let mut i = 0;
+
+        // If there is already data in the buffer, copy as much as we can into it and process
+        // the data if the buffer becomes full.
+        if self.buffer_idx != 0 {
+            let buffer_remaining = N - self.buffer_idx;
+            if input.len() >= buffer_remaining {
+                copy_memory(
+                    &input[..buffer_remaining],
+                    &mut self.buffer[self.buffer_idx..N]);
+                self.buffer_idx = 0;
+                func(&self.buffer);
+                i += buffer_remaining;
+            } else {
+                copy_memory(
+                    input,
+                    &mut self.buffer[self.buffer_idx..self.buffer_idx + input.len()]);
+                self.buffer_idx += input.len();
+                return;
+            }
         }
 
-        fn current_buffer<'s>(&'s mut self) -> &'s [u8] {
-            let tmp = self.buffer_idx;
-            self.buffer_idx = 0;
-            &self.buffer[..tmp]
+        // While we have at least a full buffer size chunks's worth of data, process that data
+        // without copying it into the buffer
+        while input.len() - i >= N {
+            func(&input[i..i + N]);
+            i += N;
         }
 
-        fn position(&self) -> usize { self.buffer_idx }
+        // Copy any input data into the buffer. At this point in the method, the ammount of
+        // data left in the input vector will be less than the buffer size and the buffer will
+        // be empty.
+        let input_remaining = input.len() - i;
+        copy_memory(
+            &input[i..],
+            &mut self.buffer[0..input_remaining]);
+        self.buffer_idx += input_remaining;
+    }
 
-        fn remaining(&self) -> usize { $size - self.buffer_idx }
+    fn reset(&mut self) {
+        self.buffer_idx = 0;
+    }
 
-        fn size(&self) -> usize { $size }
+    fn zero_until(&mut self, idx: usize) {
+        assert!(idx >= self.buffer_idx);
+        zero(&mut self.buffer[self.buffer_idx..idx]);
+        self.buffer_idx = idx;
     }
-));
 
-/// A fixed size buffer of 64 bytes useful for cryptographic operations.
-#[derive(Copy)]
-pub struct FixedBuffer64 {
-    buffer: [u8; 64],
-    buffer_idx: usize,
-}
+    fn next(&mut self, len: usize) -> &mut [u8] {
+        self.buffer_idx += len;
+        &mut self.buffer[self.buffer_idx - len..self.buffer_idx]
+    }
 
-impl Clone for FixedBuffer64 { fn clone(&self) -> FixedBuffer64 { *self } }
+    fn full_buffer(&mut self) -> &[u8] {
+        assert!(self.buffer_idx == N);
+        self.buffer_idx = 0;
+        &self.buffer[..N]
+    }
 
-impl FixedBuffer64 {
-    /// Create a new buffer
-    pub fn new() -> FixedBuffer64 {
-        FixedBuffer64 {
-            buffer: [0u8; 64],
-            buffer_idx: 0
-        }
+    fn current_buffer(&mut self) -> &[u8] {
+        let tmp = self.buffer_idx;
+        self.buffer_idx = 0;
+        &self.buffer[..tmp]
     }
-}
 
-impl_fixed_buffer!(FixedBuffer64, 64);
+    fn position(&self) -> usize { self.buffer_idx }
 
-/// A fixed size buffer of 128 bytes useful for cryptographic operations.
-#[derive(Copy)]
-pub struct FixedBuffer128 {
-    buffer: [u8; 128],
-    buffer_idx: usize,
-}
+    fn remaining(&self) -> usize { N - self.buffer_idx }
 
-impl Clone for FixedBuffer128 { fn clone(&self) -> FixedBuffer128 { *self } }
-
-impl FixedBuffer128 {
-    /// Create a new buffer
-    pub fn new() -> FixedBuffer128 {
-        FixedBuffer128 {
-            buffer: [0u8; 128],
-            buffer_idx: 0
-        }
-    }
+    fn size(&self) -> usize { N }
 }
 
-impl_fixed_buffer!(FixedBuffer128, 128);
+/// A fixed size buffer of 64 bytes useful for cryptographic operations.
+pub type FixedBuffer64 = FixedBufferN<64>;
+
+/// A fixed size buffer of 128 bytes useful for cryptographic operations.
+pub type FixedBuffer128 = FixedBufferN<128>;
 
 
 /// The StandardPadding trait adds a method useful for various hash algorithms to a FixedBuffer
@@ -511,13 +684,21 @@ impl <T: FixedBuffer> StandardPadding for T {
 #[cfg(test)]
 pub mod test {
     use std;
+    use std::cmp;
     use std::iter::repeat;
 
     use rand::IsaacRng;
     use rand::distributions::{IndependentSample, Range};
 
-    use cryptoutil::{add_bytes_to_bits, add_bytes_to_bits_tuple};
+    use rand::Rng;
+
+    use cryptoutil::{add_bytes_to_bits, add_bytes_to_bits_tuple, ct_swap, from_hex_ct, to_hex_ct,
+        debug_assert_no_overlap, partially_overlap,
+        read_u128_be, read_u128_le, write_u128_be, write_u128_le,
+        gf128_double, gf128_mul, FixedBuffer, FixedBuffer64, FixedBuffer128, FixedBufferN,
+        StandardPadding, WriteExt};
     use digest::Digest;
+    use serialize::hex::{FromHex, ToHex};
 
     /// Feed 1,000,000 'a's into the digest with varying input sizes and check that the result is
     /// correct.
@@ -543,6 +724,200 @@ pub mod test {
         assert!(expected == &result_str[..]);
     }
 
+    #[test]
+    fn test_ct_swap_no_swap() {
+        let mut a = [1u8, 2, 3, 4];
+        let mut b = [5u8, 6, 7, 8];
+        ct_swap(0, &mut a, &mut b);
+        assert_eq!(a, [1, 2, 3, 4]);
+        assert_eq!(b, [5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_ct_swap_swap() {
+        let mut a = [1u8, 2, 3, 4];
+        let mut b = [5u8, 6, 7, 8];
+        ct_swap(1, &mut a, &mut b);
+        assert_eq!(a, [5, 6, 7, 8]);
+        assert_eq!(b, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_ct_swap_random() {
+        let mut rng = IsaacRng::new_unseeded();
+        for len in 0..64 {
+            let a: Vec<u8> = rng.gen_iter().take(len).collect();
+            let b: Vec<u8> = rng.gen_iter().take(len).collect();
+            for swap in 0..2u8 {
+                let mut x = a.clone();
+                let mut y = b.clone();
+                ct_swap(swap, &mut x, &mut y);
+                if swap == 1 {
+                    assert!(x == b && y == a);
+                } else {
+                    assert!(x == a && y == b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_ct_swap_unequal_lengths() {
+        ct_swap(1, &mut [0u8; 3], &mut [0u8; 4]);
+    }
+
+    fn block(hex: &str) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        out.copy_from_slice(&hex.from_hex().unwrap());
+        out
+    }
+
+    #[test]
+    fn test_gf128_mul() {
+        // The GHASH computation of test case 2 from the GCM specification: X1 = C * H and
+        // X2 = (X1 ^ len(A) || len(C)) * H
+        let h = block("66e94bd4ef8a2c3b884cfa59ca342b2e");
+        let c = block("0388dace60b6a392f328c2b971b2fe78");
+        let x1 = gf128_mul(&c, &h);
+        assert_eq!(x1, block("5e2ec746917062882c85b0685353deb7"));
+        let mut len_block = x1;
+        len_block[15] ^= 0x80;
+        assert_eq!(gf128_mul(&len_block, &h), block("f38cbb1ad69223dcc3457ae5b6b0f885"));
+
+        // The first bit of a block is the multiplicative identity, and multiplication commutes
+        let mut one = [0u8; 16];
+        one[0] = 0x80;
+        assert_eq!(gf128_mul(&one, &h), h);
+        assert_eq!(gf128_mul(&h, &c), x1);
+        assert_eq!(gf128_mul(&[0u8; 16], &h), [0u8; 16]);
+    }
+
+    #[test]
+    fn test_gf128_double() {
+        // The CMAC subkeys for the AES-128 key from NIST SP 800-38B, Appendix D.1
+        let l = block("7df76b0c1ab899b33e42f047b91b546f");
+        let k1 = gf128_double(&l);
+        assert_eq!(k1, block("fbeed618357133667c85e08f7236a8de"));
+        assert_eq!(gf128_double(&k1), block("f7ddac306ae266ccf90bc11ee46d513b"));
+    }
+
+    #[test]
+    fn test_hex_ct_random() {
+        let mut rng = IsaacRng::new_unseeded();
+        for len in 0..64 {
+            let data: Vec<u8> = rng.gen_iter().take(len).collect();
+            let hex = to_hex_ct(&data);
+            assert_eq!(hex, data.to_hex());
+            assert_eq!(from_hex_ct(&hex).unwrap(), data);
+            assert_eq!(from_hex_ct(&hex.to_uppercase()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_hex_ct_all_bytes() {
+        let data: Vec<u8> = (0..256).map(|x| x as u8).collect();
+        let hex = to_hex_ct(&data);
+        assert_eq!(hex, data.to_hex());
+        assert_eq!(from_hex_ct(&hex).unwrap(), hex.from_hex().unwrap());
+    }
+
+    #[test]
+    fn test_from_hex_ct_invalid() {
+        assert!(from_hex_ct("").unwrap().is_empty());
+        assert!(from_hex_ct("abc").is_err());
+        assert!(from_hex_ct("0g").is_err());
+        assert!(from_hex_ct("g0").is_err());
+        assert!(from_hex_ct("0 ").is_err());
+        assert!(from_hex_ct("/0").is_err());
+        assert!(from_hex_ct(":0").is_err());
+        assert!(from_hex_ct("@0").is_err());
+        assert!(from_hex_ct("`0").is_err());
+        assert!(from_hex_ct("G0").is_err());
+        assert!(from_hex_ct("00\u{e9}").is_err());
+    }
+
+    #[test]
+    fn test_u128_be_le() {
+        let value: u128 = 0x000102030405060708090a0b0c0d0e0f;
+        let be: Vec<u8> = (0..16).collect();
+        let le: Vec<u8> = (0..16).rev().collect();
+
+        let mut buf = [0u8; 16];
+        write_u128_be(&mut buf, value);
+        assert!(buf[..] == be[..]);
+        write_u128_le(&mut buf, value);
+        assert!(buf[..] == le[..]);
+
+        assert_eq!(read_u128_be(&be), value);
+        assert_eq!(read_u128_le(&le), value);
+    }
+
+    #[test]
+    fn test_u128_round_trip() {
+        let mut rng = IsaacRng::new_unseeded();
+        let mut buf = [0u8; 16];
+        for _ in 0..100 {
+            let value = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+            write_u128_be(&mut buf, value);
+            assert_eq!(read_u128_be(&buf), value);
+            write_u128_le(&mut buf, value);
+            assert_eq!(read_u128_le(&buf), value);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_read_u128_empty() {
+        read_u128_be(&[]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_write_u128_short() {
+        write_u128_le(&mut [0u8; 15], 0);
+    }
+
+    #[test]
+    fn test_write_uint() {
+        let mut out = Vec::new();
+        out.write_uint_le(0x1234, 2).unwrap();
+        assert_eq!(out, [0x34, 0x12]);
+
+        let mut out = Vec::new();
+        out.write_uint_be(0x1234, 2).unwrap();
+        assert_eq!(out, [0x12, 0x34]);
+
+        // Only the low-order bytes are written
+        let mut out = Vec::new();
+        out.write_uint_le(0x0102030405060708, 3).unwrap();
+        out.write_uint_be(0x0102030405060708, 3).unwrap();
+        out.write_uint_le(0xff, 0).unwrap();
+        assert_eq!(out, [0x08, 0x07, 0x06, 0x06, 0x07, 0x08]);
+    }
+
+    #[test]
+    fn test_write_uint_full_width() {
+        let value = 0x0123456789abcdef;
+        let mut uint = Vec::new();
+        let mut fixed = Vec::new();
+        uint.write_uint_le(value, 8).unwrap();
+        fixed.write_u64_le(value).unwrap();
+        assert_eq!(uint, fixed);
+
+        let mut uint = Vec::new();
+        let mut fixed = Vec::new();
+        uint.write_uint_be(value, 8).unwrap();
+        fixed.write_u64_be(value).unwrap();
+        assert_eq!(uint, fixed);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_write_uint_too_wide() {
+        let _ = Vec::new().write_uint_be(0, 9);
+    }
+
     // A normal addition - no overflow occurs
     #[test]
     fn test_add_bytes_to_bits_ok() {
@@ -589,4 +964,107 @@ pub mod test {
         let value: u64 = std::u64::MAX;
         add_bytes_to_bits_tuple((value - 1, 0), 0x8000000000000000);
     }
+
+    // Feed data into a FixedBuffer in randomly sized chunks and check that the blocks it processes,
+    // together with what is left in the buffer, are exactly the data split into size byte blocks.
+    fn test_fixed_buffer_input<B: FixedBuffer>(buffer: &mut B) {
+        let size = buffer.size();
+        let mut rng = IsaacRng::new_unseeded();
+        let range = Range::new(0, 3 * size);
+        let data: Vec<u8> = (0..10 * size + 7).map(|i| i as u8).collect();
+
+        let mut blocks = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let len = cmp::min(range.ind_sample(&mut rng), data.len() - pos);
+            buffer.input(&data[pos..pos + len], |block| {
+                assert_eq!(block.len(), size);
+                blocks.extend_from_slice(block);
+            });
+            pos += len;
+        }
+
+        assert_eq!(blocks.len(), 10 * size);
+        assert_eq!(buffer.position(), 7);
+        assert_eq!(buffer.remaining(), size - 7);
+        assert!(blocks[..] == data[..10 * size]);
+        assert!(buffer.current_buffer() == &data[10 * size..]);
+        assert_eq!(buffer.position(), 0);
+    }
+
+    #[test]
+    fn test_fixed_buffer_sizes() {
+        test_fixed_buffer_input(&mut FixedBuffer64::new());
+        test_fixed_buffer_input(&mut FixedBuffer128::new());
+        test_fixed_buffer_input(&mut FixedBufferN::<72>::new());
+        test_fixed_buffer_input(&mut FixedBufferN::<136>::new());
+        assert_eq!(FixedBuffer64::new().size(), 64);
+        assert_eq!(FixedBuffer128::new().size(), 128);
+    }
+
+    // Apply standard padding leaving rem bytes free after used bytes have been input, and return
+    // every byte processed or left in the buffer, excluding the rem free bytes.
+    fn padded<B: FixedBuffer>(buffer: &mut B, used: usize, rem: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        buffer.reset();
+        buffer.input(&vec![0xaa; used], |block| out.extend_from_slice(block));
+        buffer.standard_padding(rem, |block| out.extend_from_slice(block));
+        assert_eq!(buffer.remaining(), rem);
+        out.extend_from_slice(buffer.current_buffer());
+        out
+    }
+
+    #[test]
+    fn test_fixed_buffer_standard_padding() {
+        let mut buffer = FixedBufferN::<72>::new();
+
+        // The padding fits in the same block
+        let out = padded(&mut buffer, 10, 8);
+        assert_eq!(out.len(), 64);
+        assert!(out[..10].iter().all(|&x| x == 0xaa));
+        assert_eq!(out[10], 0x80);
+        assert!(out[11..64].iter().all(|&x| x == 0));
+
+        // The padding spills over into a second block
+        let out = padded(&mut buffer, 70, 8);
+        assert_eq!(out.len(), 136);
+        assert_eq!(out[70], 0x80);
+        assert!(out[71..136].iter().all(|&x| x == 0));
+
+        // The same thing happens with the type aliases
+        let out64 = padded(&mut FixedBuffer64::new(), 60, 8);
+        let outn = padded(&mut FixedBufferN::<64>::new(), 60, 8);
+        assert_eq!(out64.len(), 120);
+        assert!(out64 == outn);
+    }
+
+    #[test]
+    fn test_partially_overlap() {
+        assert!(!partially_overlap(100, 10, 100, 10));
+        assert!(!partially_overlap(100, 10, 110, 10));
+        assert!(!partially_overlap(110, 10, 100, 10));
+        assert!(partially_overlap(100, 10, 101, 10));
+        assert!(partially_overlap(101, 10, 100, 10));
+        assert!(partially_overlap(100, 10, 109, 10));
+        assert!(!partially_overlap(100, 0, 100, 0));
+        assert!(!partially_overlap(100, 0, 105, 0));
+    }
+
+    #[test]
+    fn test_debug_assert_no_overlap_allowed() {
+        let buf = [0u8; 32];
+        // Not reported, although passing the same memory to process() as input and output is not
+        // allowed either
+        debug_assert_no_overlap(&buf[..16], &buf[..16]);
+        debug_assert_no_overlap(&buf[..16], &buf[16..]);
+        debug_assert_no_overlap(&buf[16..], &buf[..16]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "partially overlap")]
+    fn test_debug_assert_no_overlap_partial() {
+        let buf = [0u8; 32];
+        debug_assert_no_overlap(&buf[..16], &buf[8..24]);
+    }
 }
@@ -1,6 +1,5 @@
 use std::ops::{Add, Sub, Mul};
 use std::cmp::{Eq, PartialEq,min};
-use util::{fixed_time_eq};
 use step_by::RangeExt;
 
 /*
@@ -538,6 +537,8 @@ impl Fe {
         ]
     }
 
+    /// Swap self and other if do_swap is 1, and leave both unchanged if it is 0, in constant time.
+    /// do_swap must be 0 or 1.
     pub fn maybe_swap_with(&mut self, other: &mut Fe, do_swap: i32) {
         let &mut Fe(f) = self;
         let &mut Fe(g) = other;
@@ -588,6 +589,8 @@ impl Fe {
                      g5^x5, g6^x6, g7^x7, g8^x8, g9^x9]);
     }
 
+    /// Set self to other if do_swap is 1, and leave it unchanged if it is 0, in constant time.
+    /// do_swap must be 0 or 1.
     pub fn maybe_set(&mut self, other: &Fe, do_swap: i32) {
         let &mut Fe(f) = self;
         let &Fe(g) = other;
@@ -1019,9 +1022,7 @@ impl Fe {
 
 
     fn is_nonzero(&self) -> bool {
-        let bs = self.to_bytes();
-        let zero = [0; 32];
-        !fixed_time_eq(bs.as_ref(), zero.as_ref())
+        fe_iszero(self) == 0
     }
 
     fn is_negative(&self) -> bool {
@@ -2155,9 +2156,23 @@ pub fn curve25519_base(x: &[u8]) -> [u8; 32] {
     curve25519(x, base.as_ref())
 }
 
+/// Return 1 if f is zero modulo 2^255 - 19, and 0 otherwise. The running time does not depend on
+/// the value of f.
+pub fn fe_iszero(f: &Fe) -> u8 {
+    let acc = f.to_bytes().iter().fold(0u32, |acc, &b| acc | b as u32);
+    (acc.wrapping_sub(1) >> 31) as u8
+}
+
+/// Set f to g if b is 1, and leave it unchanged if b is 0. The running time and memory accesses do
+/// not depend on b, which must be 0 or 1.
+pub fn fe_cmov(f: &mut Fe, g: &Fe, b: u8) {
+    debug_assert!(b <= 1);
+    f.maybe_set(g, b as i32);
+}
+
 #[cfg(test)]
 mod tests {
-    use curve25519::{Fe, curve25519_base};
+    use curve25519::{Fe, curve25519_base, fe_cmov, fe_iszero};
 
     #[test]
     fn from_to_bytes_preserves() {
@@ -2187,6 +2202,33 @@ mod tests {
         assert!(g == f_initial);
     }
 
+    #[test]
+    fn cmov_test() {
+        let mut f = Fe([10,20,30,40,50,60,70,80,90,100]);
+        let g = Fe([11,21,31,41,51,61,71,81,91,101]);
+        let f_initial = f;
+        fe_cmov(&mut f, &g, 0);
+        assert!(f == f_initial);
+
+        fe_cmov(&mut f, &g, 1);
+        assert!(f == g);
+    }
+
+    #[test]
+    fn iszero_test() {
+        assert_eq!(fe_iszero(&Fe([0,0,0,0,0,0,0,0,0,0])), 1);
+        assert_eq!(fe_iszero(&Fe([1,0,0,0,0,0,0,0,0,0])), 0);
+        assert_eq!(fe_iszero(&Fe([0,0,0,0,0,0,0,0,0,1])), 0);
+
+        // p = 2^255 - 19 is congruent to zero
+        let mut p = [0xffu8; 32];
+        p[0] = 0xed;
+        p[31] = 0x7f;
+        assert_eq!(fe_iszero(&Fe::from_bytes(&p)), 1);
+        p[0] = 0xec;
+        assert_eq!(fe_iszero(&Fe::from_bytes(&p)), 0);
+    }
+
     struct CurveGen {
         which: u32
     }
@@ -0,0 +1,101 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module defines the DerivedKey type which holds the output of a Key Derivation Function.
+ */
+
+use util::secure_memset;
+
+/**
+ * A DerivedKey holds the key material produced by a Key Derivation Function such as PBKDF2, Scrypt,
+ * or HKDF. It is never empty, and its contents are zeroed when it is dropped.
+ *
+ * Applications often need several keys from one KDF invocation (for example, an encryption key and
+ * a MAC key). split_at() checks that the requested split actually lies within the derived key
+ * material rather than silently producing a short key.
+ */
+#[derive(Clone)]
+pub struct DerivedKey {
+    key: Vec<u8>
+}
+
+impl DerivedKey {
+    /**
+     * Create a new DerivedKey of len bytes, filled in by derive.
+     *
+     * # Arguments
+     * * len - The length of the key to derive. This must not be zero.
+     * * derive - A function that fills the buffer it is passed with the derived key material.
+     *
+     */
+    pub fn new<F: FnOnce(&mut [u8])>(len: usize, derive: F) -> DerivedKey {
+        assert!(len > 0, "the length of a derived key must not be zero");
+        let mut key = vec![0; len];
+        derive(&mut key);
+        DerivedKey {
+            key
+        }
+    }
+
+    /**
+     * Get the derived key material.
+     */
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /**
+     * Split the derived key material into the first n bytes and the remainder. Panics if n is
+     * greater than the length of the key.
+     */
+    pub fn split_at(&self, n: usize) -> (&[u8], &[u8]) {
+        assert!(n <= self.key.len(), "split point is beyond the end of the derived key");
+        self.key.split_at(n)
+    }
+}
+
+impl Drop for DerivedKey {
+    fn drop(&mut self) {
+        secure_memset(&mut self.key, 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use derivedkey::DerivedKey;
+
+    #[test]
+    fn test_new() {
+        let key = DerivedKey::new(5, |out| {
+            for (i, b) in out.iter_mut().enumerate() {
+                *b = i as u8;
+            }
+        });
+        assert_eq!(key.as_bytes(), &[0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_zero_length() {
+        DerivedKey::new(0, |_| { });
+    }
+
+    #[test]
+    fn test_split_at() {
+        let key = DerivedKey::new(4, |out| out.copy_from_slice(&[1, 2, 3, 4]));
+        assert_eq!(key.split_at(1), (&[1u8][..], &[2u8, 3, 4][..]));
+        assert_eq!(key.split_at(0), (&[][..], &[1u8, 2, 3, 4][..]));
+        assert_eq!(key.split_at(4), (&[1u8, 2, 3, 4][..], &[][..]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_at_out_of_range() {
+        let key = DerivedKey::new(4, |_| { });
+        key.split_at(5);
+    }
+}
@@ -8,6 +8,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::cmp;
 use std::iter::repeat;
 
 /**
@@ -24,6 +25,20 @@ pub trait Digest {
      */
     fn input(&mut self, input: &[u8]);
 
+    /**
+     * Provide message data that is split across several slices, such as a header and a body,
+     * without concatenating them first. This is the same as calling input() on each slice in order.
+     *
+     * # Arguments
+     *
+     * * parts - The slices of message data
+     */
+    fn input_all<'a, I: IntoIterator<Item=&'a [u8]>>(&mut self, parts: I) where Self: Sized {
+        for part in parts {
+            self.input(part);
+        }
+    }
+
     /**
      * Retrieve the digest result. This method may be called multiple times.
      *
@@ -33,6 +48,46 @@ pub trait Digest {
      */
     fn result(&mut self, out: &mut [u8]);
 
+    /**
+     * Retrieve the leading bytes of the digest result, writing min(out.len(), output_bytes())
+     * bytes to the start of out. A truncated digest of a Merkle-Damgård hash such as SHA-256 does
+     * not reveal the complete internal state, so it can't be extended by a naive length extension
+     * attack.
+     *
+     * # Arguments
+     *
+     * * out - the vector to hold the truncated result.
+     */
+    fn result_truncated(&mut self, out: &mut [u8]) {
+        let mut buf: Vec<u8> = repeat(0).take(self.output_bytes()).collect();
+        self.result(&mut buf);
+        let count = cmp::min(out.len(), buf.len());
+        out[..count].copy_from_slice(&buf[..count]);
+    }
+
+    /**
+     * Retrieve the digest result, consuming the digest, for callers that only need one result.
+     *
+     * Note that most of the digests in this crate are Copy, so calling finish() on one of them
+     * directly consumes a copy and the original stays usable. To have the compiler reject any use of the
+     * digest after it has been finished, hold it in a type that isn't Copy, such as a Box:
+     *
+     * ```compile_fail
+     * use crypto::digest::Digest;
+     * use crypto::sha2::Sha256;
+     *
+     * let mut sh: Box<dyn Digest> = Box::new(Sha256::new());
+     * sh.input_str("abc");
+     * let hash = sh.finish();
+     * sh.input_str("def"); // error: use of moved value
+     * ```
+     */
+    fn finish(mut self) -> Vec<u8> where Self: Sized {
+        let mut out = vec![0; self.output_bytes()];
+        self.result(&mut out);
+        out
+    }
+
     /**
      * Reset the digest. This method must be called after result() and before supplying more
      * data.
@@ -52,10 +107,19 @@ pub trait Digest {
     }
 
     /**
-     * Get the block size in bytes.
+     * Get the block size in bytes. This is the amount of input the compression function consumes
+     * at a time, which Hmac uses to size its key pads. Note that output_bits() is in bits.
      */
     fn block_size(&self) -> usize;
 
+    /**
+     * Get the block size in bytes. This is the same as block_size(), under a name that makes the
+     * unit explicit next to output_bits().
+     */
+    fn block_size_bytes(&self) -> usize {
+        self.block_size()
+    }
+
     /**
      * Convenience function that feeds a string into a digest.
      *
@@ -79,3 +143,295 @@ pub trait Digest {
         buf[..].to_hex()
     }
 }
+
+/**
+ * A boxed Digest is itself a Digest, so a digest chosen at runtime can be used anywhere a generic
+ * Digest is expected, for example as Hmac<Box<dyn Digest>>.
+ */
+impl <D: Digest + ?Sized> Digest for Box<D> {
+    fn input(&mut self, input: &[u8]) { (**self).input(input) }
+    fn result(&mut self, out: &mut [u8]) { (**self).result(out) }
+    fn result_truncated(&mut self, out: &mut [u8]) { (**self).result_truncated(out) }
+    fn reset(&mut self) { (**self).reset() }
+    fn output_bits(&self) -> usize { (**self).output_bits() }
+    fn output_bytes(&self) -> usize { (**self).output_bytes() }
+    fn block_size(&self) -> usize { (**self).block_size() }
+    fn block_size_bytes(&self) -> usize { (**self).block_size_bytes() }
+}
+
+/**
+ * Compute the digest of the concatenation of parts, feeding each slice to the digest in order.
+ *
+ * # Arguments
+ *
+ * * digest - The Digest to use. It is reset before use.
+ * * parts - The slices to hash
+ */
+pub fn concat_hash<D: Digest>(mut digest: D, parts: &[&[u8]]) -> Vec<u8> {
+    digest.reset();
+    digest.input_all(parts.iter().cloned());
+    let mut out = vec![0; digest.output_bytes()];
+    digest.result(&mut out);
+    out
+}
+
+/**
+ * Compute H(H(data)). With SHA-256 this is the SHA256d function that Bitcoin calls hash256.
+ *
+ * # Arguments
+ *
+ * * digest - The Digest to use. It is reset before use.
+ * * data - The data to hash
+ */
+pub fn double_hash<D: Digest>(mut digest: D, data: &[u8]) -> Vec<u8> {
+    digest.reset();
+    digest.input(data);
+    let mut out = vec![0; digest.output_bytes()];
+    digest.result(&mut out);
+    digest.reset();
+    digest.input(&out);
+    digest.result(&mut out);
+    out
+}
+
+/**
+ * A Digest that inputs a fixed domain tag before any user data, so that hashes computed for
+ * different purposes can't be mistaken for one another. The tag is input when the DomainHasher is
+ * created and again after every reset, so the result is always H(tag || data).
+ *
+ * The tag is simply prepended, so no tag used alongside it may be a prefix of another: with the
+ * tags "app" and "app-v1", "app" hashing "-v1" gives the same result as "app-v1" hashing nothing.
+ * Use fixed length tags, or tags ending in a separator byte that never occurs within them.
+ */
+#[derive(Clone)]
+pub struct DomainHasher<D: Digest> {
+    digest: D,
+    tag: Vec<u8>
+}
+
+impl <D: Digest> DomainHasher<D> {
+    /**
+     * Create a new DomainHasher.
+     *
+     * # Arguments
+     *
+     * * digest - The Digest to use. It is reset before the tag is input.
+     * * tag - The domain tag
+     */
+    pub fn new(mut digest: D, tag: &[u8]) -> DomainHasher<D> {
+        digest.reset();
+        digest.input(tag);
+        DomainHasher {
+            digest,
+            tag: tag.to_vec()
+        }
+    }
+}
+
+impl <D: Digest> Digest for DomainHasher<D> {
+    fn input(&mut self, input: &[u8]) { self.digest.input(input) }
+    fn result(&mut self, out: &mut [u8]) { self.digest.result(out) }
+    fn result_truncated(&mut self, out: &mut [u8]) { self.digest.result_truncated(out) }
+    fn reset(&mut self) {
+        self.digest.reset();
+        self.digest.input(&self.tag);
+    }
+    fn output_bits(&self) -> usize { self.digest.output_bits() }
+    fn output_bytes(&self) -> usize { self.digest.output_bytes() }
+    fn block_size(&self) -> usize { self.digest.block_size() }
+    fn block_size_bytes(&self) -> usize { self.digest.block_size_bytes() }
+}
+
+#[cfg(test)]
+mod test {
+    use blake2b::{Blake2b, Blake2bp};
+    use blake2s::Blake2s;
+    use digest::{Digest, DomainHasher, concat_hash, double_hash};
+    use md5::Md5;
+    use ripemd160::Ripemd160;
+    use sha1::Sha1;
+    use sha2::{Sha224, Sha256, Sha384, Sha512};
+    use sha3::Sha3;
+    use streebog::Streebog256;
+    use tiger::Tiger;
+    use whirlpool::Whirlpool;
+    use serialize::hex::ToHex;
+
+    #[test]
+    fn test_concat_hash() {
+        let mut sh = Sha256::new();
+        sh.input_str("The quick brown fox jumps over the lazy dog");
+        let expected = sh.result_str();
+
+        let out = concat_hash(sh, &[b"The quick brown ", b"", b"fox jumps over the lazy dog"]);
+        assert_eq!(out.to_hex(), expected);
+        assert_eq!(concat_hash(Sha256::new(), &[]).to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+
+    #[test]
+    fn test_input_all() {
+        let a: &[u8] = b"GET / HTTP/1.1\r\n";
+        let b: &[u8] = b"";
+        let c: &[u8] = b"Host: example.com\r\n\r\n";
+
+        let mut sh = Sha256::new();
+        sh.input_all(vec![a, b, c]);
+        let all = sh.result_str();
+
+        let mut sh = Sha256::new();
+        sh.input(a);
+        sh.input(b);
+        sh.input(c);
+        assert_eq!(sh.result_str(), all);
+
+        let mut sh = Sha256::new();
+        sh.input(&[a, b, c].concat());
+        assert_eq!(sh.result_str(), all);
+
+        // Nothing to hash at all
+        let mut sh = Sha256::new();
+        sh.input_all(Vec::new());
+        assert_eq!(sh.result_str(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+
+    #[test]
+    fn test_double_hash() {
+        // SHA256d of the empty input
+        assert_eq!(double_hash(Sha256::new(), &[]).to_hex(),
+            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456");
+        assert_eq!(double_hash(Sha256::new(), b"hello").to_hex(),
+            "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50");
+    }
+
+    #[test]
+    fn test_domain_hasher() {
+        let m = b"The quick brown fox jumps over the lazy dog";
+        let expected = concat_hash(Sha256::new(), &[b"app-v1", m]).to_hex();
+
+        let mut dh = DomainHasher::new(Sha256::new(), b"app-v1");
+        assert_eq!(dh.output_bytes(), 32);
+        assert_eq!(dh.block_size(), 64);
+        dh.input(m);
+        assert_eq!(dh.result_str(), expected);
+
+        // The tag is input again after a reset
+        dh.reset();
+        dh.input(&m[..10]);
+        dh.input(&m[10..]);
+        assert_eq!(dh.result_str(), expected);
+
+        // With no user data the result is the hash of the tag alone
+        dh.reset();
+        assert_eq!(dh.result_str(), concat_hash(Sha256::new(), &[b"app-v1"]).to_hex());
+
+        // The digest passed in is reset before the tag is input
+        let mut sh = Sha256::new();
+        sh.input_str("stale");
+        let mut dh = DomainHasher::new(sh, b"app-v1");
+        dh.input(m);
+        assert_eq!(dh.result_str(), expected);
+
+        let mut other = DomainHasher::new(Sha256::new(), b"app-v2");
+        other.input(m);
+        assert!(other.result_str() != expected);
+    }
+
+    fn check_output_bytes<D: Digest>(digest: D, bytes: usize) {
+        assert_eq!(digest.output_bytes(), bytes);
+        assert_eq!(digest.output_bytes() * 8, digest.output_bits());
+    }
+
+    #[test]
+    fn test_output_bytes() {
+        check_output_bytes(Md5::new(), 16);
+        check_output_bytes(Sha1::new(), 20);
+        check_output_bytes(Sha224::new(), 28);
+        check_output_bytes(Sha256::new(), 32);
+        check_output_bytes(Sha384::new(), 48);
+        check_output_bytes(Sha512::new(), 64);
+        check_output_bytes(Sha3::sha3_256(), 32);
+        check_output_bytes(Blake2b::new(20), 20);
+        check_output_bytes(Blake2s::new(32), 32);
+        check_output_bytes(Ripemd160::new(), 20);
+        check_output_bytes(Streebog256::new(), 32);
+        check_output_bytes(Tiger::new(), 24);
+        check_output_bytes(Whirlpool::new(), 64);
+    }
+
+    fn check_block_size<D: Digest>(digest: D, bytes: usize) {
+        assert_eq!(digest.block_size(), bytes);
+        assert_eq!(digest.block_size_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_block_size() {
+        check_block_size(Md5::new(), 64);
+        check_block_size(Sha1::new(), 64);
+        check_block_size(Sha224::new(), 64);
+        check_block_size(Sha256::new(), 64);
+        check_block_size(Sha384::new(), 128);
+        check_block_size(Sha512::new(), 128);
+        check_block_size(Sha3::sha3_256(), 136);
+        check_block_size(Blake2b::new(20), 128);
+        check_block_size(Blake2bp::new(64), 128);
+        check_block_size(Blake2s::new(32), 64);
+        check_block_size(Ripemd160::new(), 64);
+        check_block_size(Streebog256::new(), 64);
+        check_block_size(Tiger::new(), 64);
+        check_block_size(Whirlpool::new(), 64);
+
+        let boxed: Box<dyn Digest> = Box::new(Blake2b::new(64));
+        check_block_size(boxed, 128);
+    }
+
+    #[test]
+    fn test_result_truncated() {
+        let full = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        for &len in [0, 1, 16, 20, 31, 32].iter() {
+            let mut sh = Sha256::new();
+            sh.input_str("abc");
+            let mut out = vec![0u8; len];
+            sh.result_truncated(&mut out);
+            assert_eq!(out.to_hex(), &full[..len * 2]);
+        }
+
+        // Only output_bytes() bytes are written to a larger buffer
+        let mut sh = Sha256::new();
+        sh.input_str("abc");
+        let mut out = [0xffu8; 40];
+        sh.result_truncated(&mut out);
+        assert_eq!(out[..32].to_hex(), full);
+        assert!(out[32..].iter().all(|&x| x == 0xff));
+    }
+
+    fn check_finish<D: Digest + Clone>(mut digest: D) {
+        digest.input_str("The quick brown fox jumps over the lazy dog");
+        let copy = digest.clone();
+        let mut expected = vec![0u8; digest.output_bytes()];
+        digest.result(&mut expected);
+        assert_eq!(copy.finish(), expected);
+    }
+
+    #[test]
+    fn test_finish() {
+        check_finish(Md5::new());
+        check_finish(Sha1::new());
+        check_finish(Sha256::new());
+        check_finish(Sha512::new());
+        check_finish(Sha3::sha3_256());
+        check_finish(Blake2b::new(20));
+        check_finish(Blake2s::new(32));
+        check_finish(Ripemd160::new());
+        check_finish(Streebog256::new());
+        check_finish(Tiger::new());
+        check_finish(Whirlpool::new());
+
+        let mut boxed: Box<dyn Digest> = Box::new(Sha256::new());
+        boxed.input_str("abc");
+        let mut sh = Sha256::new();
+        sh.input_str("abc");
+        assert_eq!(boxed.finish(), sh.finish());
+    }
+}
@@ -0,0 +1,292 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the EAX authenticated encryption mode, as described by Bellare, Rogaway
+ * and Wagner in "The EAX Mode of Operation", over a block cipher with a 128 bit block size such as
+ * AES.
+ *
+ * The nonce and the header (associated data) are each authenticated with OMAC (CMAC) under a
+ * distinct tweak, the message is encrypted in CTR mode starting from the MAC of the nonce, and the
+ * tag is the XOR of the MACs of the nonce, the header, and the ciphertext. The nonce may be of any
+ * length, but must never be reused with the same key.
+ */
+
+use aead::{AeadEncryptor, AeadDecryptor};
+use blockmodes::CtrMode;
+use cmac::Cmac;
+use mac::Mac;
+use symmetriccipher::{BlockEncryptor, SymmetricCipherError, SynchronousStreamCipher};
+use util::fixed_time_eq;
+
+const BLOCK_SIZE: usize = 16;
+
+// Shorter tags would be too easy to forge; an empty tag would match any ciphertext at all.
+const MIN_TAG_SIZE: usize = 4;
+
+// OMAC^t(data) = CMAC([t]_n || data), where [t]_n is the tweak t encoded as a full block.
+fn omac<C: BlockEncryptor>(cipher: C, tweak: u8, data: &[u8]) -> Vec<u8> {
+    let mut tweak_block = [0u8; BLOCK_SIZE];
+    tweak_block[BLOCK_SIZE - 1] = tweak;
+
+    let mut cmac = Cmac::new(cipher);
+    cmac.input(&tweak_block);
+    cmac.input(data);
+    let mut result = vec![0; BLOCK_SIZE];
+    cmac.raw_result(&mut result);
+    result
+}
+
+/**
+ * The Eax struct encrypts or decrypts a single message with the EAX mode. The tag passed to
+ * encrypt() or decrypt() may be shorter than the block size, in which case the full tag is
+ * truncated to its length, but it must be at least 4 bytes long. encrypt() panics on a tag of any
+ * other length, while decrypt() returns AuthenticationFailed.
+ */
+pub struct Eax<C: BlockEncryptor + Clone> {
+    cipher: C,
+    ctr: CtrMode<C>,
+    nonce_mac: Vec<u8>,
+    header_mac: Vec<u8>,
+    finished: bool
+}
+
+impl <C: BlockEncryptor + Clone> Eax<C> {
+    /**
+     * Create a new Eax instance.
+     *
+     * # Arguments
+     * * cipher - The BlockEncryptor to use. It should already be initialized with the key.
+     * * nonce - The nonce, which may be of any length.
+     * * header - The associated data, which is authenticated but not encrypted.
+     *
+     */
+    pub fn new(cipher: C, nonce: &[u8], header: &[u8]) -> Eax<C> {
+        assert!(cipher.block_size() == BLOCK_SIZE);
+        let nonce_mac = omac(cipher.clone(), 0, nonce);
+        let header_mac = omac(cipher.clone(), 1, header);
+        Eax {
+            cipher: cipher.clone(),
+            ctr: CtrMode::new(cipher, nonce_mac.clone()),
+            nonce_mac,
+            header_mac,
+            finished: false
+        }
+    }
+
+    // Compute the full tag for the given ciphertext.
+    fn tag(&self, ciphertext: &[u8]) -> Vec<u8> {
+        let mut tag = omac(self.cipher.clone(), 2, ciphertext);
+        for ((t, &n), &h) in tag.iter_mut().zip(self.nonce_mac.iter()).zip(self.header_mac.iter()) {
+            *t ^= n ^ h;
+        }
+        tag
+    }
+}
+
+impl <C: BlockEncryptor + Clone> AeadEncryptor for Eax<C> {
+    fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]) {
+        assert!(input.len() == output.len());
+        assert!(tag.len() >= MIN_TAG_SIZE && tag.len() <= BLOCK_SIZE);
+        assert!(!self.finished);
+        self.finished = true;
+
+        self.ctr.process(input, output);
+        let full_tag = self.tag(output);
+        tag.copy_from_slice(&full_tag[..tag.len()]);
+    }
+}
+
+impl <C: BlockEncryptor + Clone> AeadDecryptor for Eax<C> {
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8])
+            -> Result<(), SymmetricCipherError> {
+        assert!(input.len() == output.len());
+        assert!(!self.finished);
+        self.finished = true;
+
+        let full_tag = self.tag(input);
+        if tag.len() >= MIN_TAG_SIZE && tag.len() <= BLOCK_SIZE &&
+                fixed_time_eq(&full_tag[..tag.len()], tag) {
+            self.ctr.process(input, output);
+            Ok(())
+        } else {
+            Err(SymmetricCipherError::AuthenticationFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aead::{AeadEncryptor, AeadDecryptor};
+    use aessafe::AesSafe128Encryptor;
+    use eax::Eax;
+    use serialize::hex::FromHex;
+    use symmetriccipher::SymmetricCipherError;
+
+    struct Test {
+        msg: &'static str,
+        key: &'static str,
+        nonce: &'static str,
+        header: &'static str,
+        cipher: &'static str
+    }
+
+    // Test vectors from Appendix E of "The EAX Mode of Operation" by Bellare, Rogaway and Wagner.
+    // The cipher field is the ciphertext followed by the 16 byte tag.
+    const TESTS: [Test; 10] = [
+        Test {
+            msg: "",
+            key: "233952dee4d5ed5f9b9c6d6ff80ff478",
+            nonce: "62ec67f9c3a4a407fcb2a8c49031a8b3",
+            header: "6bfb914fd07eae6b",
+            cipher: "e037830e8389f27b025a2d6527e79d01"
+        },
+        Test {
+            msg: "f7fb",
+            key: "91945d3f4dcbee0bf45ef52255f095a4",
+            nonce: "becaf043b0a23d843194ba972c66debd",
+            header: "fa3bfd4806eb53fa",
+            cipher: "19dd5c4c9331049d0bdab0277408f67967e5"
+        },
+        Test {
+            msg: "1a47cb4933",
+            key: "01f74ad64077f2e704c0f60ada3dd523",
+            nonce: "70c3db4f0d26368400a10ed05d2bff5e",
+            header: "234a3463c1264ac6",
+            cipher: "d851d5bae03a59f238a23e39199dc9266626c40f80"
+        },
+        Test {
+            msg: "481c9e39b1",
+            key: "d07cf6cbb7f313bdde66b727afd3c5e8",
+            nonce: "8408dfff3c1a2b1292dc199e46b7d617",
+            header: "33cce2eabff5a79d",
+            cipher: "632a9d131ad4c168a4225d8e1ff755939974a7bede"
+        },
+        Test {
+            msg: "40d0c07da5e4",
+            key: "35b6d0580005bbc12b0587124557d2c2",
+            nonce: "fdb6b06676eedc5c61d74276e1f8e816",
+            header: "aeb96eaebe2970e9",
+            cipher: "071dfe16c675cb0677e536f73afe6a14b74ee49844dd"
+        },
+        Test {
+            msg: "4de3b35c3fc039245bd1fb7d",
+            key: "bd8e6e11475e60b268784c38c62feb22",
+            nonce: "6eac5c93072d8e8513f750935e46da1b",
+            header: "d4482d1ca78dce0f",
+            cipher: "835bb4f15d743e350e728414abb8644fd6ccb86947c5e10590210a4f"
+        },
+        Test {
+            msg: "8b0a79306c9ce7ed99dae4f87f8dd61636",
+            key: "7c77d6e813bed5ac98baa417477a2e7d",
+            nonce: "1a8c98dcd73d38393b2bf1569deefc19",
+            header: "65d2017990d62528",
+            cipher: "02083e3979da014812f59f11d52630da30137327d10649b0aa6e1c181db617d7f2"
+        },
+        Test {
+            msg: "1bda122bce8a8dbaf1877d962b8592dd2d56",
+            key: "5fff20cafab119ca2fc73549e20f5b0d",
+            nonce: "dde59b97d722156d4d9aff2bc7559826",
+            header: "54b9f04e6a09189a",
+            cipher: "2ec47b2c4954a489afc7ba4897edcdae8cc33b60450599bd02c96382902aef7f832a"
+        },
+        Test {
+            msg: "6cf36720872b8513f6eab1a8a44438d5ef11",
+            key: "a4a4782bcffd3ec5e7ef6d8c34a56123",
+            nonce: "b781fcf2f75fa5a8de97a9ca48e522ec",
+            header: "899a175897561d7e",
+            cipher: "0de18fd0fdd91e7af19f1d8ee8733938b1e8e7f6d2231618102fdb7fe55ff1991700"
+        },
+        Test {
+            msg: "ca40d7446e545ffaed3bd12a740a659ffbbb3ceab7",
+            key: "8395fcf1e95bebd697bd010bc766aac3",
+            nonce: "22e7add93cfc6393c57ec0b3c17d6b44",
+            header: "126735fcc320d25a",
+            cipher: "cb8920f87a6c75cff39627b56e3ed197c552d295a7cfc46afc253b4652b1af3795b124ab6e"
+        }
+    ];
+
+    fn new_eax(test: &Test) -> Eax<AesSafe128Encryptor> {
+        let key = test.key.from_hex().unwrap();
+        Eax::new(AesSafe128Encryptor::new(&key), &test.nonce.from_hex().unwrap(),
+            &test.header.from_hex().unwrap())
+    }
+
+    #[test]
+    fn test_eax_encrypt() {
+        for test in TESTS.iter() {
+            let msg = test.msg.from_hex().unwrap();
+            let expected = test.cipher.from_hex().unwrap();
+            let mut output = vec![0; msg.len()];
+            let mut tag = [0u8; 16];
+            new_eax(test).encrypt(&msg, &mut output, &mut tag);
+            assert!(output[..] == expected[..msg.len()]);
+            assert!(tag[..] == expected[msg.len()..]);
+        }
+    }
+
+    #[test]
+    fn test_eax_decrypt() {
+        for test in TESTS.iter() {
+            let msg = test.msg.from_hex().unwrap();
+            let cipher = test.cipher.from_hex().unwrap();
+            let (ciphertext, tag) = cipher.split_at(msg.len());
+            let mut output = vec![0; msg.len()];
+            assert!(new_eax(test).decrypt(ciphertext, &mut output, tag).is_ok());
+            assert!(output == msg);
+
+            // A truncated tag is accepted if it matches the start of the full tag
+            assert!(new_eax(test).decrypt(ciphertext, &mut output, &tag[..8]).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_eax_decrypt_fail() {
+        for test in TESTS.iter() {
+            let msg = test.msg.from_hex().unwrap();
+            let mut cipher = test.cipher.from_hex().unwrap();
+            let last = cipher.len() - 1;
+            cipher[last] ^= 1;
+            let (ciphertext, tag) = cipher.split_at(msg.len());
+            let mut output = vec![0; msg.len()];
+            match new_eax(test).decrypt(ciphertext, &mut output, tag) {
+                Err(SymmetricCipherError::AuthenticationFailed) => {}
+                _ => panic!("Expected AuthenticationFailed")
+            }
+            // Nothing is decrypted if the tag doesn't match
+            assert!(output.iter().all(|&x| x == 0));
+        }
+    }
+
+    #[test]
+    fn test_eax_decrypt_bad_tag_len() {
+        // Comparing an empty tag would succeed for any ciphertext, so it must be rejected, as must
+        // a tag longer than a block
+        let test = &TESTS[1];
+        let msg = test.msg.from_hex().unwrap();
+        let cipher = test.cipher.from_hex().unwrap();
+        let mut long_tag = cipher[msg.len()..].to_vec();
+        long_tag.push(0);
+        for tag in [&[][..], &cipher[msg.len()..msg.len() + 3], &long_tag[..]].iter() {
+            let mut output = vec![0; msg.len()];
+            match new_eax(test).decrypt(&cipher[..msg.len()], &mut output, tag) {
+                Err(SymmetricCipherError::AuthenticationFailed) => {}
+                _ => panic!("Expected AuthenticationFailed")
+            }
+            assert!(output.iter().all(|&x| x == 0));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_eax_encrypt_short_tag() {
+        let test = &TESTS[1];
+        let msg = test.msg.from_hex().unwrap();
+        let mut output = vec![0; msg.len()];
+        let mut tag = [0u8; 3];
+        new_eax(test).encrypt(&msg, &mut output, &mut tag);
+    }
+}
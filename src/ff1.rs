@@ -0,0 +1,348 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the FF1 format-preserving encryption mode as specified by NIST SP 800-38G,
+ * over a block cipher with a 128 bit block size such as AES.
+ *
+ * FF1 encrypts a string of numerals in a given radix to another string of the same length in the
+ * same radix, so that a 16 digit card number encrypts to a 16 digit number. The plaintext is split
+ * into two halves which go through a 10 round Feistel network, with a round function built from
+ * CBC-MAC of the tweak, the round number, and one half interpreted as an integer.
+ *
+ * Numerals are passed as integers in [0, radix), not as characters; mapping an alphabet to
+ * numerals is left to the caller. The domain size radix^n must be at least one million, so short
+ * strings over small alphabets are rejected. Even at that size the domain is small enough that
+ * FF1 gives far weaker guarantees than an ordinary cipher: a tweak, such as the unencrypted
+ * digits of a card number, should be used whenever one is available.
+ */
+
+use cbc_mac::CbcMac;
+use mac::Mac;
+use symmetriccipher::BlockEncryptor;
+
+const BLOCK_SIZE: usize = 16;
+const ROUNDS: u8 = 10;
+const MIN_DOMAIN_SIZE: u64 = 1000000;
+const MAX_RADIX: u32 = 1 << 16;
+
+// NUM_radix(x), written as a big endian integer of exactly len bytes. The caller chooses len large
+// enough to hold any numeral string of x's length.
+fn num_radix(x: &[u16], radix: u32, len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    for &numeral in x {
+        let mut carry = numeral as u32;
+        for b in out.iter_mut().rev() {
+            let v = (*b as u32) * radix + carry;
+            *b = v as u8;
+            carry = v >> 8;
+        }
+    }
+    out
+}
+
+// STR_radix^m(y mod radix^m) for the big endian integer y: its m least significant numerals, most
+// significant first.
+fn str_radix(y: &[u8], radix: u32, m: usize) -> Vec<u16> {
+    let mut y = y.to_vec();
+    let mut out = vec![0u16; m];
+    for numeral in out.iter_mut().rev() {
+        let mut rem = 0u32;
+        for b in y.iter_mut() {
+            let v = (rem << 8) | *b as u32;
+            *b = (v / radix) as u8;
+            rem = v % radix;
+        }
+        *numeral = rem as u16;
+    }
+    out
+}
+
+// (NUM_radix(x) + NUM_radix(y)) mod radix^m, where both strings are m numerals long
+fn add_mod(x: &[u16], y: &[u16], radix: u32) -> Vec<u16> {
+    let mut out = vec![0u16; x.len()];
+    let mut carry = 0;
+    for ((o, &a), &b) in out.iter_mut().zip(x.iter()).zip(y.iter()).rev() {
+        let v = a as u32 + b as u32 + carry;
+        carry = if v >= radix { 1 } else { 0 };
+        *o = (v - carry * radix) as u16;
+    }
+    out
+}
+
+// (NUM_radix(x) - NUM_radix(y)) mod radix^m, where both strings are m numerals long
+fn sub_mod(x: &[u16], y: &[u16], radix: u32) -> Vec<u16> {
+    let mut out = vec![0u16; x.len()];
+    let mut borrow = 0;
+    for ((o, &a), &b) in out.iter_mut().zip(x.iter()).zip(y.iter()).rev() {
+        let v = a as u32 + radix - b as u32 - borrow;
+        borrow = if v < radix { 1 } else { 0 };
+        *o = (v + borrow * radix - radix) as u16;
+    }
+    out
+}
+
+/**
+ * The Ff1 struct encrypts and decrypts numeral strings in a fixed radix with the FF1 mode.
+ */
+pub struct Ff1<C: BlockEncryptor + Clone> {
+    cipher: C,
+    radix: u32,
+    min_len: usize
+}
+
+impl <C: BlockEncryptor + Clone> Ff1<C> {
+    /**
+     * Create a new Ff1 instance.
+     *
+     * # Arguments
+     * * cipher - The BlockEncryptor to use. It should already be initialized with the key.
+     * * radix - The number of distinct numerals, from 2 to 65536.
+     *
+     */
+    pub fn new(cipher: C, radix: u32) -> Ff1<C> {
+        assert!(cipher.block_size() == BLOCK_SIZE);
+        assert!((2..=MAX_RADIX).contains(&radix));
+
+        // The shortest length with radix^min_len >= 1000000, and never less than 2
+        let mut min_len = 1;
+        let mut domain = radix as u64;
+        while min_len < 2 || domain < MIN_DOMAIN_SIZE {
+            min_len += 1;
+            domain *= radix as u64;
+        }
+
+        Ff1 {
+            cipher,
+            radix,
+            min_len
+        }
+    }
+
+    /**
+     * Encrypt a numeral string. The result has the same length as the input.
+     *
+     * # Arguments
+     * * tweak - The tweak, which may be of any length, including empty.
+     * * input - The numerals to encrypt, each less than the radix.
+     *
+     */
+    pub fn encrypt(&self, tweak: &[u8], input: &[u16]) -> Result<Vec<u16>, &'static str> {
+        self.check(tweak, input)?;
+        let (u, v) = (input.len() / 2, input.len() - input.len() / 2);
+        let p = self.p_block(tweak, input.len());
+        let b = self.byte_len(v);
+
+        let mut a = input[..u].to_vec();
+        let mut b_half = input[u..].to_vec();
+        for i in 0..ROUNDS {
+            let m = if i % 2 == 0 { u } else { v };
+            let y = self.round_value(&p, tweak, i, &b_half, b);
+            let c = add_mod(&a, &str_radix(&y, self.radix, m), self.radix);
+            a = b_half;
+            b_half = c;
+        }
+
+        a.extend_from_slice(&b_half);
+        Ok(a)
+    }
+
+    /**
+     * Decrypt a numeral string produced by encrypt() with the same key, radix, and tweak.
+     *
+     * # Arguments
+     * * tweak - The tweak that was used to encrypt.
+     * * input - The numerals to decrypt, each less than the radix.
+     *
+     */
+    pub fn decrypt(&self, tweak: &[u8], input: &[u16]) -> Result<Vec<u16>, &'static str> {
+        self.check(tweak, input)?;
+        let (u, v) = (input.len() / 2, input.len() - input.len() / 2);
+        let p = self.p_block(tweak, input.len());
+        let b = self.byte_len(v);
+
+        let mut a = input[..u].to_vec();
+        let mut b_half = input[u..].to_vec();
+        for i in (0..ROUNDS).rev() {
+            let m = if i % 2 == 0 { u } else { v };
+            let y = self.round_value(&p, tweak, i, &a, b);
+            let c = sub_mod(&b_half, &str_radix(&y, self.radix, m), self.radix);
+            b_half = a;
+            a = c;
+        }
+
+        a.extend_from_slice(&b_half);
+        Ok(a)
+    }
+
+    fn check(&self, tweak: &[u8], input: &[u16]) -> Result<(), &'static str> {
+        if input.len() < self.min_len {
+            return Err("FF1 input is too short for the radix");
+        }
+        if input.len() as u64 > u32::MAX as u64 || tweak.len() as u64 > u32::MAX as u64 {
+            return Err("FF1 input or tweak is too long");
+        }
+        if input.iter().any(|&x| x as u32 >= self.radix) {
+            return Err("FF1 input contains a numeral outside of the radix");
+        }
+        Ok(())
+    }
+
+    // b = ceil(ceil(v * log2(radix)) / 8), computed exactly as the byte length of radix^v - 1
+    fn byte_len(&self, v: usize) -> usize {
+        let max = vec![(self.radix - 1) as u16; v];
+        let bytes = num_radix(&max, self.radix, 2 * v + 1);
+        bytes.len() - bytes.iter().take_while(|&&x| x == 0).count()
+    }
+
+    // The fixed first block P, which encodes the parameters of the encryption
+    fn p_block(&self, tweak: &[u8], n: usize) -> [u8; BLOCK_SIZE] {
+        let u = n / 2;
+        let mut p = [0u8; BLOCK_SIZE];
+        p[..3].copy_from_slice(&[1, 2, 1]);
+        p[3] = (self.radix >> 16) as u8;
+        p[4] = (self.radix >> 8) as u8;
+        p[5] = self.radix as u8;
+        p[6] = ROUNDS;
+        p[7] = u as u8;
+        p[8..12].copy_from_slice(&(n as u32).to_be_bytes());
+        p[12..16].copy_from_slice(&(tweak.len() as u32).to_be_bytes());
+        p
+    }
+
+    // The first d bytes of the keystream for round i, S = R || CIPH(R ^ [1]) || CIPH(R ^ [2]) ...,
+    // where R = PRF(P || Q)
+    fn round_value(&self, p: &[u8], tweak: &[u8], i: u8, half: &[u16], b: usize) -> Vec<u8> {
+        let d = 4 * b.div_ceil(4) + 4;
+
+        let mut q = tweak.to_vec();
+        let pad = (BLOCK_SIZE - (tweak.len() + b + 1) % BLOCK_SIZE) % BLOCK_SIZE;
+        q.extend(std::iter::repeat_n(0, pad));
+        q.push(i);
+        q.extend_from_slice(&num_radix(half, self.radix, b));
+
+        let mut mac = CbcMac::new(self.cipher.clone());
+        mac.input(p);
+        mac.input(&q);
+        let mut r = [0u8; BLOCK_SIZE];
+        mac.raw_result(&mut r);
+
+        let mut s = r.to_vec();
+        let mut j = 1u64;
+        while s.len() < d {
+            let mut block = r;
+            for (x, &c) in block[BLOCK_SIZE - 8..].iter_mut().zip(j.to_be_bytes().iter()) {
+                *x ^= c;
+            }
+            let mut output = [0u8; BLOCK_SIZE];
+            self.cipher.encrypt_block(&block, &mut output);
+            s.extend_from_slice(&output);
+            j += 1;
+        }
+        s.truncate(d);
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aessafe::{AesSafe128Encryptor, AesSafe256Encryptor};
+    use ff1::Ff1;
+    use serialize::hex::FromHex;
+
+    const KEY_128: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const KEY_256: &str = "2b7e151628aed2a6abf7158809cf4f3cef4359d8d580aa4f7f036d6f04fc6a94";
+
+    fn numerals(s: &str) -> Vec<u16> {
+        s.chars().map(|c| c.to_digit(36).unwrap() as u16).collect()
+    }
+
+    // Samples 1 to 3 and 7 to 9 of the NIST FF1 examples
+    #[test]
+    fn test_ff1_nist_samples() {
+        let key = KEY_128.from_hex().unwrap();
+        let tests = [
+            (10, "", "0123456789", "2433477484"),
+            (10, "39383736353433323130", "0123456789", "6124200773"),
+            (36, "3737373770717273373737", "0123456789abcdefghi", "a9tv40mll9kdu509eum"),
+        ];
+        for &(radix, tweak, pt, ct) in tests.iter() {
+            let ff1 = Ff1::new(AesSafe128Encryptor::new(&key), radix);
+            let tweak = tweak.from_hex().unwrap();
+            assert_eq!(ff1.encrypt(&tweak, &numerals(pt)).unwrap(), numerals(ct));
+            assert_eq!(ff1.decrypt(&tweak, &numerals(ct)).unwrap(), numerals(pt));
+        }
+
+        let key = KEY_256.from_hex().unwrap();
+        let tests = [
+            (10, "", "0123456789", "6657667009"),
+            (10, "39383736353433323130", "0123456789", "1001623463"),
+            (36, "3737373770717273373737", "0123456789abcdefghi", "xs8a0azh2avyalyzuwd"),
+        ];
+        for &(radix, tweak, pt, ct) in tests.iter() {
+            let ff1 = Ff1::new(AesSafe256Encryptor::new(&key), radix);
+            let tweak = tweak.from_hex().unwrap();
+            assert_eq!(ff1.encrypt(&tweak, &numerals(pt)).unwrap(), numerals(ct));
+            assert_eq!(ff1.decrypt(&tweak, &numerals(ct)).unwrap(), numerals(pt));
+        }
+    }
+
+    #[test]
+    fn test_ff1_round_trip() {
+        // Lengths and radixes which need more than one block of keystream per round, an odd split
+        // between the halves, and a tweak which fills the Q block exactly
+        let key = KEY_128.from_hex().unwrap();
+        for &radix in [2u32, 10, 26, 256, 65536].iter() {
+            let ff1 = Ff1::new(AesSafe128Encryptor::new(&key), radix);
+            for &len in [20usize, 21, 75, 200].iter() {
+                let input: Vec<u16> =
+                    (0..len).map(|i| ((i * 7919) as u32 % radix) as u16).collect();
+                for tweak in [&b""[..], &[0xa5; 13][..], &[1; 40][..]].iter() {
+                    let ct = ff1.encrypt(tweak, &input).unwrap();
+                    assert_eq!(ct.len(), len);
+                    assert!(ct.iter().all(|&x| (x as u32) < radix));
+                    assert!(ct != input);
+                    assert_eq!(ff1.decrypt(tweak, &ct).unwrap(), input);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ff1_tweak_changes_output() {
+        let key = KEY_128.from_hex().unwrap();
+        let ff1 = Ff1::new(AesSafe128Encryptor::new(&key), 10);
+        let input = numerals("4111111111111111");
+        let ct = ff1.encrypt(b"1111", &input).unwrap();
+        assert!(ct != ff1.encrypt(b"1112", &input).unwrap());
+        assert!(ff1.decrypt(b"1112", &ct).unwrap() != input);
+    }
+
+    #[test]
+    fn test_ff1_invalid_input() {
+        let key = KEY_128.from_hex().unwrap();
+
+        // 10^6 is the smallest allowed domain, so six decimal digits are the minimum
+        let ff1 = Ff1::new(AesSafe128Encryptor::new(&key), 10);
+        assert!(ff1.encrypt(b"", &numerals("12345")).is_err());
+        assert!(ff1.decrypt(b"", &numerals("12345")).is_err());
+        assert!(ff1.encrypt(b"", &numerals("123456")).is_ok());
+        assert!(ff1.encrypt(b"", &numerals("12345a")).is_err());
+        assert!(ff1.decrypt(b"", &numerals("12345a")).is_err());
+
+        // Even a large radix needs at least two numerals
+        let ff1 = Ff1::new(AesSafe128Encryptor::new(&key), 65536);
+        assert!(ff1.encrypt(b"", &[1]).is_err());
+        assert!(ff1.encrypt(b"", &[1, 65535]).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_ff1_radix_too_large() {
+        let key = KEY_128.from_hex().unwrap();
+        Ff1::new(AesSafe128Encryptor::new(&key), 65537);
+    }
+}
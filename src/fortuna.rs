@@ -180,7 +180,9 @@ pub struct Fortuna {
     pool: [Pool; NUM_POOLS],
     generator: FortunaGenerator,
     reseed_count: u32,
-    last_reseed_time: f64
+    last_reseed_time: f64,
+    // The source of the current time in seconds, which tests replace to control the 100ms limit
+    clock: fn() -> f64
 }
 
 impl Fortuna {
@@ -190,13 +192,14 @@ impl Fortuna {
             pool: [Pool::new(); NUM_POOLS],
             generator: FortunaGenerator::new(),
             reseed_count: 0,
-            last_reseed_time: 0.0
+            last_reseed_time: 0.0,
+            clock: precise_time_s
         }
     }
 
     /// Adds a random event `e` from source `s` to entropy pool `i` (PC 9.5.6)
     pub fn add_random_event(&mut self, s: u8, i: usize, e: &[u8]) {
-        assert!(i <= NUM_POOLS);
+        assert!(i < NUM_POOLS);
         // These restrictions (and `s` in [0, 255]) are part of the Fortuna spec.
         assert!(e.len() > 0);
         assert!(e.len() <= 32);
@@ -204,6 +207,34 @@ impl Fortuna {
         (&mut self.pool[i]).input(&[e.len() as u8]);
         (&mut self.pool[i]).input(e);
     }
+
+    /// Reseeds the generator from the accumulator pools, but only if the
+    /// first pool holds at least `MIN_POOL_SIZE` bytes of events and at
+    /// least 100ms have passed since the last reseed (PC 9.5.5). Returns
+    /// whether a reseed happened.
+    ///
+    /// This is called automatically whenever random data is requested,
+    /// so it only needs to be called directly by users who want new
+    /// entropy to take effect before then.
+    pub fn reseed_if_ready(&mut self) -> bool {
+        let now = (self.clock)();
+        if self.pool[0].count < MIN_POOL_SIZE || now - self.last_reseed_time <= 0.1 {
+            return false;
+        }
+        self.reseed_count += 1;
+        self.last_reseed_time = now;
+        // Compute key as Sha256d( key || s )
+        let mut hash = [0; (32 * NUM_POOLS)];
+        let mut n_pools = 0;
+        while self.reseed_count % (1 << n_pools) == 0 {
+            (&mut self.pool[n_pools]).result(&mut hash[n_pools * 32..(n_pools + 1) * 32]);
+            n_pools += 1;
+            assert!(n_pools < NUM_POOLS);
+            assert!(n_pools < 32); // width of counter
+        }
+        self.generator.reseed(&hash[..n_pools * 32]);
+        true
+    }
 }
 
 impl Rng for Fortuna {
@@ -216,22 +247,7 @@ impl Rng for Fortuna {
     /// pool, this function will fail the task.
     fn fill_bytes(&mut self, dest: &mut [u8]) {
         // Reseed if necessary
-        let now = precise_time_s();
-        if self.pool[0].count >= MIN_POOL_SIZE &&
-           now - self.last_reseed_time > 0.1 {
-            self.reseed_count += 1;
-            self.last_reseed_time = now;
-            // Compute key as Sha256d( key || s )
-            let mut hash = [0; (32 * NUM_POOLS)];
-            let mut n_pools = 0;
-            while self.reseed_count % (1 << n_pools) == 0 {
-                (&mut self.pool[n_pools]).result(&mut hash[n_pools * 32..(n_pools + 1) * 32]);
-                n_pools += 1;
-                assert!(n_pools < NUM_POOLS);
-                assert!(n_pools < 32); // width of counter
-            }
-            self.generator.reseed(&hash[..n_pools * 32]);
-        }
+        self.reseed_if_ready();
         // Fail on unseeded RNG
         if self.reseed_count == 0 {
             panic!("rust-crypto: an unseeded Fortuna was asked for random bytes!");
@@ -259,7 +275,7 @@ impl<'a> SeedableRng<&'a [u8]> for Fortuna {
 
     fn reseed(&mut self, seed: &'a [u8]) {
         self.reseed_count += 1;
-        self.last_reseed_time = precise_time_s();
+        self.last_reseed_time = (self.clock)();
         self.generator.reseed(seed);
     }
 }
@@ -271,10 +287,31 @@ fn test_force_reseed(f: &mut Fortuna) {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use rand::{SeedableRng, Rng};
 
     use super::{Fortuna, Pool, NUM_POOLS, test_force_reseed};
 
+    thread_local!(static FAKE_TIME: Cell<f64> = Cell::new(1000.0));
+
+    fn fake_time() -> f64 {
+        FAKE_TIME.with(|t| t.get())
+    }
+
+    fn advance_fake_time(seconds: f64) {
+        FAKE_TIME.with(|t| t.set(t.get() + seconds));
+    }
+
+    // Create a Fortuna seeded with seed whose notion of the current time only changes when the
+    // test calls advance_fake_time
+    fn seeded_with_fake_clock(seed: &[u8]) -> Fortuna {
+        let mut f = Fortuna::new_unseeded();
+        f.clock = fake_time;
+        f.reseed(seed);
+        f
+    }
+
     #[test]
     fn test_create_unseeded() {
         let _: Fortuna = Fortuna::new_unseeded();
@@ -342,6 +379,39 @@ mod tests {
         assert!(x != z);
     }
 
+    #[test]
+    fn test_reseed_if_ready() {
+        let seed = [0, 1, 2, 3, 4, 5];
+        let mut f1 = seeded_with_fake_clock(&seed);
+        let mut f2 = seeded_with_fake_clock(&seed);
+
+        // Each event adds 4 bytes to the pool, so the 16th event fills it. Enough time passes
+        // between the events that only the pool size prevents a reseed.
+        for i in 0..16 {
+            advance_fake_time(0.2);
+            assert!(!f2.reseed_if_ready());
+            f2.add_random_event(0, 0, &[i, i]);
+        }
+        assert!(f2.reseed_if_ready());
+        assert!(!f2.reseed_if_ready());
+
+        // f3 was seeded too recently to reseed, so its events have not been used yet
+        let mut f3 = seeded_with_fake_clock(&seed);
+        for i in 0..16 {
+            f3.add_random_event(0, 0, &[i, i]);
+        }
+        assert!(!f3.reseed_if_ready());
+        advance_fake_time(0.05);
+        assert!(!f3.reseed_if_ready());
+
+        let x = f1.next_u32();
+        assert!(f2.next_u32() != x);
+        assert_eq!(f3.next_u32(), x);
+
+        advance_fake_time(0.2);
+        assert!(f3.reseed_if_ready());
+    }
+
     #[test]
     fn test_generator_correctness() {
         let mut output = [0; 100];
@@ -10,6 +10,10 @@
 ///
 /// In order to ensure constant time computation it uses the approach described in [2] section 5.2.
 ///
+/// A table driven implementation using Shoup's 4-bit method is also available through
+/// `Ghash::new_with_table()`. It is faster, but its memory accesses depend on the data being
+/// hashed, so it should only be used where cache timing attacks are not a concern.
+///
 /// [1] - "The Galois/Counter Mode of Operation (GCM)" - David A. McGrew and John Viega
 ///       <http://csrc.nist.gov/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf>
 /// [2] - "Faster and Timing-Attack Resistant AES-GCM" - Emilia Käsper and Peter Schwabe
@@ -33,6 +37,16 @@ impl Gf128 {
         Gf128 { d: simd::u32x4(a, b, c, d) }
     }
 
+    // The same bit representation as a u128, with x^0 as the msb
+    fn from_u128(x: u128) -> Gf128 {
+        Gf128::new(x as u32, (x >> 32) as u32, (x >> 64) as u32, (x >> 96) as u32)
+    }
+
+    fn to_u128(self) -> u128 {
+        let simd::u32x4(a, b, c, d) = self.d;
+        (d as u128) << 96 | (c as u128) << 64 | (b as u128) << 32 | a as u128
+    }
+
     fn from_bytes(bytes: &[u8]) -> Gf128 {
         assert!(bytes.len() == 16);
         let d = read_u32_be(&bytes[0..4]);
@@ -69,9 +83,17 @@ impl Gf128 {
         self.cond_xor(r, self.times_x())
     }
 
-    // Adds y, and multiplies with h using a precomputed array of the values h * x^0 to h * x^127
-    fn add_and_mul(&mut self, y: Gf128, hs: &[Gf128; 128]) {
+    // Adds y, and multiplies with h
+    fn add_and_mul(&mut self, y: Gf128, h: &HKey) {
         *self = *self ^ y;
+        match *h {
+            HKey::Powers(ref hs) => self.mul_powers(hs),
+            HKey::Table(ref table) => *self = Gf128::from_u128(mul_table(self.to_u128(), table))
+        }
+    }
+
+    // Multiplies with h using a precomputed array of the values h * x^0 to h * x^127
+    fn mul_powers(&mut self, hs: &[Gf128; 128]) {
         let mut x = mem::replace(self, Gf128::new(0, 0, 0, 0));
 
         for &y in hs.iter().rev() {
@@ -98,32 +120,86 @@ impl BitXor for Gf128 {
     }
 }
 
+// The reduction modulo x^128 + x^7 + x^2 + x + 1 of the 4 lsbs of a u128 after they are shifted out
+// by a multiplication by x^4
+const fn make_reduce_4bit() -> [u128; 16] {
+    let mut table = [0u128; 16];
+    let mut i = 0;
+    while i < 16 {
+        let mut k = 0;
+        while k < 4 {
+            if i & (1 << k) != 0 {
+                table[i] ^= (0xe1 << 120) >> (3 - k);
+            }
+            k += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+static REDUCE_4BIT: [u128; 16] = make_reduce_4bit();
+
+// Computes the table of h * i for every polynomial i of degree less than 4, where the msb of i is the
+// coefficient of x^0
+fn make_table(h: u128) -> [u128; 16] {
+    let mut table = [0u128; 16];
+    let mut hx = h;
+    for bit in [8, 4, 2, 1].iter() {
+        table[*bit] = hx;
+        hx = (hx >> 1) ^ ((0xe1 << 120) & (hx & 1).wrapping_neg());
+    }
+    for i in 1..16usize {
+        let low = i & i.wrapping_neg();
+        table[i] = table[low] ^ table[i ^ low];
+    }
+    table
+}
+
+// Multiplies x with h using Shoup's 4-bit method, processing x one nibble at a time starting from
+// the highest degree coefficients
+fn mul_table(x: u128, table: &[u128; 16]) -> u128 {
+    let mut z = 0u128;
+    for i in 0..32 {
+        let nibble = ((x >> (4 * i)) & 0xf) as usize;
+        let rem = (z & 0xf) as usize;
+        z = (z >> 4) ^ REDUCE_4BIT[rem] ^ table[nibble];
+    }
+    z
+}
+
+// The precomputed values for the key h. Both are boxed, so that a Ghash stays small whichever one
+// it uses.
+#[derive(Clone)]
+enum HKey {
+    // h * x^0 to h * x^127, for the constant time multiplication
+    Powers(Box<[Gf128; 128]>),
+    // Shoup's 4-bit table
+    Table(Box<[u128; 16]>)
+}
+
 /// A structure representing the state of a GHASH computation
-#[derive(Copy)]
+#[derive(Clone)]
 pub struct Ghash {
-    hs: [Gf128; 128],
+    hs: HKey,
     state: Gf128,
     a_len: usize,
     rest: Option<[u8; 16]>,
     finished: bool
 }
 
-impl Clone for Ghash { fn clone(&self) -> Ghash { *self } }
-
 /// A structure representing the state of a GHASH computation, after input for C was provided
-#[derive(Copy)]
+#[derive(Clone)]
 pub struct GhashWithC {
-    hs: [Gf128; 128],
+    hs: HKey,
     state: Gf128,
     a_len: usize,
     c_len: usize,
     rest: Option<[u8; 16]>
 }
 
-impl Clone for GhashWithC { fn clone(&self) -> GhashWithC { *self } }
-
 fn update(state: &mut Gf128, len: &mut usize, data: &[u8], srest: &mut Option<[u8; 16]>,
-          hs: &[Gf128; 128]) {
+          hs: &HKey) {
     let rest_len = *len % 16;
     let data_len = data.len();
     *len += data_len;
@@ -144,6 +220,8 @@ fn update(state: &mut Gf128, len: &mut usize, data: &[u8], srest: &mut Option<[u
         }
     };
 
+    // What is left may be shorter than the input, if it was used to complete the buffered block
+    let data_len = data.len();
     let (data, rest) = data.split_at(data_len - data_len % 16);
 
     for chunk in data.chunks(16) {
@@ -172,8 +250,21 @@ impl Ghash {
             h = h.times_x_reduce();
         }
 
+        Ghash::with_key(HKey::Powers(Box::new(table)))
+    }
+
+    /// Creates a new GHASH state, with `h` as the key, that uses a precomputed table with Shoup's
+    /// 4-bit method. This is faster than `new()`, but it is NOT constant time: the table lookups
+    /// depend on the data being hashed and on the key.
+    pub fn new_with_table(h: &[u8]) -> Ghash {
+        assert!(h.len() == 16);
+        let h = Gf128::from_bytes(h).to_u128();
+        Ghash::with_key(HKey::Table(Box::new(make_table(h))))
+    }
+
+    fn with_key(hs: HKey) -> Ghash {
         Ghash {
-            hs: table,
+            hs,
             state: Gf128::new(0, 0, 0, 0),
             a_len: 0,
             rest: None,
@@ -295,7 +386,11 @@ impl Mac for Ghash {
 
 #[cfg(test)]
 mod test {
+    use rand::{IsaacRng, Rng};
+
+    use cryptoutil::gf128_mul;
     use ghash::Ghash;
+    use mac::{Mac, MacResult};
 
     // Test cases from:
     // <http://csrc.nist.gov/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf>
@@ -528,6 +623,98 @@ mod test {
                             .result()[..], g);
         }
     }
+
+    #[test]
+    fn partial_blocks() {
+        // Every way of feeding A and C in two pieces, so that the pieces straddle block boundaries
+        // in every possible position, must match feeding them all at once.
+        for &(h, a, c, g) in CASES.iter() {
+            for i in 0..a.len() + 1 {
+                for j in 0..c.len() + 1 {
+                    let (a1, a2) = a.split_at(i);
+                    let (c1, c2) = c.split_at(j);
+                    assert_eq!(&Ghash::new(h).input_a(a1)
+                                             .input_a(a2)
+                                             .input_c(c1)
+                                             .input_c(c2)
+                                             .result()[..], g);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn partial_aad_mac() {
+        // The 20 byte AAD of test case 4, fed through the Mac interface in one call and as 12 + 8
+        // bytes. The final 4 bytes are zero padded when the result is computed.
+        let (h, a, _, _) = CASES[3];
+        let mut one = Ghash::new(h);
+        one.input(a);
+        let mut two = Ghash::new(h);
+        two.input(&a[..12]);
+        two.input(&a[12..]);
+        let expected = Ghash::new(h).input_a(a).result();
+        assert!(Mac::result(&mut one) == MacResult::new(&expected));
+        assert!(Mac::result(&mut two) == MacResult::new(&expected));
+    }
+
+    #[test]
+    fn hash_with_table() {
+        for &(h, a, c, g) in CASES.iter() {
+            let ghash = Ghash::new_with_table(h);
+            assert_eq!(&ghash.input_a(a).input_c(c).result()[..], g);
+        }
+    }
+
+    // GHASH computed directly from its definition with the generic GF(2^128) multiplication in
+    // cryptoutil, to check both of the optimized implementations against.
+    fn reference_ghash(h: &[u8; 16], a: &[u8], c: &[u8]) -> [u8; 16] {
+        let mut y = [0u8; 16];
+        {
+            let mut absorb = |block: &[u8]| {
+                for (y, &b) in y.iter_mut().zip(block.iter()) {
+                    *y ^= b;
+                }
+                y = gf128_mul(&y, h);
+            };
+            for block in a.chunks(16).chain(c.chunks(16)) {
+                absorb(block);
+            }
+            let mut lengths = [0u8; 16];
+            lengths[..8].copy_from_slice(&(a.len() as u64 * 8).to_be_bytes());
+            lengths[8..].copy_from_slice(&(c.len() as u64 * 8).to_be_bytes());
+            absorb(&lengths);
+        }
+        y
+    }
+
+    #[test]
+    fn reference_matches_cases() {
+        for &(h, a, c, g) in CASES.iter() {
+            let mut key = [0u8; 16];
+            key.copy_from_slice(h);
+            assert_eq!(&reference_ghash(&key, a, c)[..], g);
+        }
+    }
+
+    #[test]
+    fn table_and_bitwise_match_reference() {
+        let mut rng = IsaacRng::new_unseeded();
+        let mut h = [0u8; 16];
+        let mut data = [0u8; 200];
+        for _ in 0..100 {
+            rng.fill_bytes(&mut h);
+            rng.fill_bytes(&mut data);
+            let a_len = rng.gen_range(0, 100);
+            let c_len = rng.gen_range(0, 100);
+            let (a, c) = data.split_at(a_len);
+            let c = &c[..c_len];
+
+            let expected = reference_ghash(&h, a, c);
+            assert_eq!(Ghash::new(&h).input_a(a).input_c(c).result(), expected);
+            assert_eq!(Ghash::new_with_table(&h).input_a(a).input_c(c).result(), expected);
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
@@ -574,4 +761,17 @@ mod bench {
         });
         bh.bytes = bytes.len() as u64;
     }
+
+    #[bench]
+    pub fn ghash_table_64k(bh: & mut Bencher) {
+        let mut mac = [0u8; 16];
+        let key     = [0u8; 16];
+        let bytes   = [1u8; 65536];
+        bh.iter( || {
+            let mut ghash = Ghash::new_with_table(&key);
+            ghash.input(&bytes);
+            ghash.raw_result(&mut mac);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
 }
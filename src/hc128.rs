@@ -7,7 +7,7 @@
 
 use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
 use symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
-use cryptoutil::{read_u32_le, symm_enc_or_dec, write_u32_le};
+use cryptoutil::{debug_assert_no_overlap, read_u32_le, symm_enc_or_dec, write_u32_le};
 
 use std::ptr;
 
@@ -120,6 +120,7 @@ fn f2(x: u32) -> u32 {
 impl SynchronousStreamCipher for Hc128 {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
 
         if input.len() <= 4 {
             // Process data bytewise
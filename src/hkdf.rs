@@ -10,8 +10,11 @@
 use std::iter::repeat;
 use cryptoutil::copy_memory;
 
+use derivedkey::DerivedKey;
+use util::secure_memset;
+
 use digest::Digest;
-use hmac::Hmac;
+use hmac::{Hmac, PrecomputedHmac};
 use mac::Mac;
 
 /// Execute the HKDF-Extract function.  Applications MUST NOT use this for
@@ -43,8 +46,27 @@ pub fn hkdf_extract<D: Digest>(mut digest: D, salt: &[u8], ikm: &[u8], prk: &mut
 /// * okm - The output buffer to fill with the derived key value.
 pub fn hkdf_expand<D: Digest>(mut digest: D, prk: &[u8], info: &[u8], okm: &mut [u8]) {
     digest.reset();
+    expand(Hmac::new(digest, prk), info, okm);
+}
+
+/// Execute the HKDF-Expand function like hkdf_expand, but keep copies of the digest state after
+/// the HMAC key pads have been processed. Every block of output is a separate HMAC computation
+/// under the same key, so this roughly halves the time needed for long outputs, at the cost of
+/// requiring a digest that can be cloned.
+///
+/// # Arguments
+/// * digest - The digest function to use.
+/// * prk - The pseudorandom key of at least digest.output_bytes() octets.
+/// * info - The optional context and application specific information to use.
+/// * okm - The output buffer to fill with the derived key value.
+pub fn hkdf_expand_precomputed<D: Digest + Clone>(mut digest: D, prk: &[u8], info: &[u8],
+        okm: &mut [u8]) {
+    digest.reset();
+    expand(PrecomputedHmac::new(digest, prk), info, okm);
+}
 
-    let mut mac = Hmac::new(digest, prk);
+// The HKDF-Expand loop, given an HMAC keyed with the pseudorandom key.
+fn expand<M: Mac>(mut mac: M, info: &[u8], okm: &mut [u8]) {
     let os = mac.output_bytes();
     let mut t: Vec<u8> = repeat(0).take(os).collect();
     let mut n: u8 = 0;
@@ -66,6 +88,36 @@ pub fn hkdf_expand<D: Digest>(mut digest: D, prk: &[u8], info: &[u8], okm: &mut
     }
 }
 
+/// Execute HKDF-Extract followed by HKDF-Expand, filling okm with the
+/// derived key value.  The intermediate pseudorandom key is zeroed before
+/// returning.  Applications MUST NOT use this for password hashing.
+///
+/// # Arguments
+/// * digest - The digest function to use.
+/// * salt - The optional salt value (a non-secret random value) to use.
+/// * ikm - The input keying material to use.
+/// * info - The optional context and application specific information to use.
+/// * okm - The output buffer to fill with the derived key value.
+pub fn hkdf<D: Digest + Clone>(digest: D, salt: &[u8], ikm: &[u8], info: &[u8], okm: &mut [u8]) {
+    let mut prk = vec![0; digest.output_bytes()];
+    hkdf_extract(digest.clone(), salt, ikm, &mut prk);
+    hkdf_expand_precomputed(digest, &prk, info, okm);
+    secure_memset(&mut prk, 0);
+}
+
+/// Execute the HKDF-Expand function, returning a newly allocated
+/// DerivedKey of len bytes.  Applications MUST NOT use this for password
+/// hashing.
+///
+/// # Arguments
+/// * digest - The digest function to use.
+/// * prk - The pseudorandom key of at least digest.output_bytes() octets.
+/// * info - The optional context and application specific information to use.
+/// * len - The length of the key to derive. This must not be zero.
+pub fn hkdf_expand_derive<D: Digest>(digest: D, prk: &[u8], info: &[u8], len: usize) -> DerivedKey {
+    DerivedKey::new(len, |okm| hkdf_expand(digest, prk, info, okm))
+}
+
 #[cfg(test)]
 mod test {
     use std::iter::repeat;
@@ -73,7 +125,7 @@ mod test {
     use digest::Digest;
     use sha1::Sha1;
     use sha2::Sha256;
-    use hkdf::{hkdf_extract, hkdf_expand};
+    use hkdf::{hkdf, hkdf_extract, hkdf_expand, hkdf_expand_derive, hkdf_expand_precomputed};
 
     struct TestVector<D: Digest>{
         digest: D,
@@ -162,6 +214,17 @@ mod test {
             assert!(okm.len() == t.l);
             hkdf_expand(t.digest, &prk[..], &t.info[..], &mut okm);
             assert!(okm == t.okm);
+
+            let mut okm_precomputed = vec![0; t.l];
+            hkdf_expand_precomputed(t.digest, &prk[..], &t.info[..], &mut okm_precomputed);
+            assert!(okm_precomputed == t.okm);
+
+            let key = hkdf_expand_derive(t.digest, &prk[..], &t.info[..], t.l);
+            assert!(key.as_bytes() == &t.okm[..]);
+
+            let mut okm2: Vec<u8> = repeat(0).take(t.l).collect();
+            hkdf(t.digest, &t.salt[..], &t.ikm[..], &t.info[..], &mut okm2);
+            assert!(okm2 == okm);
         }
     }
 
@@ -240,4 +303,46 @@ mod test {
             assert!(okm == t.okm);
         }
     }
+
+    #[test]
+    fn test_hkdf_expand_boxed_digest() {
+        // hkdf_expand accepts digests that can't be cloned, such as one chosen at runtime
+        let prk = [0x0bu8; 32];
+        let mut expected = [0u8; 100];
+        hkdf_expand(Sha256::new(), &prk, b"info", &mut expected);
+        let mut okm = [0u8; 100];
+        let digest: Box<dyn Digest> = Box::new(Sha256::new());
+        hkdf_expand(digest, &prk, b"info", &mut okm);
+        assert!(okm[..] == expected[..]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use hkdf::{hkdf_expand, hkdf_expand_precomputed};
+    use sha2::Sha256;
+
+    #[bench]
+    pub fn hkdf_expand_sha256_8k(bh: & mut Bencher) {
+        let prk = [1u8; 32];
+        let info = [2u8; 16];
+        let mut okm = [0u8; 8160];
+        bh.iter( || {
+            hkdf_expand(Sha256::new(), &prk, &info, &mut okm);
+        });
+        bh.bytes = okm.len() as u64;
+    }
+
+    #[bench]
+    pub fn hkdf_expand_precomputed_sha256_8k(bh: & mut Bencher) {
+        let prk = [1u8; 32];
+        let info = [2u8; 16];
+        let mut okm = [0u8; 8160];
+        bh.iter( || {
+            hkdf_expand_precomputed(Sha256::new(), &prk, &info, &mut okm);
+        });
+        bh.bytes = okm.len() as u64;
+    }
 }
@@ -13,6 +13,7 @@ use std::iter::repeat;
 use cryptoutil;
 use digest::Digest;
 use mac::{Mac, MacResult};
+use util::{fixed_time_eq, secure_memset};
 
 /**
  * The Hmac struct represents an Hmac function - a Message Authentication Code using a Digest.
@@ -77,6 +78,46 @@ impl <D: Digest> Hmac<D> {
             finished: false
         }
     }
+
+    /**
+     * Compute the Hmac and write its first out.len() bytes to out, for protocols that use a
+     * truncated Hmac such as HMAC-SHA-256-128. The truncation length must be fixed by the protocol
+     * rather than chosen by whoever supplies the tag. As with raw_result(), the output must not be
+     * compared with an expected value in variable time; use verify_truncated() for that.
+     */
+    pub fn result_truncated(&mut self, out: &mut [u8]) {
+        let output_size = self.digest.output_bytes();
+        assert!(out.len() <= output_size);
+        let mut code: Vec<u8> = repeat(0).take(output_size).collect();
+        self.raw_result(&mut code);
+        cryptoutil::copy_memory(&code[..out.len()], out);
+    }
+
+    /**
+     * Check, in fixed time, whether expected matches the first expected.len() bytes of the Hmac.
+     * expected must not be empty and must not be longer than the Hmac. The truncation length must
+     * be fixed by the protocol: a verifier that accepts tags of any length would accept a forged
+     * tag as short as a single byte.
+     */
+    pub fn verify_truncated(&mut self, expected: &[u8]) -> bool {
+        assert!(!expected.is_empty());
+        let mut code: Vec<u8> = repeat(0).take(expected.len()).collect();
+        self.result_truncated(&mut code);
+        fixed_time_eq(&code, expected)
+    }
+}
+
+/**
+ * Create an Hmac for a digest that is only known at runtime, for example one picked by name from a
+ * configuration file.
+ *
+ * # Arguments
+ * * digest - The Digest to use.
+ * * key - The key to use.
+ *
+ */
+pub fn new_boxed(digest: Box<dyn Digest>, key: &[u8]) -> Box<dyn Mac> {
+    Box::new(Hmac::new(digest, key))
 }
 
 impl <D: Digest> Mac for Hmac<D> {
@@ -117,14 +158,134 @@ impl <D: Digest> Mac for Hmac<D> {
     fn output_bytes(&self) -> usize { self.digest.output_bytes() }
 }
 
+/**
+ * An Hmac that keeps copies of the digest state after the inner and the outer key pads have been
+ * processed. Resetting and finishing it then only requires copying a saved state instead of
+ * processing another block of input. This makes it faster than Hmac when many short messages are
+ * authenticated under the same key, as in HKDF-Expand, at the cost of storing two extra digests.
+ */
+pub struct PrecomputedHmac<D> {
+    digest: D,
+    inner: D,
+    outer: D,
+    finished: bool
+}
+
+impl <D: Digest + Clone> PrecomputedHmac<D> {
+    /**
+     * Create a new PrecomputedHmac instance.
+     *
+     * # Arguments
+     * * digest - The Digest to use.
+     * * key - The key to use.
+     *
+     */
+    pub fn new(mut digest: D, key: &[u8]) -> PrecomputedHmac<D> {
+        let (mut i_key, mut o_key) = create_keys(&mut digest, key);
+        let mut inner = digest.clone();
+        inner.input(&i_key[..]);
+        let mut outer = digest;
+        outer.input(&o_key[..]);
+        secure_memset(&mut i_key, 0);
+        secure_memset(&mut o_key, 0);
+        PrecomputedHmac {
+            digest: inner.clone(),
+            inner,
+            outer,
+            finished: false
+        }
+    }
+}
+
+impl <D: Digest + Clone> Mac for PrecomputedHmac<D> {
+    fn input(&mut self, data: &[u8]) {
+        assert!(!self.finished);
+        self.digest.input(data);
+    }
+
+    fn reset(&mut self) {
+        self.digest = self.inner.clone();
+        self.finished = false;
+    }
+
+    fn result(&mut self) -> MacResult {
+        let output_size = self.digest.output_bytes();
+        let mut code = vec![0; output_size];
+
+        self.raw_result(&mut code);
+
+        MacResult::new_from_owned(code)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        if !self.finished {
+            self.digest.result(output);
+
+            self.digest = self.outer.clone();
+            self.digest.input(output);
+
+            self.finished = true;
+        }
+
+        self.digest.result(output);
+    }
+
+    fn output_bytes(&self) -> usize { self.digest.output_bytes() }
+}
+
+/**
+ * Execute the P_hash data expansion function from section 5 of RFC 5246 (TLS 1.2), filling out with
+ * HMAC(secret, A(1) || seed) || HMAC(secret, A(2) || seed) || ..., where A(0) = seed and
+ * A(i) = HMAC(secret, A(i - 1)). This is the core of the TLS 1.2 PRF.
+ *
+ * # Arguments
+ * * digest - The Digest to use.
+ * * secret - The secret to use as the Hmac key.
+ * * seed - The seed to expand.
+ * * out - The output buffer to fill with the expanded value. It may be any length.
+ *
+ */
+pub fn p_hash<D: Digest + Clone>(mut digest: D, secret: &[u8], seed: &[u8], out: &mut [u8]) {
+    digest.reset();
+
+    let mut mac = PrecomputedHmac::new(digest, secret);
+    let os = mac.output_bytes();
+    let mut a = vec![0; os];
+    let mut block = vec![0; os];
+
+    mac.input(seed);
+    mac.raw_result(&mut a);
+    mac.reset();
+
+    for chunk in out.chunks_mut(os) {
+        mac.input(&a[..]);
+        mac.input(seed);
+        mac.raw_result(&mut block);
+        mac.reset();
+        let chunk_len = chunk.len();
+        cryptoutil::copy_memory(&block[..chunk_len], chunk);
+
+        mac.input(&a[..]);
+        mac.raw_result(&mut a);
+        mac.reset();
+    }
+
+    secure_memset(&mut a, 0);
+    secure_memset(&mut block, 0);
+}
+
 #[cfg(test)]
 mod test {
     use std::iter::repeat;
 
     use mac::{Mac, MacResult};
-    use hmac::Hmac;
+    use hmac::{new_boxed, p_hash, Hmac, PrecomputedHmac};
     use digest::Digest;
+    use blake2b::Blake2b;
     use md5::Md5;
+    use sha1::Sha1;
+    use sha2::{Sha256, Sha512Trunc256};
+    use serialize::hex::FromHex;
 
     struct Test {
         key: Vec<u8>,
@@ -132,9 +293,9 @@ mod test {
         expected: Vec<u8>
     }
 
-    // Test vectors from: http://tools.ietf.org/html/rfc2104
+    // Test vectors from: http://tools.ietf.org/html/rfc2104 and http://tools.ietf.org/html/rfc2202
 
-    fn tests() -> Vec<Test> {
+    fn md5_tests() -> Vec<Test> {
         vec![
             Test {
                 key: repeat(0x0bu8).take(16).collect(),
@@ -156,15 +317,155 @@ mod test {
                 expected: vec![
                     0x56, 0xbe, 0x34, 0x52, 0x1d, 0x14, 0x4c, 0x88,
                     0xdb, 0xb8, 0xc7, 0x33, 0xf0, 0xe8, 0xb3, 0xf6 ]
+            },
+            // The key is longer than the block size, so it must be hashed first
+            Test {
+                key: vec![0xaau8; 80],
+                data: b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                expected: vec![
+                    0x6b, 0x1a, 0xb7, 0xfe, 0x4b, 0xd7, 0xbf, 0x8f,
+                    0x0b, 0x62, 0xe6, 0xce, 0x61, 0xb9, 0xd0, 0xcd ]
+            },
+            Test {
+                key: vec![0xaau8; 80],
+                data: b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data"
+                    .to_vec(),
+                expected: vec![
+                    0x6f, 0x63, 0x0f, 0xad, 0x67, 0xcd, 0xa0, 0xee,
+                    0x1f, 0xb1, 0xf5, 0x62, 0xdb, 0x3a, 0xa5, 0x3e ]
+            },
+            Test {
+                key: Vec::new(),
+                data: Vec::new(),
+                expected: vec![
+                    0x74, 0xe6, 0xf7, 0x29, 0x8a, 0x9c, 0x2d, 0x16,
+                    0x89, 0x35, 0xf5, 0x8c, 0x00, 0x1b, 0xad, 0x88 ]
             }
         ]
     }
 
-    #[test]
-    fn test_hmac_md5() {
-        let tests = tests();
+    fn sha1_tests() -> Vec<Test> {
+        vec![
+            Test {
+                key: vec![0x0bu8; 20],
+                data: b"Hi There".to_vec(),
+                expected: vec![
+                    0xb6, 0x17, 0x31, 0x86, 0x55, 0x05, 0x72, 0x64,
+                    0xe2, 0x8b, 0xc0, 0xb6, 0xfb, 0x37, 0x8c, 0x8e,
+                    0xf1, 0x46, 0xbe, 0x00 ]
+            },
+            Test {
+                key: b"Jefe".to_vec(),
+                data: b"what do ya want for nothing?".to_vec(),
+                expected: vec![
+                    0xef, 0xfc, 0xdf, 0x6a, 0xe5, 0xeb, 0x2f, 0xa2,
+                    0xd2, 0x74, 0x16, 0xd5, 0xf1, 0x84, 0xdf, 0x9c,
+                    0x25, 0x9a, 0x7c, 0x79 ]
+            },
+            // The key is longer than the block size, so it must be hashed first
+            Test {
+                key: vec![0xaau8; 80],
+                data: b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                expected: vec![
+                    0xaa, 0x4a, 0xe5, 0xe1, 0x52, 0x72, 0xd0, 0x0e,
+                    0x95, 0x70, 0x56, 0x37, 0xce, 0x8a, 0x3b, 0x55,
+                    0xed, 0x40, 0x21, 0x12 ]
+            },
+            Test {
+                key: vec![0xaau8; 80],
+                data: b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data"
+                    .to_vec(),
+                expected: vec![
+                    0xe8, 0xe9, 0x9d, 0x0f, 0x45, 0x23, 0x7d, 0x78,
+                    0x6d, 0x6b, 0xba, 0xa7, 0x96, 0x5c, 0x78, 0x08,
+                    0xbb, 0xff, 0x1a, 0x91 ]
+            },
+            Test {
+                key: Vec::new(),
+                data: Vec::new(),
+                expected: vec![
+                    0xfb, 0xdb, 0x1d, 0x1b, 0x18, 0xaa, 0x6c, 0x08,
+                    0x32, 0x4b, 0x7d, 0x64, 0xb7, 0x1f, 0xb7, 0x63,
+                    0x70, 0x69, 0x0e, 0x1d ]
+            }
+        ]
+    }
+
+    // Test vectors for HMAC-SHA-512/256 generated with Python's hmac and hashlib modules. The digest
+    // output (32 bytes) is much smaller than its block size (128 bytes).
+    fn sha512_trunc256_tests() -> Vec<Test> {
+        vec![
+            Test {
+                key: vec![0x0bu8; 20],
+                data: b"Hi There".to_vec(),
+                expected: vec![
+                    0x9f, 0x91, 0x26, 0xc3, 0xd9, 0xc3, 0xc3, 0x30,
+                    0xd7, 0x60, 0x42, 0x5c, 0xa8, 0xa2, 0x17, 0xe3,
+                    0x1f, 0xea, 0xe3, 0x1b, 0xfe, 0x70, 0x19, 0x6f,
+                    0xf8, 0x16, 0x42, 0xb8, 0x68, 0x40, 0x2e, 0xab ]
+            },
+            Test {
+                key: b"Jefe".to_vec(),
+                data: b"what do ya want for nothing?".to_vec(),
+                expected: vec![
+                    0x6d, 0xf7, 0xb2, 0x46, 0x30, 0xd5, 0xcc, 0xb2,
+                    0xee, 0x33, 0x54, 0x07, 0x08, 0x1a, 0x87, 0x18,
+                    0x8c, 0x22, 0x14, 0x89, 0x76, 0x8f, 0xa2, 0x02,
+                    0x05, 0x13, 0xb2, 0xd5, 0x93, 0x35, 0x94, 0x56 ]
+            },
+            Test {
+                key: vec![0xaau8; 20],
+                data: vec![0xddu8; 50],
+                expected: vec![
+                    0x22, 0x90, 0x06, 0x39, 0x1d, 0x66, 0xc8, 0xec,
+                    0xdd, 0xf4, 0x3b, 0xa5, 0xcf, 0x8f, 0x83, 0x53,
+                    0x0e, 0xf2, 0x21, 0xa4, 0xe9, 0x40, 0x18, 0x40,
+                    0xd1, 0xbe, 0xad, 0x51, 0x37, 0xc8, 0xa2, 0xea ]
+            },
+            // The key is exactly the block size, so it is used without being hashed
+            Test {
+                key: vec![0xaau8; 128],
+                data: b"Test Using Block-Size Key".to_vec(),
+                expected: vec![
+                    0xd4, 0x58, 0x4b, 0xf5, 0x8b, 0x51, 0x27, 0x00,
+                    0x34, 0x8f, 0x7d, 0x58, 0x25, 0x46, 0xd8, 0x44,
+                    0x88, 0xee, 0x2e, 0x00, 0xf5, 0xbe, 0xc6, 0xa1,
+                    0xf4, 0xc2, 0xc5, 0x71, 0xce, 0xf2, 0x83, 0x22 ]
+            },
+            // The key is one byte longer than the block size, so it must be hashed first
+            Test {
+                key: vec![0xaau8; 129],
+                data: b"Test Using Block-Size Key".to_vec(),
+                expected: vec![
+                    0xe9, 0xa0, 0x07, 0x75, 0x01, 0x15, 0xdb, 0xf4,
+                    0x68, 0xaa, 0x05, 0xac, 0x34, 0x3b, 0x58, 0x25,
+                    0x25, 0x2d, 0x3a, 0x21, 0x06, 0xb3, 0xc3, 0x57,
+                    0x75, 0x95, 0x54, 0xfd, 0x75, 0x9c, 0x9e, 0xc1 ]
+            },
+            Test {
+                key: vec![0xaau8; 131],
+                data: b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                expected: vec![
+                    0x87, 0x12, 0x3c, 0x45, 0xf7, 0xc5, 0x37, 0xa4,
+                    0x04, 0xf8, 0xf4, 0x7c, 0xdb, 0xed, 0xda, 0x1f,
+                    0xc9, 0xbe, 0xc6, 0x0e, 0xeb, 0x97, 0x19, 0x82,
+                    0xce, 0x7e, 0xf1, 0x0e, 0x77, 0x4e, 0x65, 0x39 ]
+            },
+            Test {
+                key: Vec::new(),
+                data: Vec::new(),
+                expected: vec![
+                    0xb7, 0x9c, 0x99, 0x51, 0xdf, 0x59, 0x52, 0x74,
+                    0x58, 0x2d, 0xc0, 0x94, 0xa1, 0xba, 0x46, 0xc3,
+                    0x3e, 0x4a, 0x36, 0x87, 0x8b, 0x2d, 0x83, 0xcb,
+                    0x85, 0x53, 0xf0, 0xfe, 0x46, 0x7d, 0xcd, 0xcf ]
+            }
+        ]
+    }
+
+    fn run_tests<D: Digest + Clone>(digest: D, tests: &[Test]) {
         for t in tests.iter() {
-            let mut hmac = Hmac::new(Md5::new(), &t.key[..]);
+            let mut hmac = Hmac::new(digest.clone(), &t.key[..]);
 
             hmac.input(&t.data[..]);
             let result = hmac.result();
@@ -180,11 +481,9 @@ mod test {
         }
     }
 
-    #[test]
-    fn test_hmac_md5_incremental() {
-        let tests = tests();
+    fn run_tests_incremental<D: Digest + Clone>(digest: D, tests: &[Test]) {
         for t in tests.iter() {
-            let mut hmac = Hmac::new(Md5::new(), &t.key[..]);
+            let mut hmac = Hmac::new(digest.clone(), &t.key[..]);
             for i in 0..t.data.len() {
                 hmac.input(&t.data[i..i + 1]);
             }
@@ -193,4 +492,152 @@ mod test {
             assert!(result == expected);
         }
     }
+
+    #[test]
+    fn test_hmac_md5() {
+        run_tests(Md5::new(), &md5_tests());
+    }
+
+    #[test]
+    fn test_hmac_md5_incremental() {
+        run_tests_incremental(Md5::new(), &md5_tests());
+    }
+
+    #[test]
+    fn test_hmac_sha1() {
+        run_tests(Sha1::new(), &sha1_tests());
+    }
+
+    #[test]
+    fn test_hmac_sha1_incremental() {
+        run_tests_incremental(Sha1::new(), &sha1_tests());
+    }
+
+    #[test]
+    fn test_hmac_sha512_trunc256() {
+        run_tests(Sha512Trunc256::new(), &sha512_trunc256_tests());
+    }
+
+    #[test]
+    fn test_hmac_sha512_trunc256_incremental() {
+        run_tests_incremental(Sha512Trunc256::new(), &sha512_trunc256_tests());
+    }
+
+    #[test]
+    fn test_hmac_sha256_truncated() {
+        // RFC 4231, test case 5 - HMAC-SHA-256 truncated to 128 bits
+        let mut hmac = Hmac::new(Sha256::new(), &[0x0cu8; 20]);
+        hmac.input(b"Test With Truncation");
+        let mut out = [0u8; 16];
+        hmac.result_truncated(&mut out);
+        assert_eq!(out.to_vec(), "a3b6167473100ee06e0c796c2955552b".from_hex().unwrap());
+        assert!(hmac.verify_truncated(&out));
+
+        // RFC 4231, test case 2, truncated to 96 bits (HMAC-SHA-256-96) and to 128 bits
+        // (HMAC-SHA-256-128, RFC 4868)
+        let full = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+            .from_hex().unwrap();
+        for &len in [12, 16].iter() {
+            let mut hmac = Hmac::new(Sha256::new(), b"Jefe");
+            hmac.input(b"what do ya want for nothing?");
+            let mut out = vec![0u8; len];
+            hmac.result_truncated(&mut out);
+            assert!(out[..] == full[..len]);
+            assert!(hmac.verify_truncated(&full[..len]));
+
+            let mut bad = full[..len].to_vec();
+            bad[len - 1] ^= 1;
+            assert!(!hmac.verify_truncated(&bad));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hmac_verify_truncated_empty() {
+        let mut hmac = Hmac::new(Sha256::new(), b"Jefe");
+        hmac.verify_truncated(&[]);
+    }
+
+    fn run_tests_precomputed<D: Digest + Clone>(digest: D, tests: &[Test]) {
+        for t in tests.iter() {
+            let mut hmac = PrecomputedHmac::new(digest.clone(), &t.key[..]);
+            let expected = MacResult::new(&t.expected[..]);
+
+            hmac.input(&t.data[..]);
+            assert!(hmac.result() == expected);
+
+            // The result can be read again before a reset
+            let mut raw = vec![0u8; t.expected.len()];
+            hmac.raw_result(&mut raw);
+            assert!(raw == t.expected);
+
+            hmac.reset();
+            for i in 0..t.data.len() {
+                hmac.input(&t.data[i..i + 1]);
+            }
+            assert!(hmac.result() == expected);
+        }
+    }
+
+    #[test]
+    fn test_precomputed_hmac() {
+        run_tests_precomputed(Md5::new(), &md5_tests());
+        run_tests_precomputed(Sha1::new(), &sha1_tests());
+        run_tests_precomputed(Sha512Trunc256::new(), &sha512_trunc256_tests());
+    }
+
+    #[test]
+    fn test_hmac_boxed() {
+        let keys: [&[u8]; 3] = [b"Jefe", &[0x0b; 20], &[0xaa; 131]];
+        for key in keys.iter() {
+            let mut generic = Hmac::new(Sha256::new(), key);
+            generic.input(b"what do ya want for nothing?");
+
+            let digest: Box<dyn Digest> = Box::new(Sha256::new());
+            let mut boxed = new_boxed(digest, key);
+            assert_eq!(boxed.output_bytes(), 32);
+            boxed.input(b"what do ya want");
+            boxed.input(b" for nothing?");
+            assert!(boxed.result() == generic.result());
+        }
+
+        // RFC 4231, test case 2
+        let mut boxed = new_boxed(Box::new(Sha256::new()), b"Jefe");
+        boxed.input(b"what do ya want for nothing?");
+        let expected = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        assert!(boxed.result() == MacResult::new(&expected.from_hex().unwrap()));
+    }
+
+    #[test]
+    fn test_p_hash_sha256() {
+        // The TLS 1.2 PRF-SHA256 vector widely used for interop testing. The PRF is P_hash with
+        // the label "test label" prepended to the seed.
+        let secret = "9bbe436ba940f017b17652849a71db35".from_hex().unwrap();
+        let mut seed = b"test label".to_vec();
+        seed.extend("a0ba9f936cda311827a6f796ffd5198c".from_hex().unwrap());
+        let expected = "e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a\
+                        6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab\
+                        4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff701\
+                        87347b66".from_hex().unwrap();
+
+        let mut out = [0u8; 100];
+        p_hash(Sha256::new(), &secret, &seed, &mut out);
+        assert!(out[..] == expected[..]);
+
+        // A shorter output is a prefix of a longer one
+        let mut short = [0u8; 20];
+        p_hash(Sha256::new(), &secret, &seed, &mut short);
+        assert!(short[..] == expected[..20]);
+    }
+
+    #[test]
+    fn test_hmac_blake2b() {
+        // HMAC pads the key to the 128 byte block size of BLAKE2b. The expected value is from
+        // Python's hmac module with hashlib.blake2b.
+        let mut hmac = Hmac::new(Blake2b::new(64), b"key");
+        hmac.input(b"The quick brown fox jumps over the lazy dog");
+        let expected = "92294f92c0dfb9b00ec9ae8bd94d7e7d8a036b885a499f149dfe2fd2199394aa\
+                        af6b8894a1730cccb2cd050f9bcf5062a38b51b0dab33207f8ef35ae2c9df51b";
+        assert!(hmac.result() == MacResult::new(&expected.from_hex().unwrap()));
+    }
 }
@@ -0,0 +1,201 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements HMAC_DRBG - the deterministic random bit generator built on HMAC that is
+ * specified in NIST SP 800-90A, section 10.1.2.
+ *
+ * Prediction resistance is not supported: callers that need it should call reseed() with fresh
+ * entropy before each call to generate().
+ */
+
+use std::cmp;
+
+use cryptoutil::copy_memory;
+use digest::Digest;
+use hmac::Hmac;
+use mac::Mac;
+use util::secure_memset;
+
+// The maximum number of requests between reseeds (SP 800-90A, Table 2)
+const RESEED_INTERVAL: u64 = 1 << 48;
+
+// The maximum number of bytes that may be generated by a single request (2^19 bits)
+const MAX_BYTES_PER_REQUEST: usize = 1 << 16;
+
+/**
+ * The HmacDrbg struct holds the working state (K, V, and the reseed counter) of an HMAC_DRBG
+ * instance. The state is zeroed when it is dropped.
+ */
+pub struct HmacDrbg<D> {
+    digest: D,
+    k: Vec<u8>,
+    v: Vec<u8>,
+    reseed_counter: u64
+}
+
+impl <D: Digest + Clone> HmacDrbg<D> {
+    /**
+     * Instantiate a new HmacDrbg.
+     *
+     * # Arguments
+     * * digest - The Digest to use.
+     * * entropy - The entropy input. This should contain at least as many bits of entropy as the
+     *   security strength required.
+     * * nonce - The nonce. This should contain at least half as many bits of entropy as the
+     *   security strength required, or be a value that never repeats.
+     * * personalization - The optional personalization string.
+     *
+     */
+    pub fn new(mut digest: D, entropy: &[u8], nonce: &[u8], personalization: &[u8])
+            -> HmacDrbg<D> {
+        digest.reset();
+        let output_bytes = digest.output_bytes();
+        let mut drbg = HmacDrbg {
+            digest,
+            k: vec![0; output_bytes],
+            v: vec![1; output_bytes],
+            reseed_counter: 1
+        };
+        drbg.update(&[entropy, nonce, personalization]);
+        drbg
+    }
+
+    /**
+     * Reseed the HmacDrbg with fresh entropy and optional additional input.
+     */
+    pub fn reseed(&mut self, entropy: &[u8], additional: &[u8]) {
+        self.update(&[entropy, additional]);
+        self.reseed_counter = 1;
+    }
+
+    /**
+     * Fill out with pseudo random bytes, first mixing in the optional additional input. An error is
+     * returned, and out is left unmodified, if more than 65536 bytes are requested at once or if
+     * the HmacDrbg must be reseeded before it can generate any more output.
+     */
+    pub fn generate(&mut self, out: &mut [u8], additional: &[u8]) -> Result<(), &'static str> {
+        if out.len() > MAX_BYTES_PER_REQUEST {
+            return Err("too many bytes requested from HmacDrbg at once");
+        }
+        if self.reseed_counter > RESEED_INTERVAL {
+            return Err("HmacDrbg must be reseeded");
+        }
+
+        if !additional.is_empty() {
+            self.update(&[additional]);
+        }
+
+        let mut pos = 0;
+        while pos < out.len() {
+            self.hmac_v();
+            let count = cmp::min(self.v.len(), out.len() - pos);
+            copy_memory(&self.v[..count], &mut out[pos..pos + count]);
+            pos += count;
+        }
+
+        self.update(&[additional]);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+
+    // V = HMAC(K, V)
+    fn hmac_v(&mut self) {
+        let mut mac = Hmac::new(self.digest.clone(), &self.k);
+        mac.input(&self.v);
+        mac.raw_result(&mut self.v);
+    }
+
+    // The HMAC_DRBG_Update function. The provided data is the concatenation of all of the slices in
+    // data.
+    fn update(&mut self, data: &[&[u8]]) {
+        self.update_round(0, data);
+        if data.iter().any(|d| !d.is_empty()) {
+            self.update_round(1, data);
+        }
+    }
+
+    // K = HMAC(K, V || round || data), then V = HMAC(K, V)
+    fn update_round(&mut self, round: u8, data: &[&[u8]]) {
+        let mut mac = Hmac::new(self.digest.clone(), &self.k);
+        mac.input(&self.v);
+        mac.input(&[round]);
+        for d in data.iter() {
+            mac.input(d);
+        }
+        mac.raw_result(&mut self.k);
+        self.hmac_v();
+    }
+}
+
+impl <D> Drop for HmacDrbg<D> {
+    fn drop(&mut self) {
+        secure_memset(&mut self.k, 0);
+        secure_memset(&mut self.v, 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hmac_drbg::HmacDrbg;
+    use sha2::Sha256;
+    use serialize::hex::FromHex;
+
+    // From the NIST DRBGVS vectors for HMAC_DRBG with SHA-256, prediction resistance disabled and
+    // no reseeding: [SHA-256], [EntropyInputLen = 256], [NonceLen = 128],
+    // [PersonalizationStringLen = 0], [AdditionalInputLen = 0], [ReturnedBitsLen = 1024], COUNT = 0.
+    #[test]
+    fn test_nist_sha256() {
+        let entropy = "ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488"
+            .from_hex().unwrap();
+        let nonce = "659ba96c601dc69fc902940805ec0ca8".from_hex().unwrap();
+        let expected = "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89\
+                        d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1\
+                        07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668\
+                        961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8"
+            .from_hex().unwrap();
+
+        let mut drbg = HmacDrbg::new(Sha256::new(), &entropy, &nonce, &[]);
+        let mut out = [0u8; 128];
+        // The returned bits are the output of the second call to generate
+        drbg.generate(&mut out, &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+        assert!(out[..] == expected[..]);
+    }
+
+    #[test]
+    fn test_reseed_and_additional_input() {
+        let new_drbg = || HmacDrbg::new(Sha256::new(), &[1u8; 32], &[2u8; 16], b"personal");
+        let mut a = new_drbg();
+        let mut b = new_drbg();
+
+        let mut out_a = [0u8; 40];
+        let mut out_b = [0u8; 40];
+        a.generate(&mut out_a, &[]).unwrap();
+        b.generate(&mut out_b, &[]).unwrap();
+        assert!(out_a[..] == out_b[..]);
+
+        // Additional input changes the output and the following state
+        a.generate(&mut out_a, b"additional").unwrap();
+        b.generate(&mut out_b, &[]).unwrap();
+        assert!(out_a[..] != out_b[..]);
+
+        a.reseed(&[3u8; 32], &[]);
+        b.reseed(&[3u8; 32], &[]);
+        a.generate(&mut out_a, &[]).unwrap();
+        b.generate(&mut out_b, &[]).unwrap();
+        assert!(out_a[..] != out_b[..]);
+    }
+
+    #[test]
+    fn test_request_too_large() {
+        let mut drbg = HmacDrbg::new(Sha256::new(), &[1u8; 32], &[2u8; 16], &[]);
+        let mut out = vec![0u8; 65537];
+        assert!(drbg.generate(&mut out, &[]).is_err());
+        assert!(out.iter().all(|&x| x == 0));
+        assert!(drbg.generate(&mut out[..65536], &[]).is_ok());
+    }
+}
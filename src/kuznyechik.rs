@@ -0,0 +1,331 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * An implementation of the Kuznyechik block cipher as specified by GOST R 34.12-2015 and RFC 7801,
+ * https://tools.ietf.org/html/rfc7801.
+ *
+ * Kuznyechik has a 128-bit block and a 256-bit key. Each of its 9 full rounds XORs in a round key
+ * and then applies the non-linear bijection pi to every byte followed by a linear transformation
+ * over GF(2^8). The 10 round keys are derived from the key by a Feistel network that uses the
+ * round function itself with constant keys.
+ *
+ * The standard writes blocks as big integers, most significant byte first, and this module stores
+ * them in that order so that blocks and keys match the hex strings printed in the standard.
+ *
+ * The round function is implemented with table lookups indexed by the data, so this
+ * implementation is not constant time.
+ */
+
+use cryptoutil::{read_u128_be, write_u128_be};
+use streebog::PI;
+use symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+#[derive(Clone, Copy)]
+pub struct Kuznyechik {
+    round_keys: [u128; 10]
+}
+
+// The coefficients of the linear function l, applied to the bytes of a block from the most
+// significant to the least significant.
+const L_COEFFICIENTS: [u8; 16] = [
+    148, 32, 133, 16, 194, 192, 1, 251, 1, 192, 194, 16, 133, 32, 148, 1
+];
+
+// Multiply two elements of GF(2^8) modulo x^8 + x^7 + x^6 + x + 1.
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut r = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            r ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0xc3;
+        }
+        b >>= 1;
+    }
+    r
+}
+
+// The byte of x at position i, counting from the most significant byte
+const fn byte(x: u128, i: usize) -> u8 {
+    (x >> (8 * (15 - i))) as u8
+}
+
+// The linear transformation L, which is 16 applications of the shift register R.
+const fn l_transform(mut x: u128) -> u128 {
+    let mut round = 0;
+    while round < 16 {
+        let mut l = 0;
+        let mut i = 0;
+        while i < 16 {
+            l ^= gf_mul(byte(x, i), L_COEFFICIENTS[i]);
+            i += 1;
+        }
+        x = ((l as u128) << 120) | (x >> 8);
+        round += 1;
+    }
+    x
+}
+
+// The inverse of L, which is 16 applications of the inverse of R.
+const fn l_inverse_transform(mut x: u128) -> u128 {
+    let mut round = 0;
+    while round < 16 {
+        let mut l = 0;
+        let mut i = 0;
+        while i < 16 {
+            l ^= gf_mul(byte(x, (i + 1) % 16), L_COEFFICIENTS[i]);
+            i += 1;
+        }
+        x = (x << 8) | (l as u128);
+        round += 1;
+    }
+    x
+}
+
+// Multiply every byte of x by c
+const fn scale(x: u128, c: u8) -> u128 {
+    let mut r = 0;
+    let mut i = 0;
+    while i < 16 {
+        r = (r << 8) | gf_mul(byte(x, i), c) as u128;
+        i += 1;
+    }
+    r
+}
+
+// Computes the tables for a linear transformation given by the function f, which is either L or
+// its inverse. Entry [i][b] is the result of applying f to a block whose only non-zero byte is at
+// position i and holds the value of b after it has gone through the S-box s. Since the
+// transformation is linear over GF(2^8), it is the column f(1 at position i) scaled by s[b].
+const fn make_table(inverse: bool, s: &[u8; 256]) -> [[u128; 256]; 16] {
+    let mut table = [[0u128; 256]; 16];
+    let mut i = 0;
+    while i < 16 {
+        let unit = 1u128 << (8 * (15 - i));
+        let column = if inverse { l_inverse_transform(unit) } else { l_transform(unit) };
+        let mut b = 0;
+        while b < 256 {
+            table[i][b] = scale(column, s[b]);
+            b += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn make_identity() -> [u8; 256] {
+    let mut s = [0u8; 256];
+    let mut b = 0;
+    while b < 256 {
+        s[b] = b as u8;
+        b += 1;
+    }
+    s
+}
+
+const fn make_pi_inverse() -> [u8; 256] {
+    let mut s = [0u8; 256];
+    let mut b = 0;
+    while b < 256 {
+        s[PI[b] as usize] = b as u8;
+        b += 1;
+    }
+    s
+}
+
+// The round constants C_i = L(i) used by the key schedule
+const fn make_constants() -> [u128; 32] {
+    let mut c = [0u128; 32];
+    let mut i = 0;
+    while i < 32 {
+        c[i] = l_transform(i as u128 + 1);
+        i += 1;
+    }
+    c
+}
+
+static PI_INVERSE: [u8; 256] = make_pi_inverse();
+
+// The combined LS transformation used when encrypting
+static LS_TABLE: [[u128; 256]; 16] = make_table(false, &PI);
+
+// The inverse linear transformation used when decrypting
+static L_INVERSE_TABLE: [[u128; 256]; 16] = make_table(true, &make_identity());
+
+static C: [u128; 32] = make_constants();
+
+fn lookup(table: &[[u128; 256]; 16], x: u128) -> u128 {
+    let mut r = 0;
+    for (i, row) in table.iter().enumerate() {
+        r ^= row[byte(x, i) as usize];
+    }
+    r
+}
+
+// The LSX transformation: XOR in the key, then apply the S-box and the linear transformation.
+fn lsx(k: u128, x: u128) -> u128 {
+    lookup(&LS_TABLE, k ^ x)
+}
+
+fn s_inverse(x: u128) -> u128 {
+    let mut r = 0;
+    for i in 0..16 {
+        r = (r << 8) | PI_INVERSE[byte(x, i) as usize] as u128;
+    }
+    r
+}
+
+impl Kuznyechik {
+    /// Create a Kuznyechik cipher for a 32 byte key.
+    pub fn new(key: &[u8]) -> Kuznyechik {
+        assert!(key.len() == 32);
+
+        let mut round_keys = [0u128; 10];
+        round_keys[0] = read_u128_be(&key[0..16]);
+        round_keys[1] = read_u128_be(&key[16..32]);
+
+        // Each pair of round keys comes from running the previous pair through 8 rounds of a
+        // Feistel network keyed by the round constants.
+        for pair in 1..5 {
+            let mut a1 = round_keys[2 * pair - 2];
+            let mut a0 = round_keys[2 * pair - 1];
+            for c in C[8 * (pair - 1)..8 * pair].iter() {
+                let t = lsx(*c, a1) ^ a0;
+                a0 = a1;
+                a1 = t;
+            }
+            round_keys[2 * pair] = a1;
+            round_keys[2 * pair + 1] = a0;
+        }
+
+        Kuznyechik {
+            round_keys
+        }
+    }
+
+    fn encrypt(&self, mut x: u128) -> u128 {
+        for k in self.round_keys[..9].iter() {
+            x = lsx(*k, x);
+        }
+        x ^ self.round_keys[9]
+    }
+
+    fn decrypt(&self, mut x: u128) -> u128 {
+        x ^= self.round_keys[9];
+        for k in self.round_keys[..9].iter().rev() {
+            x = s_inverse(lookup(&L_INVERSE_TABLE, x)) ^ *k;
+        }
+        x
+    }
+}
+
+impl BlockEncryptor for Kuznyechik {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        write_u128_be(output, self.encrypt(read_u128_be(input)));
+    }
+}
+
+impl BlockDecryptor for Kuznyechik {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        write_u128_be(output, self.decrypt(read_u128_be(input)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use kuznyechik::{l_inverse_transform, l_transform, Kuznyechik};
+    use symmetriccipher::{BlockEncryptor, BlockDecryptor};
+    use serialize::hex::FromHex;
+
+    // The example from appendix A.1 of GOST R 34.12-2015, also given in section 5 of RFC 7801
+    const KEY: &str = "8899aabbccddeeff0011223344556677fedcba98765432100123456789abcdef";
+    const PLAINTEXT: &str = "1122334455667700ffeeddccbbaa9988";
+    const CIPHERTEXT: &str = "7f679d90bebc24305a468d42b9d4edcd";
+
+    #[test]
+    fn test_linear_transformation() {
+        // The worked examples of L from the standard
+        let vectors = [
+            (0x64a59400000000000000000000000000, 0xd456584dd0e3e84cc3166e4b7fa2890d),
+            (0xd456584dd0e3e84cc3166e4b7fa2890d, 0x79d26221b87b584cd42fbc4ffea5de9a),
+            (0x79d26221b87b584cd42fbc4ffea5de9a, 0x0e93691a0cfc60408b7b68f66b513c13),
+            (0x0e93691a0cfc60408b7b68f66b513c13, 0xe6a8094fee0aa204fd97bcb0b44b8580)];
+        for &(input, output) in vectors.iter() {
+            assert_eq!(l_transform(input), output);
+            assert_eq!(l_inverse_transform(output), input);
+        }
+    }
+
+    #[test]
+    fn test_round_keys() {
+        let expected = [
+            0x8899aabbccddeeff0011223344556677, 0xfedcba98765432100123456789abcdef,
+            0xdb31485315694343228d6aef8cc78c44, 0x3d4553d8e9cfec6815ebadc40a9ffd04,
+            0x57646468c44a5e28d3e59246f429f1ac, 0xbd079435165c6432b532e82834da581b,
+            0x51e640757e8745de705727265a0098b1, 0x5a7925017b9fdd3ed72a91a22286f984,
+            0xbb44e25378c73123a5f32f73cdb6e517, 0x72e9dd7416bcf45b755dbaa88e4a4043];
+        let state = Kuznyechik::new(&KEY.from_hex().unwrap());
+        assert_eq!(state.round_keys, expected);
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let state = Kuznyechik::new(&KEY.from_hex().unwrap());
+        let mut output = [0u8; 16];
+        state.encrypt_block(&PLAINTEXT.from_hex().unwrap(), &mut output);
+        assert!(output[..] == CIPHERTEXT.from_hex().unwrap()[..]);
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let state = Kuznyechik::new(&KEY.from_hex().unwrap());
+        let mut output = [0u8; 16];
+        state.decrypt_block(&CIPHERTEXT.from_hex().unwrap(), &mut output);
+        assert!(output[..] == PLAINTEXT.from_hex().unwrap()[..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_short_key() {
+        Kuznyechik::new(&[0u8; 16]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use kuznyechik::Kuznyechik;
+    use symmetriccipher::BlockEncryptor;
+    use test::Bencher;
+
+    #[bench]
+    fn kuznyechik(bh: &mut Bencher) {
+        let key = [0u8; 32];
+        let plaintext = [1u8; 16];
+        let state = Kuznyechik::new(&key);
+        let mut ciphertext = [0u8; 16];
+
+        bh.iter(|| {
+            state.encrypt_block(&plaintext, &mut ciphertext);
+        });
+        bh.bytes = 16u64;
+    }
+}
@@ -18,6 +18,7 @@ pub mod aead;
 pub mod aes;
 pub mod aes_gcm;
 pub mod aessafe;
+pub mod ascon;
 pub mod bcrypt;
 pub mod bcrypt_pbkdf;
 pub mod blake2b;
@@ -25,32 +26,52 @@ pub mod blake2s;
 pub mod blockmodes;
 pub mod blowfish;
 pub mod buffer;
+pub mod cast5;
+pub mod cbc_mac;
+pub mod ccm;
 pub mod chacha20;
 pub mod chacha20poly1305;
+pub mod cmac;
+pub mod crc;
+pub mod cryptoio;
 mod cryptoutil;
 pub mod curve25519;
+pub mod derivedkey;
 pub mod digest;
+pub mod eax;
 pub mod ed25519;
+pub mod ff1;
 pub mod fortuna;
 pub mod ghash;
 pub mod hc128;
 pub mod hmac;
+pub mod hmac_drbg;
 pub mod hkdf;
+pub mod kuznyechik;
 pub mod mac;
+pub mod md2;
 pub mod md5;
 pub mod pbkdf2;
 pub mod poly1305;
 pub mod rc4;
+pub mod rc5;
+pub mod rfc6979;
 pub mod ripemd160;
 pub mod salsa20;
 pub mod scrypt;
+#[cfg(feature = "zeroize_on_drop")]
+pub mod secret;
 pub mod sha1;
 pub mod sha2;
 pub mod sha3;
 mod simd;
+pub mod sm3;
 pub mod sosemanuk;
 mod step_by;
+pub mod streebog;
 pub mod symmetriccipher;
+pub mod tiger;
+pub mod tls_prf;
 pub mod util;
 pub mod whirlpool;
 
@@ -8,7 +8,7 @@
  * The mac module defines the Message Authentication Code (Mac) trait.
  */
 
-use util::fixed_time_eq;
+use util::{fixed_time_eq, secure_memset};
 
 /**
  * The Mac trait defines methods for a Message Authentication function.
@@ -44,6 +44,12 @@ pub trait Mac {
      * Get the size of the Mac code, in bytes.
      */
     fn output_bytes(&self) -> usize;
+
+    /**
+     * Get the size of the Mac code, in bits. This mirrors Digest::output_bits() for code that is
+     * generic over both traits.
+     */
+    fn output_bits(&self) -> usize { self.output_bytes() * 8 }
 }
 
 /**
@@ -79,6 +85,15 @@ impl MacResult {
     pub fn code<'s>(&'s self) -> &'s [u8] {
         &self.code[..]
     }
+
+    /**
+     * Check whether other is equal to the code, for example a tag read from the wire. The
+     * comparison runs in fixed time. A slice of a different length is rejected; only the fact that
+     * the lengths differ is revealed, not how much of the code matched.
+     */
+    pub fn verify_slice(&self, other: &[u8]) -> bool {
+        fixed_time_eq(&self.code, other)
+    }
 }
 
 impl PartialEq for MacResult {
@@ -90,3 +105,129 @@ impl PartialEq for MacResult {
 }
 
 impl Eq for MacResult { }
+
+/**
+ * A VerifyingMac wraps a Mac that is only used to check a received code. The code is never exposed:
+ * the only way to obtain a result is verify(), which compares it to the expected code in fixed time.
+ */
+pub struct VerifyingMac<M: Mac> {
+    mac: M
+}
+
+impl <M: Mac> VerifyingMac<M> {
+    /**
+     * Create a new VerifyingMac.
+     *
+     * # Arguments
+     * * mac - The Mac to use. It should already be initialized with the key.
+     *
+     */
+    pub fn new(mac: M) -> VerifyingMac<M> {
+        VerifyingMac {
+            mac
+        }
+    }
+
+    /**
+     * Process input data.
+     */
+    pub fn input(&mut self, data: &[u8]) {
+        self.mac.input(data);
+    }
+
+    /**
+     * Reset the Mac state to begin processing another input stream.
+     */
+    pub fn reset(&mut self) {
+        self.mac.reset();
+    }
+
+    /**
+     * Check whether expected is the Mac code of the data processed so far. The comparison runs in
+     * fixed time, and a code of the wrong length is never accepted. Call reset() before processing
+     * another input stream.
+     */
+    pub fn verify(&mut self, expected: &[u8]) -> bool {
+        let mut code = vec![0; self.mac.output_bytes()];
+        self.mac.raw_result(&mut code);
+        let result = fixed_time_eq(&code, expected);
+        secure_memset(&mut code, 0);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aessafe::AesSafe128Encryptor;
+    use cmac::Cmac;
+    use hmac::Hmac;
+    use mac::{Mac, MacResult, VerifyingMac};
+    use poly1305::Poly1305;
+    use serialize::hex::FromHex;
+    use sha2::{Sha256, Sha512};
+
+    #[test]
+    fn test_mac_result_verify_slice() {
+        let code = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+            .from_hex().unwrap();
+        let result = MacResult::new(&code);
+        assert!(result.verify_slice(&code));
+
+        for i in 0..code.len() {
+            let mut tampered = code.clone();
+            tampered[i] ^= 0x80;
+            assert!(!result.verify_slice(&tampered));
+        }
+
+        let mut longer = code.clone();
+        longer.push(0);
+        assert!(!result.verify_slice(&longer));
+        assert!(!result.verify_slice(&code[..31]));
+    }
+
+    // RFC 4231, test case 2
+    #[test]
+    fn test_verifying_mac() {
+        let expected = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+            .from_hex().unwrap();
+        let new_mac = || VerifyingMac::new(Hmac::new(Sha256::new(), b"Jefe"));
+
+        let mut mac = new_mac();
+        mac.input(b"what do ya want ");
+        mac.input(b"for nothing?");
+        assert!(mac.verify(&expected));
+
+        let mut wrong = expected.clone();
+        wrong[31] ^= 1;
+        let mut mac = new_mac();
+        mac.input(b"what do ya want for nothing?");
+        assert!(!mac.verify(&wrong));
+
+        // A truncated or empty code is rejected
+        let mut mac = new_mac();
+        mac.input(b"what do ya want for nothing?");
+        assert!(!mac.verify(&expected[..16]));
+        assert!(!mac.verify(&[]));
+
+        // A different message is rejected, and reset() allows the MAC to be reused
+        let mut mac = new_mac();
+        mac.input(b"what do ya want for something?");
+        assert!(!mac.verify(&expected));
+        mac.reset();
+        mac.input(b"what do ya want for nothing?");
+        assert!(mac.verify(&expected));
+    }
+
+    #[test]
+    fn test_output_bits() {
+        fn check<M: Mac>(mac: M, bits: usize) {
+            assert_eq!(mac.output_bits(), bits);
+            assert_eq!(mac.output_bits(), mac.output_bytes() * 8);
+        }
+
+        check(Hmac::new(Sha256::new(), b"key"), 256);
+        check(Hmac::new(Sha512::new(), b"key"), 512);
+        check(Cmac::new(AesSafe128Encryptor::new(&[0; 16])), 128);
+        check(Poly1305::new(&[1; 32]), 128);
+    }
+}
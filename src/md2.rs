@@ -0,0 +1,250 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * An implementation of the MD2 hash as specified by RFC 1319, https://tools.ietf.org/html/rfc1319.
+ *
+ * MD2 is broken and very slow. It is only provided to verify legacy data, such as old X.509
+ * certificates signed with md2WithRSAEncryption, and must not be used for anything new.
+ *
+ * First create a `Md2` object using the `Md2` constructor, then feed it input using the `input`
+ * or `input_str` methods, which may be called any number of times.
+ *
+ * After the entire input has been fed to the hash read the result using the `result` or
+ * `result_str` methods.
+ *
+ * The `Md2` object may be reused to create multiple hashes by calling the `reset` method.
+ */
+
+use cryptoutil::{FixedBuffer, FixedBufferN};
+use digest::Digest;
+
+// The permutation of 0..255 constructed from the digits of pi
+static S: [u8; 256] = [
+    41, 46, 67, 201, 162, 216, 124, 1, 61, 54, 84, 161, 236, 240, 6, 19,
+    98, 167, 5, 243, 192, 199, 115, 140, 152, 147, 43, 217, 188, 76, 130, 202,
+    30, 155, 87, 60, 253, 212, 224, 22, 103, 66, 111, 24, 138, 23, 229, 18,
+    190, 78, 196, 214, 218, 158, 222, 73, 160, 251, 245, 142, 187, 47, 238, 122,
+    169, 104, 121, 145, 21, 178, 7, 63, 148, 194, 16, 137, 11, 34, 95, 33,
+    128, 127, 93, 154, 90, 144, 50, 39, 53, 62, 204, 231, 191, 247, 151, 3,
+    255, 25, 48, 179, 72, 165, 181, 209, 215, 94, 146, 42, 172, 86, 170, 198,
+    79, 184, 56, 210, 150, 164, 125, 182, 118, 252, 107, 226, 156, 116, 4, 241,
+    69, 157, 112, 89, 100, 113, 135, 32, 134, 91, 207, 101, 230, 45, 168, 2,
+    27, 96, 37, 173, 174, 176, 185, 246, 28, 70, 97, 105, 52, 64, 126, 15,
+    85, 71, 163, 35, 221, 81, 175, 58, 195, 92, 249, 206, 186, 197, 234, 38,
+    44, 83, 13, 110, 133, 40, 132, 9, 211, 223, 205, 244, 65, 129, 77, 82,
+    106, 220, 55, 200, 108, 193, 171, 250, 36, 225, 123, 8, 12, 189, 177, 74,
+    120, 136, 149, 139, 227, 99, 232, 109, 233, 203, 213, 254, 59, 0, 29, 57,
+    242, 239, 183, 14, 102, 88, 208, 228, 166, 119, 114, 248, 235, 117, 75, 10,
+    49, 68, 80, 180, 143, 237, 31, 26, 219, 153, 141, 51, 159, 17, 131, 20
+];
+
+#[derive(Clone, Copy)]
+struct Md2State {
+    x: [u8; 48],
+    checksum: [u8; 16]
+}
+
+impl Md2State {
+    fn new() -> Md2State {
+        Md2State {
+            x: [0; 48],
+            checksum: [0; 16]
+        }
+    }
+
+    // The 18 round permutation of the 48 byte state, after the block has been loaded into it
+    fn compress(&mut self, block: &[u8]) {
+        for (j, &b) in block[..16].iter().enumerate() {
+            self.x[16 + j] = b;
+            self.x[32 + j] = b ^ self.x[j];
+        }
+
+        let mut t = 0u8;
+        for j in 0..18 {
+            for x in self.x.iter_mut() {
+                *x ^= S[t as usize];
+                t = *x;
+            }
+            t = t.wrapping_add(j as u8);
+        }
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        // The checksum carries over from the last byte of the previous block
+        let mut l = self.checksum[15];
+        for (c, &m) in self.checksum.iter_mut().zip(block.iter()) {
+            *c ^= S[(m ^ l) as usize];
+            l = *c;
+        }
+        self.compress(block);
+    }
+}
+
+/// Structure representing the state of an MD2 computation
+#[derive(Clone, Copy)]
+pub struct Md2 {
+    state: Md2State,
+    buffer: FixedBufferN<16>,
+    computed: bool
+}
+
+impl Md2 {
+    /// Construct a new `Md2` object
+    pub fn new() -> Md2 {
+        Md2 {
+            state: Md2State::new(),
+            buffer: FixedBufferN::new(),
+            computed: false
+        }
+    }
+}
+
+impl Default for Md2 {
+    fn default() -> Md2 {
+        Md2::new()
+    }
+}
+
+impl Digest for Md2 {
+    fn input(&mut self, input: &[u8]) {
+        assert!(!self.computed);
+        let self_state = &mut self.state;
+        self.buffer.input(input, |d: &[u8]| { self_state.process_block(d); });
+    }
+
+    fn reset(&mut self) {
+        self.state = Md2State::new();
+        self.buffer.reset();
+        self.computed = false;
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        if !self.computed {
+            // Pad with n bytes of value n, where n is from 1 to 16, then append the checksum
+            let n = self.buffer.remaining();
+            for b in self.buffer.next(n).iter_mut() {
+                *b = n as u8;
+            }
+            self.state.process_block(self.buffer.full_buffer());
+            let checksum = self.state.checksum;
+            self.state.compress(&checksum);
+            self.computed = true;
+        }
+
+        out[..16].copy_from_slice(&self.state.x[..16]);
+    }
+
+    fn output_bits(&self) -> usize { 128 }
+
+    fn block_size(&self) -> usize { 16 }
+}
+
+#[cfg(test)]
+mod tests {
+    use cryptoutil::test::test_digest_1million_random;
+    use digest::Digest;
+    use md2::Md2;
+
+    struct Test {
+        input: &'static str,
+        output_str: &'static str,
+    }
+
+    fn tests() -> Vec<Test> {
+        // The test suite from appendix A.5 of RFC 1319
+        vec![
+            Test {
+                input: "",
+                output_str: "8350e5a3e24c153df2275c9f80692773",
+            },
+            Test {
+                input: "a",
+                output_str: "32ec01ec4a6dac72c0ab96fb34c0b5d1",
+            },
+            Test {
+                input: "abc",
+                output_str: "da853b0d3f88d99b30283a69e6ded6bb",
+            },
+            Test {
+                input: "message digest",
+                output_str: "ab4f496bfb2a530b219ff33031fe06b0",
+            },
+            Test {
+                input: "abcdefghijklmnopqrstuvwxyz",
+                output_str: "4e8ddff3650292ab5a4108c3aa47940b",
+            },
+            Test {
+                input: "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                output_str: "da33def2a42df13975352846c30338cd",
+            },
+            Test {
+                input: "1234567890123456789012345678901234567890123456789012345678901234567890\
+                        1234567890",
+                output_str: "d5976f79d83d3a0dc9806c3c66f3efd8",
+            },
+        ]
+    }
+
+    #[test]
+    fn test() {
+        let mut sh = Md2::new();
+        for t in tests().iter() {
+            sh.input_str(t.input);
+            assert_eq!(sh.result_str(), t.output_str);
+            sh.reset();
+        }
+
+        // Test that it works when accepting the message in pieces
+        for t in tests().iter() {
+            let len = t.input.len();
+            let mut left = len;
+            while left > 0 {
+                let take = left.div_ceil(2);
+                sh.input_str(&t.input[len - left..take + len - left]);
+                left -= take;
+            }
+            assert_eq!(sh.result_str(), t.output_str);
+            sh.reset();
+        }
+    }
+
+    #[test]
+    fn test_1million_random_md2() {
+        let mut sh = Md2::new();
+        test_digest_1million_random(
+            &mut sh,
+            16,
+            "8c0a09ff1216ecaf95c8130953c62efd");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use digest::Digest;
+    use md2::Md2;
+
+    #[bench]
+    pub fn md2_10(bh: & mut Bencher) {
+        let mut sh = Md2::new();
+        let bytes = [1u8; 10];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn md2_1k(bh: & mut Bencher) {
+        let mut sh = Md2::new();
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
@@ -18,6 +18,7 @@ use serialize::base64;
 use serialize::base64::{FromBase64, ToBase64};
 
 use cryptoutil::{read_u32_be, write_u32_be};
+use derivedkey::DerivedKey;
 use hmac::Hmac;
 use mac::Mac;
 use sha2::Sha256;
@@ -109,6 +110,90 @@ pub fn pbkdf2<M: Mac>(mac: &mut M, salt: &[u8], c: u32, output: &mut [u8]) {
     }
 }
 
+/**
+ * Execute the PBKDF2 Key Derivation Function, returning a newly allocated DerivedKey of len bytes.
+ *
+ * # Arguments
+ * * mac - The Pseudo Random Function to use.
+ * * salt - The salt value to use.
+ * * c - The iteration count.
+ * * len - The length of the key to derive. This must not be zero.
+ *
+ */
+pub fn pbkdf2_derive<M: Mac>(mac: &mut M, salt: &[u8], c: u32, len: usize) -> DerivedKey {
+    DerivedKey::new(len, |output| pbkdf2(mac, salt, c, output))
+}
+
+/**
+ * A streaming form of PBKDF2 that produces the derived key one block at a time, for callers that
+ * need more output than they want to hold in memory at once. Each block is output_bytes() of the
+ * Mac long and the blocks are produced in the same order as they appear in the output of pbkdf2, so
+ * concatenating them yields the same key.
+ */
+pub struct Pbkdf2Blocks<'a, M: 'a + Mac> {
+    mac: &'a mut M,
+    salt: &'a [u8],
+    c: u32,
+    idx: u32,
+    scratch: Vec<u8>
+}
+
+impl <'a, M: Mac> Pbkdf2Blocks<'a, M> {
+    /**
+     * Create a new Pbkdf2Blocks.
+     *
+     * # Arguments
+     * * mac - The Pseudo Random Function to use.
+     * * salt - The salt value to use.
+     * * c - The iteration count.
+     *
+     */
+    pub fn new(mac: &'a mut M, salt: &'a [u8], c: u32) -> Pbkdf2Blocks<'a, M> {
+        assert!(c > 0);
+        let os = mac.output_bytes();
+        Pbkdf2Blocks {
+            mac,
+            salt,
+            c,
+            idx: 0,
+            scratch: vec![0; os]
+        }
+    }
+
+    /// The length of each block, which is the output size of the Mac.
+    pub fn block_size(&self) -> usize {
+        self.scratch.len()
+    }
+
+    /// The number of blocks produced so far.
+    pub fn blocks_produced(&self) -> u32 {
+        self.idx
+    }
+
+    /**
+     * Calculate the next block of the derived key into block, which must be exactly block_size()
+     * bytes long. Panics once the 2^32 - 1 blocks that PBKDF2 allows have been produced.
+     */
+    pub fn next_block(&mut self, block: &mut [u8]) {
+        assert!(block.len() == self.block_size());
+        self.idx = self.idx.checked_add(1).expect("PBKDF2 size limit exceeded.");
+        calculate_block(self.mac, self.salt, self.c, self.idx, &mut self.scratch, block);
+    }
+}
+
+impl <'a, M: Mac> Iterator for Pbkdf2Blocks<'a, M> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.idx == u32::MAX {
+            return None;
+        }
+        let mut block = vec![0; self.block_size()];
+        self.next_block(&mut block);
+        Some(block)
+    }
+}
+
 /**
  * pbkdf2_simple is a helper function that should be sufficient for the majority of cases where
  * an application needs to use PBKDF2 to hash a password for storage. The result is a String that
@@ -251,7 +336,7 @@ pub fn pbkdf2_check(password: &str, hashed_value: &str) -> Result<bool, &'static
 mod test {
     use std::iter::repeat;
 
-    use pbkdf2::{pbkdf2, pbkdf2_simple, pbkdf2_check};
+    use pbkdf2::{pbkdf2, pbkdf2_derive, pbkdf2_simple, pbkdf2_check, Pbkdf2Blocks};
     use hmac::Hmac;
     use sha1::Sha1;
 
@@ -325,6 +410,46 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_pbkdf2_derive() {
+        for t in tests().iter() {
+            let mut mac = Hmac::new(Sha1::new(), &t.password[..]);
+            let key = pbkdf2_derive(&mut mac, &t.salt[..], t.c, t.expected.len());
+            assert!(key.as_bytes() == &t.expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_blocks() {
+        for t in tests().iter() {
+            // Derive more than one block, ending part way through a block
+            let len = t.expected.len() * 3 + 7;
+            let mut mac = Hmac::new(Sha1::new(), &t.password[..]);
+            let mut expected = vec![0; len];
+            pbkdf2(&mut mac, &t.salt[..], t.c, &mut expected);
+
+            let mut mac = Hmac::new(Sha1::new(), &t.password[..]);
+            let mut blocks = Pbkdf2Blocks::new(&mut mac, &t.salt[..], t.c);
+            assert_eq!(blocks.block_size(), 20);
+            let mut result = Vec::new();
+            let mut block = [0u8; 20];
+            while result.len() < len {
+                blocks.next_block(&mut block);
+                result.extend_from_slice(&block);
+            }
+            assert_eq!(blocks.blocks_produced() as usize, len.div_ceil(20));
+            result.truncate(len);
+            assert!(result == expected);
+
+            let mut mac = Hmac::new(Sha1::new(), &t.password[..]);
+            let collected: Vec<u8> = Pbkdf2Blocks::new(&mut mac, &t.salt[..], t.c)
+                .flat_map(|b| b.into_iter())
+                .take(len)
+                .collect();
+            assert!(collected == expected);
+        }
+    }
+
     #[test]
     fn test_pbkdf2_simple() {
         let password = "password";
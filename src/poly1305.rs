@@ -6,44 +6,75 @@
 
 // This is a port of Andrew Moons poly1305-donna
 // https://github.com/floodyberry/poly1305-donna
+//
+// On 64-bit targets the arithmetic follows poly1305-donna-64, with three 44, 44 and 42 bit limbs
+// and 128-bit products. Other targets use poly1305-donna-32, with five 26 bit limbs and 64-bit
+// products. Both compute exactly the same tags.
 
 use std::cmp::min;
 
+use aessafe::AesSafe128Encryptor;
+use cryptoutil::copy_memory;
+#[cfg(any(test, not(target_pointer_width = "64")))]
 use cryptoutil::{read_u32_le, write_u32_le};
+#[cfg(target_pointer_width = "64")]
+use cryptoutil::{read_u64v_le, write_u64_le};
 use mac::{Mac, MacResult};
+use symmetriccipher::BlockEncryptor;
+use util::secure_memset;
 
+// The accumulator h and the clamped key r, in one of the limb representations
+trait Limbs: Copy {
+    // Clamp r from the first 16 bytes of the key and start with h = 0
+    fn new(key: &[u8]) -> Self;
+
+    // Set h = 0, keeping r
+    fn reset(&mut self);
+
+    // h = (h + m) * r (partially reduced) for the 16 byte block m. The 2^128 bit is added for a
+    // full block; a padded final block already has its 1 byte in place.
+    fn block(&mut self, m: &[u8], full: bool);
+
+    // Returns (h + pad) mod 2^128 after fully reducing h. The only branches depend on the length
+    // of the message, which is public; the choice between h and h - p is made with a mask, so
+    // nothing here branches on h.
+    fn finish(&self, pad: &[u8; 16]) -> [u8; 16];
+}
+
+#[cfg(target_pointer_width = "64")]
+type NativeLimbs = Limbs44;
+#[cfg(not(target_pointer_width = "64"))]
+type NativeLimbs = Limbs26;
+
+// poly1305-donna-32. On 64-bit targets this is only built to cross-check Limbs44 in the tests.
+#[cfg(any(test, not(target_pointer_width = "64")))]
 #[derive(Clone, Copy)]
-pub struct Poly1305 {
-    r         : [u32; 5],
-    h         : [u32; 5],
-    pad       : [u32; 4],
-    leftover  : usize,
-    buffer    : [u8; 16],
-    finalized : bool,
+struct Limbs26 {
+    r : [u32; 5],
+    h : [u32; 5],
 }
 
-impl Poly1305 {
-    pub fn new(key: &[u8]) -> Poly1305 {
-        assert!(key.len() == 32);
-        let mut poly = Poly1305{ r: [0u32; 5], h: [0u32; 5], pad: [0u32; 4], leftover: 0, buffer: [0u8; 16], finalized: false };
+#[cfg(any(test, not(target_pointer_width = "64")))]
+impl Limbs for Limbs26 {
+    fn new(key: &[u8]) -> Limbs26 {
+        let mut r = [0u32; 5];
 
         // r &= 0xffffffc0ffffffc0ffffffc0fffffff
-        poly.r[0] = (read_u32_le(&key[0..4])     ) & 0x3ffffff;
-        poly.r[1] = (read_u32_le(&key[3..7]) >> 2) & 0x3ffff03;
-        poly.r[2] = (read_u32_le(&key[6..10]) >> 4) & 0x3ffc0ff;
-        poly.r[3] = (read_u32_le(&key[9..13]) >> 6) & 0x3f03fff;
-        poly.r[4] = (read_u32_le(&key[12..16]) >> 8) & 0x00fffff;
+        r[0] = (read_u32_le(&key[0..4])     ) & 0x3ffffff;
+        r[1] = (read_u32_le(&key[3..7]) >> 2) & 0x3ffff03;
+        r[2] = (read_u32_le(&key[6..10]) >> 4) & 0x3ffc0ff;
+        r[3] = (read_u32_le(&key[9..13]) >> 6) & 0x3f03fff;
+        r[4] = (read_u32_le(&key[12..16]) >> 8) & 0x00fffff;
 
-        poly.pad[0] = read_u32_le(&key[16..20]);
-        poly.pad[1] = read_u32_le(&key[20..24]);
-        poly.pad[2] = read_u32_le(&key[24..28]);
-        poly.pad[3] = read_u32_le(&key[28..32]);
+        Limbs26 { r, h: [0u32; 5] }
+    }
 
-        poly
+    fn reset(&mut self) {
+        self.h = [0u32; 5];
     }
 
-    fn block(&mut self, m: &[u8]) {
-        let hibit : u32 = if self.finalized { 0 } else { 1 << 24 };
+    fn block(&mut self, m: &[u8], full: bool) {
+        let hibit : u32 = if full { 1 << 24 } else { 0 };
 
         let r0 = self.r[0];
         let r1 = self.r[1];
@@ -93,17 +124,7 @@ impl Poly1305 {
         self.h[4] = h4;
     }
 
-    fn finish(&mut self) {
-        if self.leftover > 0 {
-            self.buffer[self.leftover] = 1;
-            for i in self.leftover+1..16 {
-                self.buffer[i] = 0;
-            }
-            self.finalized = true;
-            let tmp = self.buffer;
-            self.block(&tmp);
-        }
-
+    fn finish(&self, pad: &[u8; 16]) -> [u8; 16] {
         // fully carry h
         let mut h0 = self.h[0];
         let mut h1 = self.h[1];
@@ -148,28 +169,215 @@ impl Poly1305 {
 
         // h = mac = (h + pad) % (2^128)
         let mut f : u64;
-        f = h0 as u64 + self.pad[0] as u64            ; h0 = f as u32;
-        f = h1 as u64 + self.pad[1] as u64 + (f >> 32); h1 = f as u32;
-        f = h2 as u64 + self.pad[2] as u64 + (f >> 32); h2 = f as u32;
-        f = h3 as u64 + self.pad[3] as u64 + (f >> 32); h3 = f as u32;
+        f = h0 as u64 + read_u32_le(&pad[0..4]) as u64            ; h0 = f as u32;
+        f = h1 as u64 + read_u32_le(&pad[4..8]) as u64 + (f >> 32); h1 = f as u32;
+        f = h2 as u64 + read_u32_le(&pad[8..12]) as u64 + (f >> 32); h2 = f as u32;
+        f = h3 as u64 + read_u32_le(&pad[12..16]) as u64 + (f >> 32); h3 = f as u32;
+
+        let mut mac = [0u8; 16];
+        write_u32_le(&mut mac[0..4], h0);
+        write_u32_le(&mut mac[4..8], h1);
+        write_u32_le(&mut mac[8..12], h2);
+        write_u32_le(&mut mac[12..16], h3);
+        mac
+    }
+}
+
+// poly1305-donna-64
+#[cfg(target_pointer_width = "64")]
+#[derive(Clone, Copy)]
+struct Limbs44 {
+    r : [u64; 3],
+    h : [u64; 3],
+}
+
+#[cfg(target_pointer_width = "64")]
+impl Limbs for Limbs44 {
+    fn new(key: &[u8]) -> Limbs44 {
+        let mut t = [0u64; 2];
+        read_u64v_le(&mut t, &key[0..16]);
+
+        // r &= 0xffffffc0ffffffc0ffffffc0fffffff
+        let r = [
+            t[0] & 0xffc0fffffff,
+            ((t[0] >> 44) | (t[1] << 20)) & 0xfffffc0ffff,
+            (t[1] >> 24) & 0x00ffffffc0f,
+        ];
+
+        Limbs44 { r, h: [0u64; 3] }
+    }
+
+    fn reset(&mut self) {
+        self.h = [0u64; 3];
+    }
+
+    fn block(&mut self, m: &[u8], full: bool) {
+        let hibit : u64 = if full { 1 << 40 } else { 0 };
+
+        let r0 = self.r[0];
+        let r1 = self.r[1];
+        let r2 = self.r[2];
+
+        // The limbs are 44, 44 and 42 bits, so the products which wrap past 2^130 pick up a factor
+        // of 4 along with the 5
+        let s1 = r1 * (5 << 2);
+        let s2 = r2 * (5 << 2);
+
+        let mut h0 = self.h[0];
+        let mut h1 = self.h[1];
+        let mut h2 = self.h[2];
+
+        // h += m
+        let mut t = [0u64; 2];
+        read_u64v_le(&mut t, &m[0..16]);
+        h0 += t[0] & 0xfffffffffff;
+        h1 += ((t[0] >> 44) | (t[1] << 20)) & 0xfffffffffff;
+        h2 += ((t[1] >> 24) & 0x3ffffffffff) | hibit;
+
+        // h *= r
+        let     d0 = (h0 as u128 * r0 as u128) + (h1 as u128 * s2 as u128) + (h2 as u128 * s1 as u128);
+        let mut d1 = (h0 as u128 * r1 as u128) + (h1 as u128 * r0 as u128) + (h2 as u128 * s2 as u128);
+        let mut d2 = (h0 as u128 * r2 as u128) + (h1 as u128 * r1 as u128) + (h2 as u128 * r0 as u128);
+
+        // (partial) h %= p
+        let mut c : u64;
+                         c = (d0 >> 44) as u64; h0 = d0 as u64 & 0xfffffffffff;
+        d1 += c as u128; c = (d1 >> 44) as u64; h1 = d1 as u64 & 0xfffffffffff;
+        d2 += c as u128; c = (d2 >> 42) as u64; h2 = d2 as u64 & 0x3ffffffffff;
+        h0 += c * 5;     c = h0 >> 44; h0 &= 0xfffffffffff;
+        h1 += c;
 
         self.h[0] = h0;
         self.h[1] = h1;
         self.h[2] = h2;
-        self.h[3] = h3;
+    }
+
+    fn finish(&self, pad: &[u8; 16]) -> [u8; 16] {
+        // fully carry h
+        let mut h0 = self.h[0];
+        let mut h1 = self.h[1];
+        let mut h2 = self.h[2];
+
+        let mut c : u64;
+                     c = h1 >> 44; h1 &= 0xfffffffffff;
+        h2 +=     c; c = h2 >> 42; h2 &= 0x3ffffffffff;
+        h0 += c * 5; c = h0 >> 44; h0 &= 0xfffffffffff;
+        h1 +=     c; c = h1 >> 44; h1 &= 0xfffffffffff;
+        h2 +=     c; c = h2 >> 42; h2 &= 0x3ffffffffff;
+        h0 += c * 5; c = h0 >> 44; h0 &= 0xfffffffffff;
+        h1 +=     c;
+
+        // compute h + -p
+        let mut g0 = h0.wrapping_add(5); c = g0 >> 44; g0 &= 0xfffffffffff;
+        let mut g1 = h1.wrapping_add(c); c = g1 >> 44; g1 &= 0xfffffffffff;
+        let mut g2 = h2.wrapping_add(c).wrapping_sub(1 << 42);
+
+        // select h if h < p, or h + -p if h >= p
+        let mut mask = (g2 >> (64 - 1)).wrapping_sub(1);
+        g0 &= mask;
+        g1 &= mask;
+        g2 &= mask;
+        mask = !mask;
+        h0 = (h0 & mask) | g0;
+        h1 = (h1 & mask) | g1;
+        h2 = (h2 & mask) | g2;
+
+        // h = (h + pad)
+        let mut t = [0u64; 2];
+        read_u64v_le(&mut t, pad);
+        h0 += t[0] & 0xfffffffffff;
+        c = h0 >> 44; h0 &= 0xfffffffffff;
+        h1 += (((t[0] >> 44) | (t[1] << 20)) & 0xfffffffffff) + c;
+        c = h1 >> 44; h1 &= 0xfffffffffff;
+        h2 += ((t[1] >> 24) & 0x3ffffffffff) + c;
+        h2 &= 0x3ffffffffff;
+
+        // mac = h % (2^128)
+        h0 |= h1 << 44;
+        h1 = (h1 >> 20) | (h2 << 24);
+
+        let mut mac = [0u8; 16];
+        write_u64_le(&mut mac[0..8], h0);
+        write_u64_le(&mut mac[8..16], h1);
+        mac
+    }
+}
+
+/**
+ * Poly1305 as specified by RFC 8439. The key must only be used to authenticate a single message.
+ *
+ * Once the tag has been obtained with result() or raw_result(), input() panics until reset() is
+ * called; can_input() reports whether more input is accepted. reset() is the only way to start
+ * another message, and doesn't make it safe to authenticate a different message with the same key.
+ */
+#[derive(Clone, Copy)]
+pub struct Poly1305 {
+    limbs     : NativeLimbs,
+    pad       : [u8; 16],
+    tag       : [u8; 16],
+    leftover  : usize,
+    buffer    : [u8; 16],
+    finalized : bool,
+}
+
+impl Poly1305 {
+    pub fn new(key: &[u8]) -> Poly1305 {
+        assert!(key.len() == 32);
+        let mut pad = [0u8; 16];
+        copy_memory(&key[16..32], &mut pad);
+        Poly1305 {
+            limbs: NativeLimbs::new(&key[0..16]),
+            pad,
+            tag: [0u8; 16],
+            leftover: 0,
+            buffer: [0u8; 16],
+            finalized: false
+        }
+    }
+
+    /// Create a new Poly1305 instance, rejecting keys which are almost certainly the result of a
+    /// programming error. Currently, this means a key whose pad (the last 16 bytes) is all zeros.
+    /// Poly1305::new() accepts such keys.
+    pub fn new_checked(key: &[u8]) -> Result<Poly1305, &'static str> {
+        if key.len() != 32 {
+            return Err("Poly1305 key must be 32 bytes");
+        }
+        if key[16..32].iter().fold(0, |acc, &b| acc | b) == 0 {
+            return Err("Poly1305 key has an all-zero pad");
+        }
+        Ok(Poly1305::new(key))
+    }
+
+    /// Whether input() may be called, which is true until the tag has been computed and again
+    /// after reset().
+    pub fn can_input(&self) -> bool {
+        !self.finalized
+    }
+
+    // Computes the tag. This must run exactly once per message: the padded final block is
+    // absorbed here, and the tag is kept for later calls to raw_result().
+    fn finish(&mut self) {
+        if self.leftover > 0 {
+            self.buffer[self.leftover] = 1;
+            for i in self.leftover+1..16 {
+                self.buffer[i] = 0;
+            }
+            let tmp = self.buffer;
+            self.limbs.block(&tmp, false);
+        }
+        self.finalized = true;
+        self.tag = self.limbs.finish(&self.pad);
     }
 }
 
 impl Mac for Poly1305 {
     fn input(&mut self, data: &[u8]) {
-        assert!(!self.finalized);
+        assert!(!self.finalized, "Poly1305 input after the tag was computed; call reset() first");
         let mut m = data;
 
         if self.leftover > 0 {
             let want = min(16 - self.leftover, m.len());
-            for i in 0..want {
-                self.buffer[self.leftover+i] = m[i];
-            }
+            copy_memory(&m[..want], &mut self.buffer[self.leftover..]);
             m = &m[want..];
             self.leftover += want;
 
@@ -177,27 +385,30 @@ impl Mac for Poly1305 {
                 return;
             }
 
-            // self.block(self.buffer[..]);
             let tmp = self.buffer;
-            self.block(&tmp);
+            self.limbs.block(&tmp, true);
 
             self.leftover = 0;
         }
 
+        // Full blocks are processed directly from the input, only a partial block at the end is
+        // buffered
         while m.len() >= 16 {
-            self.block(&m[0..16]);
+            self.limbs.block(&m[0..16], true);
             m = &m[16..];
         }
 
-        for i in 0..m.len() {
-            self.buffer[i] = m[i];
-        }
+        copy_memory(m, &mut self.buffer);
         self.leftover = m.len();
     }
 
     fn reset(&mut self) {
-        self.h = [0u32; 5];
+        // The stale buffer bytes would never be read, since they are overwritten before leftover
+        // counts them again, but they are part of the previous message so clear them anyway
+        self.limbs.reset();
+        self.buffer = [0u8; 16];
         self.leftover = 0;
+        self.tag = [0u8; 16];
         self.finalized = false;
     }
 
@@ -212,21 +423,70 @@ impl Mac for Poly1305 {
         if !self.finalized{
             self.finish();
         }
-        write_u32_le(&mut output[0..4], self.h[0]);
-        write_u32_le(&mut output[4..8], self.h[1]);
-        write_u32_le(&mut output[8..12], self.h[2]);
-        write_u32_le(&mut output[12..16], self.h[3]);
+        copy_memory(&self.tag, &mut output[0..16]);
     }
 
     fn output_bytes(&self) -> usize { 16 }
 }
 
+/**
+ * Poly1305-AES, the original construction from Bernstein's "The Poly1305-AES message-authentication
+ * code", where the pad added to the polynomial is the encryption of a nonce under AES-128.
+ *
+ * A nonce must never be used to authenticate more than one message under the same key. Since the
+ * pad is derived from the nonce when the Poly1305Aes is created, reset() does not make it safe to
+ * authenticate a different message: create a new Poly1305Aes with a new nonce instead.
+ */
+#[derive(Clone, Copy)]
+pub struct Poly1305Aes {
+    poly: Poly1305
+}
+
+impl Poly1305Aes {
+    /// Create a Poly1305-AES instance from a 32 byte key, which is the 16 byte AES key k followed by
+    /// the 16 byte r, and a 16 byte nonce.
+    pub fn new(key: &[u8], nonce: &[u8]) -> Poly1305Aes {
+        assert!(key.len() == 32);
+        assert!(nonce.len() == 16);
+
+        let mut poly_key = [0u8; 32];
+        copy_memory(&key[16..32], &mut poly_key[0..16]);
+        AesSafe128Encryptor::new(&key[0..16]).encrypt_block(nonce, &mut poly_key[16..32]);
+        let poly = Poly1305::new(&poly_key);
+        secure_memset(&mut poly_key, 0);
+
+        Poly1305Aes {
+            poly
+        }
+    }
+
+    /// Whether input() may be called; see Poly1305::can_input().
+    pub fn can_input(&self) -> bool {
+        self.poly.can_input()
+    }
+}
+
+impl Mac for Poly1305Aes {
+    fn input(&mut self, data: &[u8]) { self.poly.input(data) }
+    fn reset(&mut self) { self.poly.reset() }
+    fn result(&mut self) -> MacResult { self.poly.result() }
+    fn raw_result(&mut self, output: &mut [u8]) { self.poly.raw_result(output) }
+    fn output_bytes(&self) -> usize { self.poly.output_bytes() }
+}
+
 #[cfg(test)]
 mod test {
+    use std::cmp::min;
     use std::iter::repeat;
 
-    use poly1305::Poly1305;
-    use mac::Mac;
+    use rand::{IsaacRng, Rng};
+    use rand::distributions::{IndependentSample, Range};
+
+    use poly1305::{Limbs, Limbs26, Poly1305, Poly1305Aes};
+    #[cfg(target_pointer_width = "64")]
+    use poly1305::Limbs44;
+    use mac::{Mac, MacResult};
+    use serialize::hex::FromHex;
 
     fn poly1305(key: &[u8], msg: &[u8], mac: &mut [u8]) {
         let mut poly = Poly1305::new(key);
@@ -234,6 +494,52 @@ mod test {
         poly.raw_result(mac);
     }
 
+    // Computes a tag with the given limb representation directly, bypassing the buffering in
+    // Poly1305
+    fn limbs_tag<L: Limbs>(key: &[u8], msg: &[u8]) -> [u8; 16] {
+        let mut limbs = L::new(&key[0..16]);
+        for chunk in msg.chunks(16) {
+            if chunk.len() == 16 {
+                limbs.block(chunk, true);
+            } else {
+                let mut block = [0u8; 16];
+                block[..chunk.len()].copy_from_slice(chunk);
+                block[chunk.len()] = 1;
+                limbs.block(&block, false);
+            }
+        }
+        let mut pad = [0u8; 16];
+        pad.copy_from_slice(&key[16..32]);
+        limbs.finish(&pad)
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_limbs_cross_check() {
+        let mut rng = IsaacRng::new_unseeded();
+        let len_range = Range::new(0, 300);
+        let mut key = [0u8; 32];
+        for _ in 0..1000 {
+            rng.fill_bytes(&mut key);
+            let mut msg = vec![0u8; len_range.ind_sample(&mut rng)];
+            rng.fill_bytes(&mut msg);
+            let expected = limbs_tag::<Limbs26>(&key, &msg);
+            assert_eq!(limbs_tag::<Limbs44>(&key, &msg), expected);
+
+            let mut mac = [0u8; 16];
+            poly1305(&key, &msg, &mut mac);
+            assert_eq!(mac, expected);
+        }
+
+        // All ones keys and messages keep h close to 2^130 - 5 through every carry
+        let key = [0xffu8; 32];
+        let msg = [0xffu8; 100];
+        for len in 0..msg.len() {
+            assert_eq!(limbs_tag::<Limbs44>(&key, &msg[..len]),
+                       limbs_tag::<Limbs26>(&key, &msg[..len]));
+        }
+    }
+
     #[test]
     fn test_nacl_vector() {
         let key = [
@@ -288,6 +594,27 @@ mod test {
         assert_eq!(&mac[..], &expected[..]);
     }
 
+    #[test]
+    fn test_new_checked() {
+        let mut key = [0x55u8; 32];
+        for b in key[16..].iter_mut() {
+            *b = 0;
+        }
+        assert!(Poly1305::new_checked(&key).is_err());
+        assert!(Poly1305::new_checked(&key[..31]).is_err());
+
+        // A single non-zero byte in the pad is enough
+        key[31] = 1;
+        let msg = b"Cryptographic Forum Research Group";
+        let mut expected = [0u8; 16];
+        poly1305(&key, msg, &mut expected);
+        let mut poly = Poly1305::new_checked(&key).unwrap();
+        poly.input(msg);
+        let mut mac = [0u8; 16];
+        poly.raw_result(&mut mac);
+        assert!(mac == expected);
+    }
+
     #[test]
     fn donna_self_test() {
         let wrap_key = [
@@ -335,6 +662,29 @@ mod test {
         assert_eq!(&mac[..], &total_mac[..]);
     }
 
+    #[test]
+    fn test_random_chunks() {
+        let key: Vec<u8> = (0..32).map(|i| (i * 13 + 1) as u8).collect();
+        let msg: Vec<u8> = (0..4099).map(|i| (i * 7) as u8).collect();
+        let mut expected = [0u8; 16];
+        poly1305(&key, &msg, &mut expected);
+
+        let mut rng = IsaacRng::new_unseeded();
+        let range = Range::new(0, 50);
+        for _ in 0..100 {
+            let mut poly = Poly1305::new(&key);
+            let mut m = &msg[..];
+            while m.len() > 0 {
+                let size = min(range.ind_sample(&mut rng), m.len());
+                poly.input(&m[..size]);
+                m = &m[size..];
+            }
+            let mut mac = [0u8; 16];
+            poly.raw_result(&mut mac);
+            assert_eq!(mac, expected);
+        }
+    }
+
     #[test]
     fn test_tls_vectors() {
         // from http://tools.ietf.org/html/draft-agl-tls-chacha20poly1305-04
@@ -356,13 +706,132 @@ mod test {
         poly1305(key, msg, &mut mac);
         assert_eq!(&mac[..], &expected[..]);
     }
+
+    #[test]
+    fn test_rfc8439_vectors() {
+        // The example from section 2.5.2 of RFC 8439, followed by the vectors from appendix A.3
+        // that exercise the final reduction, where h is close to or above 2^130 - 5
+        // (key, message, tag)
+        let vectors = [
+            ("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b",
+             "43727970746f6772617068696320466f72756d2052657365617263682047726f7570",
+             "a8061dc1305136c6c22b8baf0c0127a9"),
+            ("02000000000000000000000000000000ffffffffffffffffffffffffffffffff",
+             "02000000000000000000000000000000",
+             "03000000000000000000000000000000"),
+            ("0100000000000000000000000000000000000000000000000000000000000000",
+             "fffffffffffffffffffffffffffffffff0ffffffffffffffffffffffffffffff\
+              11000000000000000000000000000000",
+             "05000000000000000000000000000000"),
+            ("0100000000000000000000000000000000000000000000000000000000000000",
+             "fffffffffffffffffffffffffffffffffbfefefefefefefefefefefefefefefe\
+              01010101010101010101010101010101",
+             "00000000000000000000000000000000"),
+            ("0200000000000000000000000000000000000000000000000000000000000000",
+             "fdffffffffffffffffffffffffffffff",
+             "faffffffffffffffffffffffffffffff"),
+            ("0100000000000000040000000000000000000000000000000000000000000000",
+             "e33594d7505e43b900000000000000003394d7505e4379cd0100000000000000\
+              0000000000000000000000000000000001000000000000000000000000000000",
+             "14000000000000005500000000000000"),
+            ("0100000000000000040000000000000000000000000000000000000000000000",
+             "e33594d7505e43b900000000000000003394d7505e4379cd0100000000000000\
+              00000000000000000000000000000000",
+             "13000000000000000000000000000000"),
+        ];
+        for &(key, msg, tag) in vectors.iter() {
+            let key = key.from_hex().unwrap();
+            let msg = msg.from_hex().unwrap();
+            let tag = tag.from_hex().unwrap();
+            let mut poly = Poly1305::new(&key);
+            poly.input(&msg);
+            let mut mac = [0u8; 16];
+            poly.raw_result(&mut mac);
+            assert_eq!(&mac[..], &tag[..]);
+            assert_eq!(&limbs_tag::<Limbs26>(&key, &msg)[..], &tag[..]);
+
+            // Asking for the tag again must not finish the computation a second time, whether
+            // or not the message ended with a partial block
+            poly.raw_result(&mut mac);
+            assert_eq!(&mac[..], &tag[..]);
+            assert!(poly.result() == MacResult::new(&tag));
+        }
+    }
+
+    #[test]
+    fn test_reset_reuse() {
+        let key: Vec<u8> = (0..32).map(|i| (i * 29 + 3) as u8).collect();
+        let first: Vec<u8> = (0..45).map(|i| (i * 7 + 1) as u8).collect();
+        let second = b"Cryptographic Forum Research Group";
+
+        let mut poly = Poly1305::new(&key);
+        assert!(poly.can_input());
+        poly.input(&first);
+        let mut mac = [0u8; 16];
+        poly.raw_result(&mut mac);
+        assert!(!poly.can_input());
+
+        // The first message left a partial block behind, which must not leak into the next one
+        for &msg in [&second[..], &second[..3], &b""[..], &first[..32]].iter() {
+            poly.reset();
+            assert!(poly.can_input());
+            poly.input(msg);
+            poly.raw_result(&mut mac);
+
+            let mut expected = [0u8; 16];
+            poly1305(&key, msg, &mut expected);
+            assert_eq!(mac, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "call reset() first")]
+    fn test_input_after_result() {
+        let mut poly = Poly1305::new(&[1; 32]);
+        poly.input(b"message");
+        poly.result();
+        poly.input(b"more");
+    }
+
+    #[test]
+    fn test_poly1305_aes() {
+        // The examples from appendix B of "The Poly1305-AES message-authentication code"
+        // (message, k, r, nonce, tag)
+        let vectors = [
+            ("f3f6",
+             "ec074c835580741701425b623235add6",
+             "851fc40c3467ac0be05cc20404f3f700",
+             "fb447350c4e868c52ac3275cf9d4327e",
+             "f4c633c3044fc145f84f335cb81953de"),
+            ("",
+             "75deaa25c09f208e1dc4ce6b5cad3fbf",
+             "a0f3080000f46400d0c7e9076c834403",
+             "61ee09218d29b0aaed7e154a2c5509cc",
+             "dd3fab2251f11ac759f0887129cc2ee7"),
+            ("663cea190ffb83d89593f3f476b6bc24d7e679107ea26adb8caf6652d0656136",
+             "6acb5f61a7176dd320c5c1eb2edcdc74",
+             "48443d0bb0d21109c89a100b5ce2c208",
+             "ae212a55399729595dea458bc621ff0e",
+             "0ee1c16bb73f0f4fd19881753c01cdbe")];
+        for &(msg, k, r, nonce, tag) in vectors.iter() {
+            let mut key = k.from_hex().unwrap();
+            key.extend(r.from_hex().unwrap());
+            let mut mac = Poly1305Aes::new(&key, &nonce.from_hex().unwrap());
+            mac.input(&msg.from_hex().unwrap());
+            let mut out = [0u8; 16];
+            mac.raw_result(&mut out);
+            assert_eq!(out.to_vec(), tag.from_hex().unwrap());
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
 mod bench {
     use test::Bencher;
     use mac::Mac;
-    use poly1305::Poly1305;
+    use poly1305::{Limbs, Limbs26, Poly1305};
+    #[cfg(target_pointer_width = "64")]
+    use poly1305::Limbs44;
 
     #[bench]
     pub fn poly1305_10(bh: & mut Bencher) {
@@ -402,4 +871,29 @@ mod bench {
         });
         bh.bytes = bytes.len() as u64;
     }
+
+    fn bench_limbs<L: Limbs>(bh: & mut Bencher) {
+        let key   = [0u8; 16];
+        let pad   = [0u8; 16];
+        let bytes = [1u8; 65536];
+        bh.iter( || {
+            let mut limbs = L::new(&key);
+            for block in bytes.chunks(16) {
+                limbs.block(block, true);
+            }
+            limbs.finish(&pad)
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn poly1305_limbs26_64k(bh: & mut Bencher) {
+        bench_limbs::<Limbs26>(bh);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[bench]
+    pub fn poly1305_limbs44_64k(bh: & mut Bencher) {
+        bench_limbs::<Limbs44>(bh);
+    }
 }
@@ -11,7 +11,7 @@
 
 use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
 use symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
-use cryptoutil::symm_enc_or_dec;
+use cryptoutil::{debug_assert_no_overlap, symm_enc_or_dec};
 
 #[derive(Copy)]
 pub struct Rc4 {
@@ -36,6 +36,17 @@ impl Rc4 {
         }
         rc4
     }
+
+    /// Create an RC4-drop[n] cipher, which discards the first drop bytes of keystream to avoid the
+    /// biases in the early output of RC4. RFC 4345 specifies a drop of 1536 bytes for SSH.
+    pub fn new_drop(key: &[u8], drop: usize) -> Rc4 {
+        let mut rc4 = Rc4::new(key);
+        for _ in 0..drop {
+            rc4.next();
+        }
+        rc4
+    }
+
     fn next(&mut self) -> u8 {
         self.i = self.i.wrapping_add(1);
         self.j = self.j.wrapping_add(self.state[self.i as usize]);
@@ -48,6 +59,7 @@ impl Rc4 {
 impl SynchronousStreamCipher for Rc4 {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
         for (x, y) in input.iter().zip(output.iter_mut()) {
             *y = *x ^ self.next();
         }
@@ -102,6 +114,17 @@ mod test {
         ]
     }
 
+    #[test]
+    fn process_in_place() {
+        for t in tests().iter() {
+            let mut data = t.input.as_bytes().to_vec();
+            Rc4::new(t.key.as_bytes()).process_in_place(&mut data);
+            assert!(data == t.output);
+            Rc4::new(t.key.as_bytes()).process_in_place(&mut data);
+            assert!(&data[..] == t.input.as_bytes());
+        }
+    }
+
     #[test]
     fn wikipedia_tests() {
         let tests = tests();
@@ -112,6 +135,21 @@ mod test {
             assert!(result == t.output);
         }
     }
+
+    #[test]
+    fn drop_skips_keystream() {
+        let zeros = [0u8; 512];
+        let mut plain = [0u8; 512];
+        Rc4::new(b"Key").process(&zeros, &mut plain);
+
+        let mut dropped = [0u8; 256];
+        Rc4::new_drop(b"Key", 256).process(&zeros[..256], &mut dropped);
+        assert!(dropped[..] == plain[256..]);
+
+        let mut none = [0u8; 16];
+        Rc4::new_drop(b"Key", 0).process(&zeros[..16], &mut none);
+        assert!(none[..] == plain[..16]);
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
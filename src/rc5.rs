@@ -0,0 +1,209 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * An implementation of the RC5 block cipher as described in "The RC5 Encryption Algorithm" by
+ * Ronald L. Rivest.
+ *
+ * RC5 is parameterized by its word size, number of rounds, and key length. This module implements
+ * the 32-bit word size (a 64-bit block), which is by far the most widely deployed variant. The
+ * number of rounds defaults to 12, giving the common RC5-32/12/b parameterization, but may be
+ * chosen at construction time.
+ */
+
+use std::cmp;
+
+use cryptoutil::{read_u32_le, write_u32_le};
+use symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+// Magic constants for w = 32: P = Odd((e - 2) * 2^32) and Q = Odd((phi - 1) * 2^32)
+const P32: u32 = 0xb7e15163;
+const Q32: u32 = 0x9e3779b9;
+
+/// The number of rounds used by Rc5::new()
+pub const DEFAULT_ROUNDS: usize = 12;
+
+#[derive(Clone)]
+pub struct Rc5 {
+    rounds: usize,
+    s: Vec<u32>
+}
+
+impl Rc5 {
+    /// Create an RC5-32/12/b cipher for a key of b bytes, with b at most 255.
+    pub fn new(key: &[u8]) -> Rc5 {
+        Rc5::new_with_rounds(key, DEFAULT_ROUNDS)
+    }
+
+    /// Create an RC5-32/r/b cipher using the given number of rounds r, which may be at most 255.
+    pub fn new_with_rounds(key: &[u8], rounds: usize) -> Rc5 {
+        assert!(key.len() <= 255);
+        assert!(rounds <= 255);
+
+        // Load the key into an array of words, little-endian, padding the last word with zeros
+        let c = if key.is_empty() { 1 } else { key.len().div_ceil(4) };
+        let mut l = vec![0u32; c];
+        for (i, &b) in key.iter().enumerate().rev() {
+            l[i / 4] = (l[i / 4] << 8).wrapping_add(b as u32);
+        }
+
+        let t = 2 * rounds + 2;
+        let mut s = vec![0u32; t];
+        s[0] = P32;
+        for i in 1..t {
+            s[i] = s[i - 1].wrapping_add(Q32);
+        }
+
+        // Mix the secret key into the expanded key table
+        let mut a = 0u32;
+        let mut b = 0u32;
+        let mut i = 0;
+        let mut j = 0;
+        for _ in 0..3 * cmp::max(t, c) {
+            s[i] = s[i].wrapping_add(a).wrapping_add(b).rotate_left(3);
+            a = s[i];
+            l[j] = l[j].wrapping_add(a).wrapping_add(b).rotate_left(a.wrapping_add(b));
+            b = l[j];
+            i = (i + 1) % t;
+            j = (j + 1) % c;
+        }
+
+        Rc5 {
+            rounds,
+            s
+        }
+    }
+
+    fn encrypt(&self, mut a: u32, mut b: u32) -> (u32, u32) {
+        a = a.wrapping_add(self.s[0]);
+        b = b.wrapping_add(self.s[1]);
+        for i in 1..self.rounds + 1 {
+            a = (a ^ b).rotate_left(b).wrapping_add(self.s[2 * i]);
+            b = (b ^ a).rotate_left(a).wrapping_add(self.s[2 * i + 1]);
+        }
+        (a, b)
+    }
+
+    fn decrypt(&self, mut a: u32, mut b: u32) -> (u32, u32) {
+        for i in (1..self.rounds + 1).rev() {
+            b = b.wrapping_sub(self.s[2 * i + 1]).rotate_right(a) ^ a;
+            a = a.wrapping_sub(self.s[2 * i]).rotate_right(b) ^ b;
+        }
+        b = b.wrapping_sub(self.s[1]);
+        a = a.wrapping_sub(self.s[0]);
+        (a, b)
+    }
+}
+
+impl BlockEncryptor for Rc5 {
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 8);
+        assert!(output.len() == 8);
+        let (a, b) = self.encrypt(read_u32_le(&input[0..4]), read_u32_le(&input[4..8]));
+        write_u32_le(&mut output[0..4], a);
+        write_u32_le(&mut output[4..8], b);
+    }
+}
+
+impl BlockDecryptor for Rc5 {
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 8);
+        assert!(output.len() == 8);
+        let (a, b) = self.decrypt(read_u32_le(&input[0..4]), read_u32_le(&input[4..8]));
+        write_u32_le(&mut output[0..4], a);
+        write_u32_le(&mut output[4..8], b);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rc5::Rc5;
+    use symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    struct Test {
+        key: Vec<u8>,
+        plaintext: Vec<u8>,
+        ciphertext: Vec<u8>
+    }
+
+    // RC5-32/12/16 test vectors from "The RC5 Encryption Algorithm" by Ronald L. Rivest
+    fn rivest_test_vectors() -> Vec<Test> {
+        vec![
+            Test {
+                key: vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                          0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                plaintext: vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                ciphertext: vec![0x21, 0xa5, 0xdb, 0xee, 0x15, 0x4b, 0x8f, 0x6d]
+            },
+            Test {
+                key: vec![0x91, 0x5f, 0x46, 0x19, 0xbe, 0x41, 0xb2, 0x51,
+                          0x63, 0x55, 0xa5, 0x01, 0x10, 0xa9, 0xce, 0x91],
+                plaintext: vec![0x21, 0xa5, 0xdb, 0xee, 0x15, 0x4b, 0x8f, 0x6d],
+                ciphertext: vec![0xf7, 0xc0, 0x13, 0xac, 0x5b, 0x2b, 0x89, 0x52]
+            },
+            Test {
+                key: vec![0x78, 0x33, 0x48, 0xe7, 0x5a, 0xeb, 0x0f, 0x2f,
+                          0xd7, 0xb1, 0x69, 0xbb, 0x8d, 0xc1, 0x67, 0x87],
+                plaintext: vec![0xf7, 0xc0, 0x13, 0xac, 0x5b, 0x2b, 0x89, 0x52],
+                ciphertext: vec![0x2f, 0x42, 0xb3, 0xb7, 0x03, 0x69, 0xfc, 0x92]
+            },
+            Test {
+                key: vec![0xdc, 0x49, 0xdb, 0x13, 0x75, 0xa5, 0x58, 0x4f,
+                          0x64, 0x85, 0xb4, 0x13, 0xb5, 0xf1, 0x2b, 0xaf],
+                plaintext: vec![0x2f, 0x42, 0xb3, 0xb7, 0x03, 0x69, 0xfc, 0x92],
+                ciphertext: vec![0x65, 0xc1, 0x78, 0xb2, 0x84, 0xd1, 0x97, 0xcc]
+            },
+            Test {
+                key: vec![0x52, 0x69, 0xf1, 0x49, 0xd4, 0x1b, 0xa0, 0x15,
+                          0x24, 0x97, 0x57, 0x4d, 0x7f, 0x15, 0x31, 0x25],
+                plaintext: vec![0x65, 0xc1, 0x78, 0xb2, 0x84, 0xd1, 0x97, 0xcc],
+                ciphertext: vec![0xeb, 0x44, 0xe4, 0x15, 0xda, 0x31, 0x98, 0x24]
+            }
+        ]
+    }
+
+    #[test]
+    fn encrypt_rivest_test_vectors() {
+        let mut output = [0u8; 8];
+        for test in rivest_test_vectors().iter() {
+            let state = Rc5::new(&test.key[..]);
+            state.encrypt_block(&test.plaintext[..], &mut output[..]);
+            assert!(test.ciphertext[..] == output[..]);
+        }
+    }
+
+    #[test]
+    fn decrypt_rivest_test_vectors() {
+        let mut output = [0u8; 8];
+        for test in rivest_test_vectors().iter() {
+            let state = Rc5::new(&test.key[..]);
+            state.decrypt_block(&test.ciphertext[..], &mut output[..]);
+            assert!(test.plaintext[..] == output[..]);
+        }
+    }
+
+    #[test]
+    fn round_trip_custom_rounds() {
+        let key = [0x42u8; 10];
+        let plaintext = [0x01u8, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+        let mut ciphertext = [0u8; 8];
+        let mut output = [0u8; 8];
+        for &rounds in [0usize, 1, 12, 16, 20, 255].iter() {
+            let state = Rc5::new_with_rounds(&key, rounds);
+            state.encrypt_block(&plaintext, &mut ciphertext);
+            state.decrypt_block(&ciphertext, &mut output);
+            assert!(plaintext == output);
+        }
+    }
+}
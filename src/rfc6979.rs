@@ -0,0 +1,146 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the deterministic generation of the per-signature value k for DSA and
+ * ECDSA as specified by RFC 6979, https://tools.ietf.org/html/rfc6979. k is derived from the
+ * private key and the hash of the message with HMAC_DRBG, so signing does not depend on the quality
+ * of a random number generator.
+ *
+ * All integers are big-endian byte strings.
+ */
+
+use digest::Digest;
+use hmac_drbg::HmacDrbg;
+
+// Compute a - b in place, returning the final borrow (1 if a < b). a and b must be the same length.
+fn sub_assign(a: &mut [u8], b: &[u8]) -> u8 {
+    let mut borrow = 0i16;
+    for (x, &y) in a.iter_mut().zip(b.iter()).rev() {
+        let t = *x as i16 - y as i16 - borrow;
+        *x = t as u8;
+        borrow = (t >> 8) & 1;
+    }
+    borrow as u8
+}
+
+// The bits2int function from RFC 6979, section 2.3.2: interpret the leftmost qlen bits of data as
+// an integer, returned as rlen bytes.
+fn bits2int(data: &[u8], qlen: usize, rlen: usize) -> Vec<u8> {
+    let mut out = vec![0; rlen];
+    if data.len() <= rlen {
+        out[rlen - data.len()..].copy_from_slice(data);
+    } else {
+        out.copy_from_slice(&data[..rlen]);
+    }
+    if data.len() * 8 > qlen {
+        let shift = rlen * 8 - qlen;
+        if shift > 0 {
+            for i in (0..rlen).rev() {
+                let prev = if i > 0 { out[i - 1] } else { 0 };
+                out[i] = (out[i] >> shift) | (prev << (8 - shift));
+            }
+        }
+    }
+    out
+}
+
+/**
+ * Deterministically generate the value k, in the range [1, q - 1], for a DSA or ECDSA signature.
+ *
+ * # Arguments
+ * * digest - The Digest to use with HMAC_DRBG. This should be the same hash function that was used
+ *   to compute message_hash.
+ * * order - The order, q, of the group.
+ * * private_key - The private key, x, which must be less than q.
+ * * message_hash - The hash of the message to be signed.
+ *
+ */
+pub fn generate_k<D: Digest + Clone>(
+        digest: D,
+        order: &[u8],
+        private_key: &[u8],
+        message_hash: &[u8]) -> Vec<u8> {
+    let first = order.iter().position(|&x| x != 0).expect("the order must not be zero");
+    let order = &order[first..];
+    let rlen = order.len();
+    let qlen = rlen * 8 - order[0].leading_zeros() as usize;
+
+    // int2octets(x)
+    let first = private_key.iter().position(|&x| x != 0).unwrap_or(private_key.len());
+    let private_key = &private_key[first..];
+    assert!(private_key.len() <= rlen, "the private key must be less than the order");
+    let mut x = vec![0; rlen];
+    x[rlen - private_key.len()..].copy_from_slice(private_key);
+
+    // bits2octets(h1). bits2int(h1) is less than 2^qlen, so it is less than 2q and reducing it
+    // modulo q takes at most a single subtraction.
+    let mut h = bits2int(message_hash, qlen, rlen);
+    let mut reduced = h.clone();
+    if sub_assign(&mut reduced, order) == 0 {
+        h = reduced;
+    }
+
+    let mut drbg = HmacDrbg::new(digest, &x, &h, &[]);
+    let mut t = vec![0; rlen];
+    loop {
+        // Each call to generate() after the first also performs the K = HMAC_K(V || 0x00),
+        // V = HMAC_K(V) step that RFC 6979 requires when a candidate is rejected.
+        drbg.generate(&mut t, &[]).expect("HMAC_DRBG failed to generate k");
+        let k = bits2int(&t, qlen, rlen);
+
+        let mut diff = k.clone();
+        let less_than_order = sub_assign(&mut diff, order);
+        let non_zero = k.iter().fold(0, |acc, &b| acc | b);
+        if less_than_order == 1 && non_zero != 0 {
+            return k;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use digest::Digest;
+    use rfc6979::generate_k;
+    use sha2::Sha256;
+    use serialize::hex::FromHex;
+
+    fn sha256(msg: &[u8]) -> Vec<u8> {
+        let mut sh = Sha256::new();
+        sh.input(msg);
+        let mut h = vec![0; 32];
+        sh.result(&mut h);
+        h
+    }
+
+    // RFC 6979, Appendix A.2.5 - ECDSA with P-256 and SHA-256
+    #[test]
+    fn test_p256_sha256() {
+        let q = "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"
+            .from_hex().unwrap();
+        let x = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721"
+            .from_hex().unwrap();
+
+        let k = generate_k(Sha256::new(), &q, &x, &sha256(b"sample"));
+        assert_eq!(k, "a6e3c57dd01abe90086538398355dd4c3b17aa873382b0f24d6129493d8aad60"
+            .from_hex().unwrap());
+
+        let k = generate_k(Sha256::new(), &q, &x, &sha256(b"test"));
+        assert_eq!(k, "d16b6ae827f17175e040871a1c7ec3500192c4c92677336ec2537acaee0008e0"
+            .from_hex().unwrap());
+    }
+
+    // RFC 6979, Appendix A.1.2 - the order is 163 bits long, so it is not a whole number of bytes,
+    // and the first candidates for k are rejected because they are not less than q.
+    #[test]
+    fn test_rejection() {
+        let q = "04000000000000000000020108a2e0cc0d99f8a5ef".from_hex().unwrap();
+        let x = "009a4d6792295a7f730fc3f2b49cbc0f62e862272f".from_hex().unwrap();
+
+        let k = generate_k(Sha256::new(), &q, &x, &sha256(b"sample"));
+        assert_eq!(k, "023af4074c90a02b3fe61d286d5c87f425e6bdd81b".from_hex().unwrap());
+    }
+}
@@ -6,7 +6,8 @@
 
 use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
 use symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
-use cryptoutil::{read_u32_le, symm_enc_or_dec, write_u32_le, xor_keystream};
+use cryptoutil::{debug_assert_no_overlap, read_u32_le, symm_enc_or_dec, write_u32_le, xor_keystream,
+    zero};
 use simd::u32x4;
 
 use std::cmp;
@@ -24,6 +25,8 @@ pub struct Salsa20 {
     state: SalsaState,
     output: [u8; 64],
     offset: usize,
+    // Set once a call to encrypt() or decrypt() with eof set has consumed all of its input
+    finished: bool,
 }
 
 impl Clone for Salsa20 { fn clone(&self) -> Salsa20 { *self } }
@@ -83,13 +86,25 @@ impl Salsa20 {
     pub fn new(key: &[u8], nonce: &[u8]) -> Salsa20 {
         assert!(key.len() == 16 || key.len() == 32);
         assert!(nonce.len() == 8);
-        Salsa20 { state: Salsa20::expand(key, nonce), output: [0; 64], offset: 64 }
+        Salsa20 { state: Salsa20::expand(key, nonce), output: [0; 64], offset: 64, finished: false }
+    }
+
+    /// Replace the key and nonce in place, as if a new Salsa20 had been constructed with them.
+    /// Any keystream left over from the previous key is discarded.
+    pub fn re_key(&mut self, key: &[u8], nonce: &[u8]) {
+        assert!(key.len() == 16 || key.len() == 32);
+        assert!(nonce.len() == 8);
+
+        self.state = Salsa20::expand(key, nonce);
+        zero(&mut self.output);
+        self.offset = 64;
+        self.finished = false;
     }
 
     pub fn new_xsalsa20(key: &[u8], nonce: &[u8]) -> Salsa20 {
         assert!(key.len() == 32);
         assert!(nonce.len() == 24);
-        let mut xsalsa20 = Salsa20 { state: Salsa20::expand(key, &nonce[0..16]), output: [0; 64], offset: 64 };
+        let mut xsalsa20 = Salsa20 { state: Salsa20::expand(key, &nonce[0..16]), output: [0; 64], offset: 64, finished: false };
 
         let mut new_key = [0; 32];
         xsalsa20.hsalsa20_hash(&mut new_key);
@@ -98,6 +113,27 @@ impl Salsa20 {
         xsalsa20
     }
 
+    /// Move to an arbitrary position in the keystream, so that the next byte processed is XORed
+    /// with keystream byte `byte_offset`. For XSalsa20 the offset is into the keystream generated
+    /// with the derived subkey, which is the keystream that encrypts the data. Seeking also allows
+    /// data to be processed again after a final call to encrypt() or decrypt().
+    pub fn seek(&mut self, byte_offset: u64) {
+        self.finished = false;
+        let block = byte_offset / 64;
+        let u32x4(a0, _, a2, a3) = self.state.a;
+        self.state.a = u32x4(a0, (block >> 32) as u32, a2, a3); // x9
+        let u32x4(_, b1, b2, b3) = self.state.b;
+        self.state.b = u32x4(block as u32, b1, b2, b3); // x8
+
+        let rem = (byte_offset % 64) as usize;
+        if rem == 0 {
+            self.offset = 64;
+        } else {
+            self.hash();
+            self.offset = rem;
+        }
+    }
+
     fn expand(key: &[u8], nonce: &[u8]) -> SalsaState {
         let constant = match key.len() {
             16 => b"expand 16-byte k",
@@ -180,7 +216,7 @@ impl Salsa20 {
         }
 
         self.state.b = self.state.b + u32x4(1, 0, 0, 0);
-        let u32x4(_, _, _, ctr_lo) = self.state.b;
+        let u32x4(ctr_lo, _, _, _) = self.state.b;
         if ctr_lo == 0 {
             self.state.a = self.state.a + u32x4(0, 1, 0, 0);
         }
@@ -213,7 +249,9 @@ impl Salsa20 {
 impl SynchronousStreamCipher for Salsa20 {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
         let len = input.len();
+        assert!(len == 0 || !self.finished, "keystream used after the final call to encrypt");
         let mut i = 0;
         while i < len {
             // If there is no keystream available in the output buffer,
@@ -229,26 +267,62 @@ impl SynchronousStreamCipher for Salsa20 {
             self.offset += count;
         }
     }
+
+    fn process_in_place(&mut self, data: &mut [u8]) {
+        let len = data.len();
+        assert!(len == 0 || !self.finished, "keystream used after the final call to encrypt");
+        let mut i = 0;
+        while i < len {
+            if self.offset == 64 {
+                self.hash();
+            }
+
+            let count = cmp::min(64 - self.offset, len - i);
+            for (d, &k) in data[i..i+count].iter_mut().zip(self.output[self.offset..].iter()) {
+                *d ^= k;
+            }
+            i += count;
+            self.offset += count;
+        }
+    }
 }
 
+impl Salsa20 {
+    // Once a call with eof set has consumed all of its input the message is complete, and any
+    // further data would be processed with keystream that continues the finished message.
+    fn process_buffers(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        let result = symm_enc_or_dec(self, input, output);
+        if let Ok(BufferResult::BufferUnderflow) = result {
+            self.finished |= eof;
+        }
+        result
+    }
+}
+
+/// Once encrypt() has been called with eof set and has consumed all of its input, processing any
+/// more data panics, since it would continue the keystream of a finished message. Empty calls are
+/// still allowed. Use re_key() to start a new message.
 impl Encryptor for Salsa20 {
-    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
             -> Result<BufferResult, SymmetricCipherError> {
-        symm_enc_or_dec(self, input, output)
+        self.process_buffers(input, output, eof)
     }
 }
 
+/// Like encrypt(), processing any data after a call to decrypt() with eof set has consumed all of
+/// its input panics.
 impl Decryptor for Salsa20 {
-    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
             -> Result<BufferResult, SymmetricCipherError> {
-        symm_enc_or_dec(self, input, output)
+        self.process_buffers(input, output, eof)
     }
 }
 
 pub fn hsalsa20(key: &[u8], nonce: &[u8], out: &mut [u8]) {
     assert!(key.len() == 32);
     assert!(nonce.len() == 16);
-    let mut h = Salsa20 { state: Salsa20::expand(key, nonce), output: [0; 64], offset: 64 };
+    let mut h = Salsa20 { state: Salsa20::expand(key, nonce), output: [0; 64], offset: 64, finished: false };
     h.hsalsa20_hash(out);
 }
 
@@ -256,8 +330,9 @@ pub fn hsalsa20(key: &[u8], nonce: &[u8], out: &mut [u8]) {
 mod test {
     use std::iter::repeat;
 
+    use buffer::{RefReadBuffer, RefWriteBuffer};
     use salsa20::Salsa20;
-    use symmetriccipher::SynchronousStreamCipher;
+    use symmetriccipher::{Decryptor, Encryptor, SynchronousStreamCipher};
 
     use digest::Digest;
     use sha2::Sha256;
@@ -330,6 +405,159 @@ mod test {
         assert!(&out_str[..] == output_str);
     }
 
+    #[test]
+    fn test_salsa20_process_in_place() {
+        let key = [0x1fu8; 32];
+        let nonce = [0x2eu8; 8];
+        let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut expected = vec![0u8; plaintext.len()];
+        Salsa20::new(&key, &nonce).process(&plaintext, &mut expected);
+
+        // Encrypt in pieces that don't line up with the block size
+        let mut data = plaintext.clone();
+        let mut cipher = Salsa20::new(&key, &nonce);
+        cipher.process_in_place(&mut data[..3]);
+        cipher.process_in_place(&mut data[3..130]);
+        cipher.process_in_place(&mut data[130..]);
+        assert!(data == expected);
+
+        Salsa20::new(&key, &nonce).process_in_place(&mut data);
+        assert!(data == plaintext);
+    }
+
+    #[test]
+    fn test_salsa20_keystream() {
+        let key = [0x1fu8; 32];
+        let nonce = [0x2eu8; 8];
+        let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut expected = vec![0u8; plaintext.len()];
+        Salsa20::new(&key, &nonce).process(&plaintext, &mut expected);
+
+        // Request the keystream in pieces that don't line up with the block size
+        let mut cipher = Salsa20::new(&key, &nonce);
+        let mut keystream = vec![0u8; plaintext.len()];
+        cipher.keystream(&mut keystream[..3]);
+        cipher.keystream(&mut keystream[3..130]);
+        cipher.keystream(&mut keystream[130..]);
+
+        let xored: Vec<u8> = plaintext.iter().zip(keystream.iter()).map(|(&p, &k)| p ^ k).collect();
+        assert!(xored == expected);
+    }
+
+    #[test]
+    fn test_salsa20_re_key() {
+        let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let mut cipher = Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        // Leave part of a block of keystream buffered
+        let mut scratch = [0u8; 70];
+        cipher.process(&plaintext[..70], &mut scratch);
+
+        for &key in [&[0x3du8; 32][..], &[0x5bu8; 16][..]].iter() {
+            let nonce = [0x4cu8; 8];
+            let mut expected = vec![0u8; plaintext.len()];
+            Salsa20::new(key, &nonce).process(&plaintext, &mut expected);
+
+            cipher.re_key(key, &nonce);
+            let mut output = vec![0u8; plaintext.len()];
+            cipher.process(&plaintext, &mut output);
+            assert!(output == expected);
+        }
+    }
+
+    #[test]
+    fn test_salsa20_final() {
+        let plaintext: Vec<u8> = (0..100).map(|i| i as u8).collect();
+        let mut expected = vec![0u8; plaintext.len()];
+        Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]).process(&plaintext, &mut expected);
+
+        // A final call that runs out of output space can be continued until it has consumed all
+        // of its input, and empty calls afterwards are harmless.
+        let mut cipher = Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        let mut output = vec![0u8; plaintext.len()];
+        let mut read_buffer = RefReadBuffer::new(&plaintext);
+        for chunk in output.chunks_mut(30) {
+            cipher.encrypt(&mut read_buffer, &mut RefWriteBuffer::new(chunk), true).unwrap();
+        }
+        cipher.encrypt(&mut RefReadBuffer::new(&[]), &mut RefWriteBuffer::new(&mut []), true)
+            .unwrap();
+        assert!(output == expected);
+
+        // Re-keying starts a new message
+        cipher.re_key(&[0x1fu8; 32], &[0x2eu8; 8]);
+        cipher.process(&plaintext, &mut output);
+        assert!(output == expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_salsa20_encrypt_after_final() {
+        let mut cipher = Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        let mut output = [0u8; 10];
+        cipher.encrypt(&mut RefReadBuffer::new(&[1; 10]), &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        cipher.encrypt(&mut RefReadBuffer::new(&[2; 10]), &mut RefWriteBuffer::new(&mut output),
+            false).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_salsa20_process_after_final() {
+        let mut cipher = Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        let mut output = [0u8; 10];
+        cipher.decrypt(&mut RefReadBuffer::new(&[1; 10]), &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        cipher.process(&[2; 10], &mut output);
+    }
+
+    // Seeking to k and then processing data must give the same result as processing k + n bytes
+    // from the start of the keystream and taking the last n.
+    fn check_seek<F: Fn() -> Salsa20>(new_cipher: F) {
+        let input: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let mut expected = vec![0u8; input.len()];
+        new_cipher().process(&input, &mut expected);
+
+        for &k in [0, 1, 63, 64, 65, 128, 200].iter() {
+            for &n in [1, 63, 64, 65, 100].iter() {
+                let mut cipher = new_cipher();
+                // Put the cipher into the middle of a block first
+                let mut scratch = [0u8; 7];
+                cipher.process(&[0u8; 7], &mut scratch);
+
+                cipher.seek(k as u64);
+                let mut output = vec![0u8; n];
+                cipher.process(&input[k..k + n], &mut output);
+                assert!(output[..] == expected[k..k + n]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_salsa20_seek() {
+        check_seek(|| Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]));
+        check_seek(|| Salsa20::new_xsalsa20(&[0x1fu8; 32], &[0x2eu8; 24]));
+    }
+
+    #[test]
+    fn test_salsa20_seek_counter_carry() {
+        // The block counter is 64 bits long, so seeking past block 2^32 - 1 must carry into the
+        // high word in the same way that generating the keystream does.
+        let mut cipher = Salsa20::new(&[0x1fu8; 32], &[0x2eu8; 8]);
+        cipher.seek(((1 << 32) - 1) * 64);
+        let mut expected = [0u8; 128];
+        cipher.process(&[0u8; 128], &mut expected);
+
+        cipher.seek((1 << 32) * 64 + 10);
+        let mut output = [0u8; 54];
+        cipher.process(&[0u8; 54], &mut output);
+        assert!(output[..] == expected[74..]);
+
+        // Computed with a reference implementation of the Salsa20 core
+        let result = [0x8c, 0xfc, 0x1e, 0xe9, 0x3b, 0x20, 0x39, 0xa4, 0x6a, 0xfd];
+        assert!(output[..10] == result[..]);
+    }
+
     #[test]
     fn test_xsalsa20_cryptopp() {
         let key =
@@ -23,6 +23,7 @@ use serialize::base64;
 use serialize::base64::{FromBase64, ToBase64};
 
 use cryptoutil::{read_u32_le, read_u32v_le, write_u32_le};
+use derivedkey::DerivedKey;
 use hmac::Hmac;
 use pbkdf2::pbkdf2;
 use sha2::Sha256;
@@ -247,6 +248,21 @@ pub fn scrypt(password: &[u8], salt: &[u8], params: &ScryptParams, output: &mut
     pbkdf2(&mut mac, &*b, 1, output);
 }
 
+/**
+ * The scrypt key derivation function, returning a newly allocated DerivedKey of len bytes.
+ *
+ * # Arguments
+ *
+ * * password - The password to process as a byte vector
+ * * salt - The salt value to use as a byte vector
+ * * params - The ScryptParams to use
+ * * len - The length of the key to derive. This must not be zero.
+ *
+ */
+pub fn scrypt_derive(password: &[u8], salt: &[u8], params: &ScryptParams, len: usize) -> DerivedKey {
+    DerivedKey::new(len, |output| scrypt(password, salt, params, output))
+}
+
 /**
  * scrypt_simple is a helper function that should be sufficient for the majority of cases where
  * an application needs to use Scrypt to hash a password for storage. The result is a String that
@@ -410,7 +426,7 @@ pub fn scrypt_check(password: &str, hashed_value: &str) -> Result<bool, &'static
 mod test {
     use std::iter::repeat;
 
-    use scrypt::{scrypt, scrypt_simple, scrypt_check, ScryptParams};
+    use scrypt::{scrypt, scrypt_derive, scrypt_simple, scrypt_check, ScryptParams};
 
     struct Test {
         password: &'static str,
@@ -487,6 +503,15 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_scrypt_derive() {
+        // Only the first test vector, since the others are slow
+        let t = &tests()[0];
+        let params = ScryptParams::new(t.log_n, t.r, t.p);
+        let key = scrypt_derive(t.password.as_bytes(), t.salt.as_bytes(), &params, t.expected.len());
+        assert!(key.as_bytes() == &t.expected[..]);
+    }
+
     fn test_scrypt_simple(log_n: u8, r: u32, p: u32) {
         let password = "password";
 
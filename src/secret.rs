@@ -0,0 +1,106 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+A wrapper for key material that overwrites it with zeros when it is dropped.
+
+None of the types in this crate clear their keys on their own, since doing so costs time in paths
+where that matters. `Secret` makes this opt-in: wrap any buffer that can be viewed as a byte slice
+and its contents are zeroed, using `util::secure_memset`, when the wrapper goes out of scope. The
+wrapper dereferences to the inner value, so it can be passed wherever the key is expected, and
+`as_mut_bytes()` gives mutable access to its bytes.
+
+Only the bytes can be changed, not the length. Growing a `Vec` may reallocate it and free the old
+buffer without zeroing it, and shrinking it with `truncate()` or `clear()` would hide the bytes
+past the new length from the zeroing on drop. Either way the key material would be left behind, so
+`Secret` does not hand out `&mut T`.
+
+This module is only available when the `zeroize_on_drop` feature is enabled.
+
+# Caveats
+
+Zeroing on drop is best effort. The write itself can not be removed by the optimizer, but the
+compiler is free to leave copies of the value elsewhere: moving a `Secret` by value may leave the
+old bytes behind on the stack, and buffers such as a `Vec` may have been reallocated before they
+were wrapped. Keys derived into other structures, such as expanded cipher key schedules, are not
+affected at all. Wrap buffers in place and avoid moving them to get the most out of this.
+
+# Usage
+
+```rust
+use self::crypto::secret::Secret;
+
+let mut key = Secret::new([0u8; 32]);
+key.as_mut_bytes()[0] = 1;
+assert_eq!(key.len(), 32);
+```
+*/
+
+use std::ops::Deref;
+
+use util::secure_memset;
+
+/// Holds a value whose bytes are overwritten with zeros when it is dropped.
+pub struct Secret<T: AsMut<[u8]>> {
+    inner: T,
+}
+
+impl <T: AsMut<[u8]>> Secret<T> {
+    /// Wraps the given value.
+    pub fn new(inner: T) -> Secret<T> {
+        Secret { inner }
+    }
+
+    /// Gets mutable access to the bytes of the wrapped value.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        self.inner.as_mut()
+    }
+}
+
+impl <T: AsMut<[u8]>> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl <T: AsMut<[u8]>> Drop for Secret<T> {
+    fn drop(&mut self) {
+        secure_memset(self.inner.as_mut(), 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use secret::Secret;
+
+    #[test]
+    fn test_secret_zeroes_on_drop() {
+        // Wrapping a mutable borrow lets the buffer be inspected after the wrapper is gone
+        let mut key = [0x42u8; 32];
+        {
+            let mut secret = Secret::new(&mut key);
+            assert!(secret.iter().all(|&b| b == 0x42));
+            secret.as_mut_bytes()[0] = 0x17;
+        }
+        assert!(key.iter().all(|&b| b == 0));
+
+        let mut buf = [0xffu8; 100];
+        drop(Secret::new(&mut buf[10..20]));
+        assert!(buf[10..20].iter().all(|&b| b == 0));
+        assert!(buf[..10].iter().chain(buf[20..].iter()).all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn test_secret_deref() {
+        let mut secret = Secret::new(vec![1u8, 2, 3]);
+        secret.as_mut_bytes()[2] = 4;
+        secret.as_mut_bytes().iter_mut().for_each(|b| *b *= 2);
+        assert_eq!(&secret[..], &[2, 4, 8]);
+        assert_eq!(secret.len(), 3);
+    }
+}
@@ -399,6 +399,36 @@ impl Sha1 {
         st.reset();
         st
     }
+
+    /// Construct a `Sha1` object that resumes from the chaining values `h` after `length_bytes`
+    /// bytes have been processed. `length_bytes` must be a multiple of the 64 byte block size.
+    ///
+    /// Combined with `export_state` on a finalized object this allows computing the hash of a
+    /// message extended past its padding, which is useful when analyzing or testing for length
+    /// extension weaknesses.
+    pub fn from_state(h: [u32; STATE_LEN], length_bytes: u64) -> Sha1 {
+        assert!(length_bytes.is_multiple_of(64));
+        Sha1 {
+            h,
+            length_bits: length_bytes << 3,
+            buffer: FixedBuffer64::new(),
+            computed: false,
+        }
+    }
+
+    /// Return the chaining values and the number of bytes that have been processed into them.
+    /// Input that is still buffered waiting for a full block is not included, so the byte count
+    /// is always a multiple of 64. After the result has been computed, the count includes the
+    /// padding and the returned chaining values are the digest.
+    pub fn export_state(&self) -> ([u32; STATE_LEN], u64) {
+        let length_bytes = self.length_bits >> 3;
+        if self.computed {
+            // The padding adds a 0x80 byte and an 8 byte length, rounded up to a full block
+            (self.h, (length_bytes + 9).div_ceil(64) * 64)
+        } else {
+            (self.h, length_bytes - self.buffer.position() as u64)
+        }
+    }
 }
 
 impl Digest for Sha1 {
@@ -420,6 +450,7 @@ impl Digest for Sha1 {
 
 #[cfg(test)]
 mod tests {
+    use cryptoutil::read_u32_be;
     use cryptoutil::test::test_digest_1million_random;
     use digest::Digest;
     use sha1::Sha1;
@@ -521,6 +552,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_export_import_state() {
+        let mut msg = [0x61u8; 67];
+        msg[64..].copy_from_slice(b"abc");
+
+        let mut sh = Sha1::new();
+        sh.input(&msg);
+        let expected = sh.result_str();
+
+        // Only the first block has been processed, "abc" is still buffered
+        let mut sh = Sha1::new();
+        sh.input(&msg);
+        let (h, length_bytes) = sh.export_state();
+        assert_eq!(length_bytes, 64);
+
+        let mut resumed = Sha1::from_state(h, length_bytes);
+        resumed.input(b"abc");
+        assert_eq!(resumed.result_str(), expected);
+    }
+
+    #[test]
+    fn test_length_extension() {
+        let mut sh = Sha1::new();
+        sh.input_str("abc");
+        let mut digest = [0u8; 20];
+        sh.result(&mut digest);
+        let (h, length_bytes) = sh.export_state();
+        assert_eq!(length_bytes, 64);
+        for (i, word) in h.iter().enumerate() {
+            assert_eq!(*word, read_u32_be(&digest[4 * i..4 * i + 4]));
+        }
+
+        // "abc" followed by its padding for a 24 bit message, then the extension
+        let mut glued = vec![0u8; 64];
+        glued[..3].copy_from_slice(b"abc");
+        glued[3] = 0x80;
+        glued[63] = 24;
+        glued.extend_from_slice(b"def");
+        let mut full = Sha1::new();
+        full.input(&glued);
+
+        let mut extended = Sha1::from_state(h, length_bytes);
+        extended.input_str("def");
+        assert_eq!(extended.result_str(), full.result_str());
+    }
+
     #[test]
     fn test_1million_random_sha1() {
         let mut sh = Sha1::new();
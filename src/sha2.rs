@@ -77,6 +77,10 @@ use cryptoutil::{write_u32_be, read_u32v_be,
                  FixedBuffer, FixedBuffer128, FixedBuffer64, StandardPadding};
 
 use simd::{u32x4, u64x2};
+#[cfg(target_arch = "x86_64")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(target_arch = "x86_64")]
+use util;
 
 const STATE_LEN: usize = 8;
 const BLOCK_LEN: usize = 16;
@@ -344,17 +348,56 @@ pub fn sha256_digest_block_u32(state: &mut [u32; 8], block: &[u32; 16]) {
 /// }
 /// ```
 ///
-/// **NOTE**: It is important to note, however, that these instructions are not implemented
-/// by any CPU (at the time of this writing), and so they are emulated in this library
-/// until the instructions become more common, and gain support in LLVM (and GCC, etc.).
+/// **NOTE**: On x86-64 CPUs that implement the SHA extensions, the block is processed with
+/// the real instructions. Everywhere else they are emulated as described above.
 ///
 pub fn sha256_digest_block(state: &mut [u32; 8], block: &[u8/*; 64*/]) {
     assert_eq!(block.len(), BLOCK_LEN*4);
+    if use_sha_ni() {
+        sha256_digest_block_ni(state, block);
+        return;
+    }
     let mut block2 = [0u32; BLOCK_LEN];
     read_u32v_be(&mut block2[..], block);
     sha256_digest_block_u32(state, &block2);
 }
 
+#[cfg(target_arch = "x86_64")]
+extern "C" {
+    fn rust_crypto_sha256_ni_digest_block(state: *mut u32, block: *const u8, k: *const u32);
+}
+
+// Whether to use the SHA extensions. The result of the CPUID check is cached, since running it for
+// every block would cost more than the block itself.
+#[cfg(target_arch = "x86_64")]
+fn use_sha_ni() -> bool {
+    static SHA_NI: AtomicUsize = AtomicUsize::new(0);
+    match SHA_NI.load(Ordering::Relaxed) {
+        0 => {
+            let supported = util::supports_sha_ni();
+            SHA_NI.store(if supported { 2 } else { 1 }, Ordering::Relaxed);
+            supported
+        }
+        cached => cached == 2
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn use_sha_ni() -> bool { false }
+
+#[cfg(target_arch = "x86_64")]
+fn sha256_digest_block_ni(state: &mut [u32; 8], block: &[u8]) {
+    assert_eq!(block.len(), BLOCK_LEN*4);
+    unsafe {
+        rust_crypto_sha256_ni_digest_block(state.as_mut_ptr(), block.as_ptr(), K32.as_ptr());
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn sha256_digest_block_ni(_: &mut [u32; 8], _: &[u8]) {
+    unreachable!();
+}
+
 /// Not an intrinsic, but works like an unaligned load.
 #[inline]
 fn sha512load(v0: u64x2, v1: u64x2) -> u64x2 {
@@ -1385,6 +1428,37 @@ mod tests {
         test_hash(&mut *sh, &tests[..]);
     }
 
+    fn test_abc<D: Digest>(sh: &mut D, output_bits: usize, output_str: &str) {
+        assert_eq!(sh.output_bits(), output_bits);
+
+        // Hash twice so that the initial hash value restored by reset() is checked as well
+        for _ in 0..2 {
+            sh.input_str("abc");
+            assert_eq!(sh.result_str(), output_str);
+            sh.reset();
+        }
+    }
+
+    #[test]
+    fn test_nist_abc() {
+        // The one block message example from FIPS 180-2 and the NIST example values for the
+        // SHA-512/t variants
+        test_abc(&mut Sha224::new(), 224,
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7");
+        test_abc(&mut Sha256::new(), 256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        test_abc(&mut Sha384::new(), 384,
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+             8086072ba1e7cc2358baeca134c825a7");
+        test_abc(&mut Sha512::new(), 512,
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f");
+        test_abc(&mut Sha512Trunc224::new(), 224,
+            "4634270f707b6a54daae7530460842e20e37ed265ceee9a43e8924aa");
+        test_abc(&mut Sha512Trunc256::new(), 256,
+            "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23");
+    }
+
     #[test]
     fn test_1million_random_sha512() {
         let mut sh = Sha512::new();
@@ -1402,6 +1476,34 @@ mod tests {
             64,
             "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
     }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_sha256_ni_matches_portable() {
+        use cryptoutil::read_u32v_be;
+        use rand::{IsaacRng, Rng};
+        use sha2::{sha256_digest_block_ni, sha256_digest_block_u32};
+        use util::supports_sha_ni;
+
+        if !supports_sha_ni() {
+            return;
+        }
+        let mut rng = IsaacRng::new_unseeded();
+        let mut block = [0u8; 64];
+        let mut words = [0u32; 16];
+        for _ in 0..1000 {
+            let mut state = [0u32; 8];
+            for s in state.iter_mut() {
+                *s = rng.next_u32();
+            }
+            rng.fill_bytes(&mut block);
+            let mut expected = state;
+            read_u32v_be(&mut words, &block);
+            sha256_digest_block_u32(&mut expected, &words);
+            sha256_digest_block_ni(&mut state, &block);
+            assert_eq!(state, expected);
+        }
+    }
 }
 
 
@@ -1411,7 +1513,7 @@ mod bench {
     use test::Bencher;
     use digest::Digest;
     use sha2::{STATE_LEN, BLOCK_LEN};
-    use sha2::{Sha256, Sha512, sha256_digest_block_u32, sha512_digest_block_u64};
+    use sha2::{Sha256, Sha512, sha256_digest_block, sha256_digest_block_u32, sha512_digest_block_u64};
 
     #[bench]
     pub fn sha256_block(bh: & mut Bencher) {
@@ -1423,6 +1525,16 @@ mod bench {
         bh.bytes = 64u64;
     }
 
+    #[bench]
+    pub fn sha256_block_dispatch(bh: & mut Bencher) {
+        let mut state = [0u32; STATE_LEN];
+        let block = [1u8; 64];
+        bh.iter( || {
+            sha256_digest_block(&mut state, &block);
+        });
+        bh.bytes = 64u64;
+    }
+
     #[bench]
     pub fn sha512_block(bh: & mut Bencher) {
         let mut state = [0u64; STATE_LEN];
@@ -0,0 +1,17 @@
+ifndef X64
+.686p
+.XMM
+.model flat, C
+endif
+
+.code
+
+; The SHA extension code path is only enabled when building with GCC or Clang, so
+; this is never called
+ifdef X64
+rust_crypto_sha256_ni_digest_block PROC public
+  ret
+rust_crypto_sha256_ni_digest_block ENDP
+endif
+
+end
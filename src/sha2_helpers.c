@@ -0,0 +1,238 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#include <stdint.h>
+#include <stdlib.h>
+
+#if defined(__x86_64__)
+
+// Byte order shuffle that turns each big endian message word into a little endian one
+static const uint8_t rust_crypto_sha256_shuffle_mask[16] = {
+    3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8, 15, 14, 13, 12
+};
+
+// Process a single 64 byte block with the SHA extensions. state holds the eight working variables
+// a through h and k points to the 64 round constants. The state is kept in xmm1 (ABEF) and xmm2
+// (CDGH), the layout used by sha256rnds2, and the message schedule rotates through xmm3 - xmm6.
+void rust_crypto_sha256_ni_digest_block(uint32_t* state, const uint8_t* block, const uint32_t* k) {
+    #ifdef __SSE__
+    asm volatile(
+        " \
+            movdqu (%0), %%xmm1; \
+            movdqu 16(%0), %%xmm2; \
+            movdqu (%3), %%xmm8; \
+            \
+            pshufd $0xb1, %%xmm1, %%xmm1; \
+            pshufd $0x1b, %%xmm2, %%xmm2; \
+            movdqa %%xmm1, %%xmm7; \
+            palignr $8, %%xmm2, %%xmm1; \
+            pblendw $0xf0, %%xmm7, %%xmm2; \
+            movdqa %%xmm1, %%xmm9; \
+            movdqa %%xmm2, %%xmm10; \
+            \
+            movdqu (%1), %%xmm3; \
+            pshufb %%xmm8, %%xmm3; \
+            movdqa %%xmm3, %%xmm0; \
+            movdqu (%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            \
+            movdqu 16(%1), %%xmm4; \
+            pshufb %%xmm8, %%xmm4; \
+            movdqa %%xmm4, %%xmm0; \
+            movdqu 16(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm4, %%xmm3; \
+            \
+            movdqu 32(%1), %%xmm5; \
+            pshufb %%xmm8, %%xmm5; \
+            movdqa %%xmm5, %%xmm0; \
+            movdqu 32(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm5, %%xmm4; \
+            \
+            movdqu 48(%1), %%xmm6; \
+            pshufb %%xmm8, %%xmm6; \
+            movdqa %%xmm6, %%xmm0; \
+            movdqu 48(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm6, %%xmm7; \
+            palignr $4, %%xmm5, %%xmm7; \
+            paddd %%xmm7, %%xmm3; \
+            sha256msg2 %%xmm6, %%xmm3; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm6, %%xmm5; \
+            \
+            movdqa %%xmm3, %%xmm0; \
+            movdqu 64(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm3, %%xmm7; \
+            palignr $4, %%xmm6, %%xmm7; \
+            paddd %%xmm7, %%xmm4; \
+            sha256msg2 %%xmm3, %%xmm4; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm3, %%xmm6; \
+            \
+            movdqa %%xmm4, %%xmm0; \
+            movdqu 80(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm4, %%xmm7; \
+            palignr $4, %%xmm3, %%xmm7; \
+            paddd %%xmm7, %%xmm5; \
+            sha256msg2 %%xmm4, %%xmm5; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm4, %%xmm3; \
+            \
+            movdqa %%xmm5, %%xmm0; \
+            movdqu 96(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm5, %%xmm7; \
+            palignr $4, %%xmm4, %%xmm7; \
+            paddd %%xmm7, %%xmm6; \
+            sha256msg2 %%xmm5, %%xmm6; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm5, %%xmm4; \
+            \
+            movdqa %%xmm6, %%xmm0; \
+            movdqu 112(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm6, %%xmm7; \
+            palignr $4, %%xmm5, %%xmm7; \
+            paddd %%xmm7, %%xmm3; \
+            sha256msg2 %%xmm6, %%xmm3; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm6, %%xmm5; \
+            \
+            movdqa %%xmm3, %%xmm0; \
+            movdqu 128(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm3, %%xmm7; \
+            palignr $4, %%xmm6, %%xmm7; \
+            paddd %%xmm7, %%xmm4; \
+            sha256msg2 %%xmm3, %%xmm4; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm3, %%xmm6; \
+            \
+            movdqa %%xmm4, %%xmm0; \
+            movdqu 144(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm4, %%xmm7; \
+            palignr $4, %%xmm3, %%xmm7; \
+            paddd %%xmm7, %%xmm5; \
+            sha256msg2 %%xmm4, %%xmm5; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm4, %%xmm3; \
+            \
+            movdqa %%xmm5, %%xmm0; \
+            movdqu 160(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm5, %%xmm7; \
+            palignr $4, %%xmm4, %%xmm7; \
+            paddd %%xmm7, %%xmm6; \
+            sha256msg2 %%xmm5, %%xmm6; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm5, %%xmm4; \
+            \
+            movdqa %%xmm6, %%xmm0; \
+            movdqu 176(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm6, %%xmm7; \
+            palignr $4, %%xmm5, %%xmm7; \
+            paddd %%xmm7, %%xmm3; \
+            sha256msg2 %%xmm6, %%xmm3; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm6, %%xmm5; \
+            \
+            movdqa %%xmm3, %%xmm0; \
+            movdqu 192(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm3, %%xmm7; \
+            palignr $4, %%xmm6, %%xmm7; \
+            paddd %%xmm7, %%xmm4; \
+            sha256msg2 %%xmm3, %%xmm4; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            sha256msg1 %%xmm3, %%xmm6; \
+            \
+            movdqa %%xmm4, %%xmm0; \
+            movdqu 208(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm4, %%xmm7; \
+            palignr $4, %%xmm3, %%xmm7; \
+            paddd %%xmm7, %%xmm5; \
+            sha256msg2 %%xmm4, %%xmm5; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            \
+            movdqa %%xmm5, %%xmm0; \
+            movdqu 224(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            movdqa %%xmm5, %%xmm7; \
+            palignr $4, %%xmm4, %%xmm7; \
+            paddd %%xmm7, %%xmm6; \
+            sha256msg2 %%xmm5, %%xmm6; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            \
+            movdqa %%xmm6, %%xmm0; \
+            movdqu 240(%2), %%xmm7; \
+            paddd %%xmm7, %%xmm0; \
+            sha256rnds2 %%xmm1, %%xmm2; \
+            pshufd $0x0e, %%xmm0, %%xmm0; \
+            sha256rnds2 %%xmm2, %%xmm1; \
+            \
+            paddd %%xmm9, %%xmm1; \
+            paddd %%xmm10, %%xmm2; \
+            \
+            pshufd $0x1b, %%xmm1, %%xmm1; \
+            pshufd $0xb1, %%xmm2, %%xmm2; \
+            movdqa %%xmm1, %%xmm7; \
+            pblendw $0xf0, %%xmm2, %%xmm1; \
+            palignr $8, %%xmm7, %%xmm2; \
+            \
+            movdqu %%xmm1, (%0); \
+            movdqu %%xmm2, 16(%0); \
+        "
+    : // outputs
+    : "r" (state), "r" (block), "r" (k), "r" (rust_crypto_sha256_shuffle_mask) // inputs
+    : "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10",
+      "memory" // clobbers
+    );
+    #else
+    exit(1);
+    #endif
+}
+
+#endif
@@ -21,6 +21,11 @@ There are 6 standard algorithms specified in the SHA-3 standard:
  * `SHAKE256`, an extendable output function (XOF)
  * `Keccak224`, `Keccak256`, `Keccak384`, `Keccak512` (NIST submission without padding changes)
 
+The `Keccak` type is a more general sponge whose capacity, domain separation suffix, and output
+length are chosen by the caller. Its `new_keccak256()` and `new_sha3_256()` constructors make the
+difference between the original Keccak-256 used by Ethereum and the standardized SHA3-256
+explicit: they are identical except for the domain separation suffix.
+
 Based on an [implementation by Sébastien Martini](https://github.com/seb-m/crypto.rs/blob/master/src/sha3.rs)
 
 # Usage
@@ -48,7 +53,7 @@ assert_eq!(hex, "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe2451143153
 use std::cmp;
 
 use digest::Digest;
-use cryptoutil::{write_u64v_le, read_u64v_le, zero};
+use cryptoutil::{write_u64v_le, read_u64v_le, zero, FixedBuffer, FixedBufferN};
 
 const B: usize = 200;
 const NROUNDS: usize = 24;
@@ -203,14 +208,71 @@ impl Sha3Mode {
     }
 }
 
+// XOR a block of rate bytes into the state and apply the permutation.
+fn absorb_block(state: &mut [u8; B], block: &[u8]) {
+    for (s, &b) in state.iter_mut().zip(block.iter()) {
+        *s ^= b;
+    }
+    keccak_f(state);
+}
+
+/// The input buffer for the absorb phase, which holds one block of rate bytes. The rate depends
+/// on the mode, so there is a separately sized FixedBuffer for each possible rate. The largest one
+/// is boxed so that the smaller rates don't pay for it.
+#[derive(Clone)]
+enum RateBuffer {
+    Rate72(FixedBufferN<72>),
+    Rate104(FixedBufferN<104>),
+    Rate136(FixedBufferN<136>),
+    Rate144(FixedBufferN<144>),
+    Rate168(Box<FixedBufferN<168>>)
+}
+
+macro_rules! with_rate_buffer(
+    ($rate_buffer:expr, $buffer:ident => $body:expr) => (
+        match $rate_buffer {
+            RateBuffer::Rate72(ref mut $buffer) => $body,
+            RateBuffer::Rate104(ref mut $buffer) => $body,
+            RateBuffer::Rate136(ref mut $buffer) => $body,
+            RateBuffer::Rate144(ref mut $buffer) => $body,
+            RateBuffer::Rate168(ref mut $buffer) => $body
+        }
+    )
+);
+
+impl RateBuffer {
+    fn new(rate: usize) -> RateBuffer {
+        match rate {
+            72 => RateBuffer::Rate72(FixedBufferN::new()),
+            104 => RateBuffer::Rate104(FixedBufferN::new()),
+            136 => RateBuffer::Rate136(FixedBufferN::new()),
+            144 => RateBuffer::Rate144(FixedBufferN::new()),
+            168 => RateBuffer::Rate168(Box::new(FixedBufferN::new())),
+            _ => unreachable!()
+        }
+    }
+
+    fn input(&mut self, state: &mut [u8; B], data: &[u8]) {
+        with_rate_buffer!(*self, buffer => buffer.input(data, |block| absorb_block(state, block)))
+    }
 
+    fn position(&mut self) -> usize {
+        with_rate_buffer!(*self, buffer => buffer.position())
+    }
+
+    fn reset(&mut self) {
+        with_rate_buffer!(*self, buffer => buffer.reset())
+    }
+}
+
+#[derive(Clone)]
 pub struct Sha3 {
     state: [u8; B],  // B bytes
     mode: Sha3Mode,
+    buffer: RateBuffer,  // Enqueued bytes for absorb phase
     can_absorb: bool,  // Can absorb
     can_squeeze: bool,  // Can squeeze
-    offset: usize  // Enqueued bytes in state for absorb phase
-                   // Squeeze offset for squeeze phase
+    offset: usize  // Squeeze offset for squeeze phase
 }
 
 impl Sha3 {
@@ -219,6 +281,7 @@ impl Sha3 {
         Sha3 {
             state: [0; B],
             mode: mode,
+            buffer: RateBuffer::new(B - mode.capacity()),
             can_absorb: true,
             can_squeeze: true,
             offset: 0
@@ -324,7 +387,7 @@ impl Sha3 {
             buf[buflen - 1] |= 0x80;
         }
 
-        let p_len = pad_len(ds_len, self.offset * 8, self.rate() * 8);
+        let p_len = pad_len(ds_len, self.buffer.position() * 8, self.rate() * 8);
 
         let mut p: Vec<u8> = vec![0; p_len];
 
@@ -335,6 +398,7 @@ impl Sha3 {
         set_pad(ds_len, &mut p);
 
         self.input(&p);
+        assert!(self.buffer.position() == 0);
         self.can_absorb = false;
     }
 
@@ -349,29 +413,7 @@ impl Digest for Sha3 {
             panic!("Invalid state, absorb phase already finalized.");
         }
 
-        let r = self.rate();
-        assert!(self.offset < r);
-
-        let in_len = data.len();
-        let mut in_pos: usize = 0;
-
-        // Absorb
-        while in_pos < in_len {
-            let offset = self.offset;
-            let nread = cmp::min(r - offset, in_len - in_pos);
-            for i in 0..nread {
-                self.state[offset + i] = self.state[offset + i] ^ data[in_pos + i];
-            }
-            in_pos += nread;
-
-            if offset + nread != r {
-                self.offset += nread;
-                break;
-            }
-
-            self.offset = 0;
-            keccak_f(&mut self.state);
-        }
+        self.buffer.input(&mut self.state, data);
     }
 
     fn result(&mut self, out: &mut [u8]) {
@@ -429,6 +471,7 @@ impl Digest for Sha3 {
     fn reset(&mut self) {
         self.can_absorb = true;
         self.can_squeeze = true;
+        self.buffer.reset();
         self.offset = 0;
 
         zero(&mut self.state);
@@ -443,13 +486,98 @@ impl Digest for Sha3 {
     }
 }
 
-impl Copy for Sha3 {
 
+/// A Keccak sponge with a configurable capacity, domain separation suffix, and output length.
+///
+/// The domain separation suffix holds the bits that are appended to the message before the pad10*1
+/// padding, stored least significant bit first, together with the first padding bit. It is 0x01
+/// for the original Keccak submission, 0x06 for the SHA-3 hash functions, and 0x1f for SHAKE.
+#[derive(Clone, Copy)]
+pub struct Keccak {
+    state: [u8; B],
+    rate: usize,
+    suffix: u8,
+    output_bytes: usize,
+    offset: usize,
+    finished: bool
 }
 
-impl Clone for Sha3 {
-    fn clone(&self) -> Self {
-        *self
+impl Keccak {
+    /// New Keccak sponge. The rate is 200 bytes minus capacity, which must be a positive multiple
+    /// of 8 bytes less than 200. output_bytes may be larger than the rate, in which case more
+    /// output is squeezed from the sponge as needed.
+    pub fn new(capacity: usize, suffix: u8, output_bytes: usize) -> Keccak {
+        assert!(capacity > 0 && capacity < B && capacity % 8 == 0);
+        assert!(suffix != 0);
+        Keccak {
+            state: [0; B],
+            rate: B - capacity,
+            suffix,
+            output_bytes,
+            offset: 0,
+            finished: false
+        }
+    }
+
+    /// New Keccak-256 instance, as used by Ethereum. This is not the same as SHA3-256.
+    pub fn new_keccak256() -> Keccak {
+        Keccak::new(64, 0x01, 32)
+    }
+
+    /// New SHA3-256 instance.
+    pub fn new_sha3_256() -> Keccak {
+        Keccak::new(64, 0x06, 32)
+    }
+}
+
+impl Digest for Keccak {
+    fn input(&mut self, data: &[u8]) {
+        assert!(!self.finished);
+        for &b in data.iter() {
+            self.state[self.offset] ^= b;
+            self.offset += 1;
+            if self.offset == self.rate {
+                keccak_f(&mut self.state);
+                self.offset = 0;
+            }
+        }
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        assert!(out.len() >= self.output_bytes);
+        if !self.finished {
+            // The suffix and the first padding bit may land in the same byte as the final padding
+            // bit if the message fills all but the last byte of the block
+            self.state[self.offset] ^= self.suffix;
+            self.state[self.rate - 1] ^= 0x80;
+            keccak_f(&mut self.state);
+            self.finished = true;
+        }
+
+        // Squeeze from a copy of the state so that result() may be called more than once
+        let mut state = self.state;
+        let mut chunks = out[..self.output_bytes].chunks_mut(self.rate).peekable();
+        while let Some(chunk) = chunks.next() {
+            let len = chunk.len();
+            chunk.copy_from_slice(&state[..len]);
+            if chunks.peek().is_some() {
+                keccak_f(&mut state);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        zero(&mut self.state);
+        self.offset = 0;
+        self.finished = false;
+    }
+
+    fn output_bits(&self) -> usize {
+        self.output_bytes * 8
+    }
+
+    fn block_size(&self) -> usize {
+        self.rate
     }
 }
 
@@ -458,7 +586,7 @@ impl Clone for Sha3 {
 #[cfg(test)]
 mod tests {
     use digest::Digest;
-    use sha3::{Sha3, Sha3Mode};
+    use sha3::{B, Keccak, Sha3, Sha3Mode};
     use serialize::hex::{FromHex, ToHex};
 
     struct Test {
@@ -536,6 +664,148 @@ mod tests {
         test_hash(&mut *sh, &test_cases[..]);
     }
 
+    #[test]
+    fn test_keccak_sponge_256() {
+        let test_cases = vec![
+            Test {
+                input: "",
+                output_str: "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+            },
+            Test {
+                input: "cc",
+                output_str: "eead6dbfc7340a56caedc044696a168870549a6a7f6f56961e84a54bd9970b8a"
+            },
+        ];
+
+        test_hash(&mut Keccak::new_keccak256(), &test_cases[..]);
+    }
+
+    #[test]
+    fn test_keccak_sponge_sha3_256() {
+        let test_cases = vec![
+            Test {
+                input: "",
+                output_str: "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+            },
+            Test {
+                input: "616263",
+                output_str: "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+            },
+        ];
+
+        test_hash(&mut Keccak::new_sha3_256(), &test_cases[..]);
+    }
+
+    #[test]
+    fn test_keccak_sponge_matches_sha3() {
+        // Lengths around the rate of each variant exercise the padding at block boundaries
+        let input: Vec<u8> = (0..400).map(|i| i as u8).collect();
+        let modes = [(Sha3Mode::Sha3_224, 0x06), (Sha3Mode::Sha3_384, 0x06),
+                     (Sha3Mode::Sha3_512, 0x06), (Sha3Mode::Keccak512, 0x01)];
+        for &(mode, suffix) in modes.iter() {
+            let mut sha3 = Sha3::new(mode);
+            let mut keccak = Keccak::new(B - sha3.block_size(), suffix, mode.digest_length());
+            for len in 0..input.len() {
+                let mut expected = vec![0u8; mode.digest_length()];
+                sha3.input(&input[..len]);
+                sha3.result(&mut expected);
+                sha3.reset();
+
+                let mut output = vec![0u8; mode.digest_length()];
+                keccak.input(&input[..len]);
+                keccak.result(&mut output);
+                keccak.reset();
+
+                assert!(output == expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunked_input() {
+        // Feeding the input in pieces of every size must give the same result as feeding it all
+        // at once, whichever rate the buffer has.
+        let input: Vec<u8> = (0..500).map(|i| (i * 13) as u8).collect();
+        let modes = [Sha3Mode::Sha3_224, Sha3Mode::Sha3_256, Sha3Mode::Sha3_384,
+                     Sha3Mode::Sha3_512, Sha3Mode::Shake128, Sha3Mode::Shake256,
+                     Sha3Mode::Keccak224, Sha3Mode::Keccak256, Sha3Mode::Keccak384,
+                     Sha3Mode::Keccak512];
+        for &mode in modes.iter() {
+            let mut sh = Sha3::new(mode);
+            let mut expected = [0u8; 64];
+            sh.input(&input);
+            sh.result(&mut expected);
+
+            for chunk_size in 1..sh.block_size() + 2 {
+                sh.reset();
+                for chunk in input.chunks(chunk_size) {
+                    sh.input(chunk);
+                }
+                let mut output = [0u8; 64];
+                sh.result(&mut output);
+                assert!(output[..] == expected[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rate_boundaries() {
+        // A message of rate - 1 bytes leaves a single byte for the padding, so the domain suffix,
+        // the first padding bit and the final padding bit all merge into it (0x86 for SHA-3, 0x9f
+        // for SHAKE). At rate bytes the padding takes a whole block of its own.
+        let input: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let sha3_256 = [
+            (135, "fded8fd9d6551c601eeb3b7c6bc5e5cfd8aad1d015b7e9aaa9c9b9475231d5e2"),
+            (136, "cf3ccff92480a29160c2d38317c430e14749bfee1788106957dfe73f8c4930e5"),
+            (137, "ce9d7dc90913ee5d92745019479a5352c6d6279bef18ed07dc0a83ee8084daca")];
+        let shake128 = [
+            (167, "1e552791cc4e93a0d4a8dc47ae49228c2faa869e40e628f6ace477aec3f1ca7a"),
+            (168, "f15277eb61c4908d44a2853f3cde071ae2ed7a23461fbe162a1a98cf6875059c"),
+            (169, "015be3338c986d9846affa0f94b4afc2a76bc289c709e1a596ec9eccf090a773")];
+
+        let mut sh = Sha3::sha3_256();
+        let mut keccak = Keccak::new_sha3_256();
+        assert_eq!(sh.block_size(), 136);
+        for &(len, expected) in sha3_256.iter() {
+            sh.input(&input[..len]);
+            assert_eq!(sh.result_str(), expected);
+            sh.reset();
+
+            keccak.input(&input[..len]);
+            assert_eq!(keccak.result_str(), expected);
+            keccak.reset();
+        }
+
+        let mut sh = Sha3::shake_128();
+        assert_eq!(sh.block_size(), 168);
+        for &(len, expected) in shake128.iter() {
+            let mut out = [0u8; 32];
+            sh.input(&input[..len]);
+            sh.result(&mut out);
+            assert_eq!(out.to_hex(), expected);
+            sh.reset();
+        }
+    }
+
+    #[test]
+    fn test_keccak_sponge_long_output() {
+        // SHAKE128 squeezes several blocks of output when asked for more than its 168 byte rate
+        let mut expected = [0u8; 500];
+        let mut shake = Sha3::shake_128();
+        shake.input(b"abc");
+        shake.result(&mut expected);
+
+        let mut keccak = Keccak::new(32, 0x1f, 500);
+        keccak.input(b"abc");
+        let mut output = [0u8; 500];
+        keccak.result(&mut output);
+        assert!(output[..] == expected[..]);
+
+        // Calling result() again must produce the same output
+        keccak.result(&mut output);
+        assert!(output[..] == expected[..]);
+    }
+
     #[test]
     fn test_keccak_384() {
         let test_cases = vec![
@@ -0,0 +1,264 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * An implementation of the SM3 cryptographic hash as specified by GM/T 0004-2012, the Chinese
+ * national hash standard used alongside SM2 and SM4. See also
+ * https://tools.ietf.org/html/draft-sca-cfrg-sm3-02.
+ *
+ * First create a `Sm3` object using the `Sm3` constructor, then feed it input using the `input`
+ * or `input_str` methods, which may be called any number of times.
+ *
+ * After the entire input has been fed to the hash read the result using the `result` or
+ * `result_str` methods.
+ *
+ * The `Sm3` object may be reused to create multiple hashes by calling the `reset` method.
+ */
+
+use cryptoutil::{write_u32_be, read_u32v_be, add_bytes_to_bits, FixedBuffer, FixedBuffer64,
+    StandardPadding};
+use digest::Digest;
+
+const IV: [u32; 8] = [
+    0x7380166f, 0x4914b2b9, 0x172442d7, 0xda8a0600,
+    0xa96f30bc, 0x163138aa, 0xe38dee4d, 0xb0fb0e4e
+];
+
+// The round constants, T_j for rounds 0 to 15 and 16 to 63
+const T_LOW: u32 = 0x79cc4519;
+const T_HIGH: u32 = 0x7a879d8a;
+
+/// Structure representing the state of an SM3 computation
+#[derive(Clone, Copy)]
+pub struct Sm3 {
+    h: [u32; 8],
+    length_bits: u64,
+    buffer: FixedBuffer64,
+    computed: bool,
+}
+
+fn p0(x: u32) -> u32 {
+    x ^ x.rotate_left(9) ^ x.rotate_left(17)
+}
+
+fn p1(x: u32) -> u32 {
+    x ^ x.rotate_left(15) ^ x.rotate_left(23)
+}
+
+// The boolean functions FF_j and GG_j. For the first 16 rounds both are XOR; afterwards FF is the
+// majority function and GG is the choice function.
+fn ff(x: u32, y: u32, z: u32, j: usize) -> u32 {
+    if j < 16 { x ^ y ^ z } else { (x & y) | (x & z) | (y & z) }
+}
+
+fn gg(x: u32, y: u32, z: u32, j: usize) -> u32 {
+    if j < 16 { x ^ y ^ z } else { (x & y) | (!x & z) }
+}
+
+fn process_msg_block(data: &[u8], h: &mut [u32; 8]) {
+    // Message expansion: W[0..68] from the block, and W'[j] = W[j] ^ W[j + 4]
+    let mut w = [0u32; 68];
+    read_u32v_be(&mut w[0..16], data);
+    for j in 16..68 {
+        w[j] = p1(w[j - 16] ^ w[j - 9] ^ w[j - 3].rotate_left(15)) ^ w[j - 13].rotate_left(7) ^
+            w[j - 6];
+    }
+    let mut w1 = [0u32; 64];
+    for j in 0..64 {
+        w1[j] = w[j] ^ w[j + 4];
+    }
+
+    let mut a = h[0];
+    let mut b = h[1];
+    let mut c = h[2];
+    let mut d = h[3];
+    let mut e = h[4];
+    let mut f = h[5];
+    let mut g = h[6];
+    let mut hh = h[7];
+
+    for j in 0..64 {
+        let t = if j < 16 { T_LOW } else { T_HIGH };
+        let a12 = a.rotate_left(12);
+        let ss1 = a12.wrapping_add(e).wrapping_add(t.rotate_left((j % 32) as u32)).rotate_left(7);
+        let ss2 = ss1 ^ a12;
+        let tt1 = ff(a, b, c, j).wrapping_add(d).wrapping_add(ss2).wrapping_add(w1[j]);
+        let tt2 = gg(e, f, g, j).wrapping_add(hh).wrapping_add(ss1).wrapping_add(w[j]);
+        d = c;
+        c = b.rotate_left(9);
+        b = a;
+        a = tt1;
+        hh = g;
+        g = f.rotate_left(19);
+        f = e;
+        e = p0(tt2);
+    }
+
+    h[0] ^= a;
+    h[1] ^= b;
+    h[2] ^= c;
+    h[3] ^= d;
+    h[4] ^= e;
+    h[5] ^= f;
+    h[6] ^= g;
+    h[7] ^= hh;
+}
+
+impl Sm3 {
+    /// Construct an `Sm3` object
+    pub fn new() -> Sm3 {
+        Sm3 {
+            h: IV,
+            length_bits: 0,
+            buffer: FixedBuffer64::new(),
+            computed: false,
+        }
+    }
+}
+
+impl Default for Sm3 {
+    fn default() -> Sm3 {
+        Sm3::new()
+    }
+}
+
+impl Digest for Sm3 {
+    fn reset(&mut self) {
+        self.h = IV;
+        self.length_bits = 0;
+        self.buffer.reset();
+        self.computed = false;
+    }
+
+    fn input(&mut self, msg: &[u8]) {
+        assert!(!self.computed);
+        // Assumes that msg.len() can be converted to u64 without overflow
+        self.length_bits = add_bytes_to_bits(self.length_bits, msg.len() as u64);
+        let st_h = &mut self.h;
+        self.buffer.input(msg, |d: &[u8]| { process_msg_block(d, st_h); });
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        if !self.computed {
+            let st_h = &mut self.h;
+            self.buffer.standard_padding(8, |d: &[u8]| { process_msg_block(d, st_h) });
+
+            // The message length in bits is appended big endian
+            write_u32_be(self.buffer.next(4), (self.length_bits >> 32) as u32);
+            write_u32_be(self.buffer.next(4), self.length_bits as u32);
+            process_msg_block(self.buffer.full_buffer(), st_h);
+
+            self.computed = true;
+        }
+
+        for (chunk, &v) in out[..32].chunks_mut(4).zip(self.h.iter()) {
+            write_u32_be(chunk, v);
+        }
+    }
+
+    fn output_bits(&self) -> usize { 256 }
+
+    fn block_size(&self) -> usize { 64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use cryptoutil::test::test_digest_1million_random;
+    use digest::Digest;
+    use sm3::Sm3;
+
+    struct Test {
+        input: &'static str,
+        output_str: &'static str,
+    }
+
+    fn tests() -> Vec<Test> {
+        vec![
+            // The examples from appendix A of GM/T 0004-2012
+            Test {
+                input: "abc",
+                output_str: "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0",
+            },
+            Test {
+                input: "abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd",
+                output_str: "debe9ff92275b8a138604889c18e5a4d6fdb70e5387e5765293dcba39c0c5732",
+            },
+            Test {
+                input: "",
+                output_str: "1ab21d8355cfa17f8e61194831e81a8f22bec8c728fefb747ed035eb5082aa2b",
+            },
+        ]
+    }
+
+    #[test]
+    fn test() {
+        let mut sh = Sm3::new();
+        for t in tests().iter() {
+            sh.input_str(t.input);
+            assert_eq!(sh.result_str(), t.output_str);
+            sh.reset();
+        }
+
+        // Test that it works when accepting the message in pieces
+        for t in tests().iter() {
+            let len = t.input.len();
+            let mut left = len;
+            while left > 0 {
+                let take = left.div_ceil(2);
+                sh.input_str(&t.input[len - left..take + len - left]);
+                left -= take;
+            }
+            assert_eq!(sh.result_str(), t.output_str);
+            sh.reset();
+        }
+    }
+
+    #[test]
+    fn test_1million_random_sm3() {
+        let mut sh = Sm3::new();
+        test_digest_1million_random(
+            &mut sh,
+            64,
+            "c8aaf89429554029e231941a2acc0ad61ff2a5acd8fadd25847a3a732b3b02c3");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use digest::Digest;
+    use sm3::Sm3;
+
+    #[bench]
+    pub fn sm3_10(bh: & mut Bencher) {
+        let mut sh = Sm3::new();
+        let bytes = [1u8; 10];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn sm3_1k(bh: & mut Bencher) {
+        let mut sh = Sm3::new();
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn sm3_64k(bh: & mut Bencher) {
+        let mut sh = Sm3::new();
+        let bytes = [1u8; 65536];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
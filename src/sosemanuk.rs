@@ -7,7 +7,7 @@
 
 use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
 use symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
-use cryptoutil::{read_u32_le, symm_enc_or_dec, write_u32v_le};
+use cryptoutil::{debug_assert_no_overlap, read_u32_le, symm_enc_or_dec, write_u32v_le};
 
 use cryptoutil::copy_memory;
 
@@ -2306,6 +2306,7 @@ fn iv_setup(iv : &[u8], subkeys : &mut[u32; 100], lfsr : &mut[u32; 10], fsm_r :
 impl SynchronousStreamCipher for Sosemanuk {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
+        debug_assert_no_overlap(input, output);
         for (x, y) in input.iter().zip(output.iter_mut()) {
             *y = *x ^ self.next();
         }
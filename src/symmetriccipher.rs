@@ -4,7 +4,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
+use buffer::{BufferResult, RefReadBuffer, RefWriteBuffer, WriteBuffer};
 use cryptoutil::symm_enc_or_dec;
 
 pub trait BlockEncryptor {
@@ -17,6 +17,11 @@ pub trait BlockEncryptorX8 {
     fn encrypt_block_x8(&self, input: &[u8], output: &mut [u8]);
 }
 
+pub trait BlockEncryptorX16 {
+    fn block_size(&self) -> usize;
+    fn encrypt_block_x16(&self, input: &[u8], output: &mut [u8]);
+}
+
 pub trait BlockDecryptor {
     fn block_size(&self) -> usize;
     fn decrypt_block(&self, input: &[u8], output: &mut [u8]);
@@ -29,22 +34,98 @@ pub trait BlockDecryptorX8 {
 
 #[derive(Debug, Clone, Copy)]
 pub enum SymmetricCipherError {
+    /// The input ended with a partial block, for example because a block mode ciphertext was
+    /// truncated, and the padding mode couldn't complete it. Output that was already written for
+    /// the complete blocks before it should be discarded. The fallible constructors in the aes
+    /// module also return it for a key or iv of the wrong length, and encrypt_all() and
+    /// decrypt_all() for an output buffer that is too small.
     InvalidLength,
-    InvalidPadding
+    /// The padding of the decrypted data was malformed. Callers must treat this exactly like a
+    /// failed MAC check and must not reveal which of the two occurred.
+    InvalidPadding,
+    /// The authentication tag of an AEAD ciphertext did not match, so it has been tampered with
+    /// or was encrypted with a different key, nonce, or associated data. No plaintext is released.
+    AuthenticationFailed
 }
 
 pub trait Encryptor {
     fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
         -> Result<BufferResult, SymmetricCipherError>;
+
+    /// Encrypt all of input as the final call, and return the number of bytes written to output.
+    /// With a padding mode this is not input.len(), so truncate output to it rather than guessing.
+    /// input.len() plus one block is always enough room for the padded ciphertext. If output is
+    /// too small, InvalidLength is returned and the encryptor can't be used any further.
+    fn encrypt_all(&mut self, input: &[u8], output: &mut [u8])
+            -> Result<usize, SymmetricCipherError> {
+        let mut read_buffer = RefReadBuffer::new(input);
+        let mut write_buffer = RefWriteBuffer::new(output);
+        match self.encrypt(&mut read_buffer, &mut write_buffer, true)? {
+            BufferResult::BufferUnderflow => Ok(write_buffer.position()),
+            BufferResult::BufferOverflow => Err(SymmetricCipherError::InvalidLength)
+        }
+    }
 }
 
 pub trait Decryptor {
     fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
         -> Result<BufferResult, SymmetricCipherError>;
+
+    /// Decrypt all of input as the final call, and return the number of bytes written to output.
+    /// With a padding mode this is less than input.len() once the padding has been removed, so
+    /// truncate output to it. An output as long as input is always enough. If output is too small,
+    /// InvalidLength is returned and the decryptor can't be used any further.
+    fn decrypt_all(&mut self, input: &[u8], output: &mut [u8])
+            -> Result<usize, SymmetricCipherError> {
+        let mut read_buffer = RefReadBuffer::new(input);
+        let mut write_buffer = RefWriteBuffer::new(output);
+        match self.decrypt(&mut read_buffer, &mut write_buffer, true)? {
+            BufferResult::BufferUnderflow => Ok(write_buffer.position()),
+            BufferResult::BufferOverflow => Err(SymmetricCipherError::InvalidLength)
+        }
+    }
+}
+
+impl Encryptor for Box<dyn Encryptor + 'static> {
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        let me = &mut **self;
+        me.encrypt(input, output, eof)
+    }
+}
+
+impl Decryptor for Box<dyn Decryptor + 'static> {
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, eof: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        let me = &mut **self;
+        me.decrypt(input, output, eof)
+    }
 }
 
 pub trait SynchronousStreamCipher {
     fn process(&mut self, input: &[u8], output: &mut [u8]);
+
+    /// Write the raw keystream to out, advancing the cipher by out.len() bytes. This is the same
+    /// as calling process() with an all-zero input of the same length.
+    fn keystream(&mut self, out: &mut [u8]) {
+        let zeros = [0u8; 64];
+        for chunk in out.chunks_mut(zeros.len()) {
+            let len = chunk.len();
+            self.process(&zeros[..len], chunk);
+        }
+    }
+
+    /// Encrypt or decrypt data in place by XORing it with the keystream.
+    fn process_in_place(&mut self, data: &mut [u8]) {
+        let mut keystream = [0u8; 64];
+        for chunk in data.chunks_mut(keystream.len()) {
+            let keystream = &mut keystream[..chunk.len()];
+            self.keystream(keystream);
+            for (d, &k) in chunk.iter_mut().zip(keystream.iter()) {
+                *d ^= k;
+            }
+        }
+    }
 }
 
 // TODO - Its a bit unclear to me why this is necessary
@@ -53,6 +134,16 @@ impl SynchronousStreamCipher for Box<SynchronousStreamCipher + 'static> {
         let me = &mut **self;
         me.process(input, output);
     }
+
+    fn keystream(&mut self, out: &mut [u8]) {
+        let me = &mut **self;
+        me.keystream(out);
+    }
+
+    fn process_in_place(&mut self, data: &mut [u8]) {
+        let me = &mut **self;
+        me.process_in_place(data);
+    }
 }
 
 impl Encryptor for Box<SynchronousStreamCipher + 'static> {
@@ -0,0 +1,68 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module implements the TLS 1.2 pseudorandom function as specified by
+//! section 5 of https://tools.ietf.org/html/rfc5246.
+
+use digest::Digest;
+use hmac::p_hash;
+
+/// Execute the TLS 1.2 PRF, filling out with P_hash(secret, label || seed).
+/// TLS 1.2 cipher suites use SHA-256 unless they specify another digest.
+///
+/// # Arguments
+/// * digest - The digest function to use.
+/// * secret - The secret, such as the pre-master or master secret.
+/// * label - The ASCII label, such as "master secret" or "key expansion".
+/// * seed - The seed, usually the client and server randoms.
+/// * out - The output buffer to fill with the derived value.
+pub fn prf_tls12<D: Digest + Clone>(digest: D, secret: &[u8], label: &[u8], seed: &[u8],
+        out: &mut [u8]) {
+    let mut label_seed = Vec::with_capacity(label.len() + seed.len());
+    label_seed.extend_from_slice(label);
+    label_seed.extend_from_slice(seed);
+    p_hash(digest, secret, &label_seed, out);
+}
+
+#[cfg(test)]
+mod test {
+    use serialize::hex::FromHex;
+
+    use sha2::Sha256;
+    use tls_prf::prf_tls12;
+
+    #[test]
+    fn test_prf_tls12_sha256() {
+        let secret = "9bbe436ba940f017b17652849a71db35".from_hex().unwrap();
+        let seed = "a0ba9f936cda311827a6f796ffd5198c".from_hex().unwrap();
+        let expected = "e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a\
+                        6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab\
+                        4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff701\
+                        87347b66".from_hex().unwrap();
+
+        let mut out = [0u8; 100];
+        prf_tls12(Sha256::new(), &secret, b"test label", &seed, &mut out);
+        assert!(out[..] == expected[..]);
+    }
+
+    #[test]
+    fn test_key_expansion_sha256() {
+        // The key block for a 48 byte master secret, expanded to enough material for
+        // AES-256-CBC with HMAC-SHA1: two 20 byte MAC keys, two 32 byte keys. The expected value
+        // was computed with an independent implementation of P_SHA256.
+        let master_secret: Vec<u8> = (0..48).collect();
+        let mut seed: Vec<u8> = (0x20..0x40).collect(); // server_random
+        seed.extend(0x40..0x60u8); // client_random
+        let expected = "b50b7476326a8bec4d44bb7a7b8fa5c74493913db2382d3c754a6aafb7742df0\
+                        5727a2760956889bb394a9ab8f33398ab25e413e4ea80b2d0e69bfd343f2647e\
+                        2b3ad42a375f4dfba67637817d36f85fd3ff6336724dbf54e8a72b2ffa7644b2\
+                        cf1acaa9848a2844".from_hex().unwrap();
+
+        let mut key_block = [0u8; 104];
+        prf_tls12(Sha256::new(), &master_secret, b"key expansion", &seed, &mut key_block);
+        assert!(key_block[..] == expected[..]);
+    }
+}
@@ -4,11 +4,19 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::io;
+
 use libc;
+use rand::{OsRng, Rng};
+
+pub use cryptoutil::{ct_swap, from_hex_ct, gf128_double, gf128_mul, read_u128_be, read_u128_le,
+    to_hex_ct, write_u128_be, write_u128_le, WriteExt};
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 extern {
     pub fn rust_crypto_util_supports_aesni() -> u32;
+    pub fn rust_crypto_util_supports_avx2() -> u32;
+    pub fn rust_crypto_util_supports_sha_ni() -> u32;
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
@@ -18,6 +26,20 @@ pub fn supports_aesni() -> bool {
     }
 }
 
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn supports_avx2() -> bool {
+    unsafe {
+        rust_crypto_util_supports_avx2() != 0
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn supports_sha_ni() -> bool {
+    unsafe {
+        rust_crypto_util_supports_sha_ni() != 0
+    }
+}
+
 extern {
     pub fn rust_crypto_util_fixed_time_eq_asm(
             lhsp: *const u8,
@@ -54,9 +76,26 @@ pub fn fixed_time_eq(lhs: &[u8], rhs: &[u8]) -> bool {
     }
 }
 
+/// Fill out with bytes from the operating system's cryptographically secure random number
+/// generator, for example to create a salt or a key. An error is returned if the generator is not
+/// available.
+pub fn random_bytes(out: &mut [u8]) -> io::Result<()> {
+    let mut rng = OsRng::new()?;
+    rng.fill_bytes(out);
+    Ok(())
+}
+
+/// Generate an N byte salt for PBKDF2, bcrypt or scrypt using random_bytes(). 16 bytes is a good
+/// default.
+pub fn random_salt<const N: usize>() -> io::Result<[u8; N]> {
+    let mut salt = [0u8; N];
+    random_bytes(&mut salt)?;
+    Ok(salt)
+}
+
 #[cfg(test)]
 mod test {
-    use util::fixed_time_eq;
+    use util::{fixed_time_eq, random_bytes, random_salt};
 
     #[test]
     pub fn test_fixed_time_eq() {
@@ -77,4 +116,27 @@ mod test {
         assert!(!fixed_time_eq(&a, &f));
         assert!(!fixed_time_eq(&a, &g));
     }
+
+    #[test]
+    fn test_random_bytes() {
+        // Every 16 byte chunk of two independently filled buffers must differ, which shows that
+        // the whole buffer was written. A false failure has probability 2^-128 per chunk.
+        let mut a = [0u8; 128];
+        let mut b = [0u8; 128];
+        random_bytes(&mut a).unwrap();
+        random_bytes(&mut b).unwrap();
+        for (x, y) in a.chunks(16).zip(b.chunks(16)) {
+            assert!(x != y);
+        }
+
+        random_bytes(&mut []).unwrap();
+    }
+
+    #[test]
+    fn test_random_salt() {
+        let a: [u8; 16] = random_salt().unwrap();
+        let b: [u8; 16] = random_salt().unwrap();
+        assert!(a != b);
+        assert_eq!(random_salt::<32>().unwrap().len(), 32);
+    }
 }
@@ -13,6 +13,20 @@ rust_crypto_util_supports_aesni PROC public
   ret
 rust_crypto_util_supports_aesni ENDP
 
+rust_crypto_util_supports_avx2 PROC public
+  ; Return false since the AVX2 detection has only
+  ; been written for GCC and Clang
+  xor eax, eax
+  ret
+rust_crypto_util_supports_avx2 ENDP
+
+rust_crypto_util_supports_sha_ni PROC public
+  ; Return false since the SHA extension code paths are only
+  ; enabled when building with GCC or Clang
+  xor eax, eax
+  ret
+rust_crypto_util_supports_sha_ni ENDP
+
 ; The rust_crypto_util_fixed_time_eq_asm for X86-64
 ifdef X64
 rust_crypto_util_fixed_time_eq_asm PROC public lhs:QWORD, rhs:QWORD, count:QWORD
@@ -44,6 +44,158 @@ uint32_t rust_crypto_util_supports_aesni() {
 }
 #endif
 
+#if defined(__i386__) || defined(__x86_64__)
+// AVX2 is only usable if the CPU supports it and the OS saves the YMM registers on a context switch,
+// which is indicated by the OSXSAVE bit and the XMM and YMM state bits of XCR0.
+uint32_t rust_crypto_util_supports_avx2() {
+    #ifdef __SSE__
+    uint32_t max_leaf, flags, xcr0, ext_flags;
+    #if defined(__i386__)
+    asm(
+        "pushl %%ebx; \
+         xor %%eax, %%eax; cpuid; \
+         popl %%ebx;"
+        : "=a" (max_leaf) // output
+        : // input
+        : "ecx", "edx" // clobbers
+    );
+    #else
+    asm(
+        "xor %%eax, %%eax; cpuid;"
+        : "=a" (max_leaf) // output
+        : // input
+        : "ebx", "ecx", "edx" // clobbers
+    );
+    #endif
+    if (max_leaf < 7) {
+        return 0;
+    }
+    #if defined(__i386__)
+    asm(
+        "pushl %%ebx; \
+         mov $1, %%eax; cpuid; \
+         popl %%ebx;"
+        : "=c" (flags) // output
+        : // input
+        : "eax", "edx" // clobbers
+    );
+    #else
+    asm(
+        "mov $1, %%eax; cpuid;"
+        : "=c" (flags) // output
+        : // input
+        : "eax", "ebx", "edx" // clobbers
+    );
+    #endif
+    // OSXSAVE and AVX
+    if ((flags & 0x18000000) != 0x18000000) {
+        return 0;
+    }
+    asm(
+        "xgetbv;"
+        : "=a" (xcr0) // output
+        : "c" (0) // input
+        : "edx" // clobbers
+    );
+    if ((xcr0 & 0x06) != 0x06) {
+        return 0;
+    }
+    #if defined(__i386__)
+    asm(
+        "pushl %%ebx; \
+         mov $7, %%eax; xor %%ecx, %%ecx; cpuid; \
+         mov %%ebx, %%esi; \
+         popl %%ebx;"
+        : "=S" (ext_flags) // output
+        : // input
+        : "eax", "ecx", "edx" // clobbers
+    );
+    #else
+    asm(
+        "mov $7, %%eax; xor %%ecx, %%ecx; cpuid;"
+        : "=b" (ext_flags) // output
+        : // input
+        : "eax", "ecx", "edx" // clobbers
+    );
+    #endif
+    return ext_flags & 0x00000020;
+    #else
+    return 0;
+    #endif
+}
+#endif
+
+#if defined(__i386__) || defined(__x86_64__)
+// The SHA extensions code path also uses SSSE3 and SSE4.1 instructions, so all three are required.
+uint32_t rust_crypto_util_supports_sha_ni() {
+    #ifdef __SSE__
+    uint32_t max_leaf, flags, ext_flags;
+    #if defined(__i386__)
+    asm(
+        "pushl %%ebx; \
+         xor %%eax, %%eax; cpuid; \
+         popl %%ebx;"
+        : "=a" (max_leaf) // output
+        : // input
+        : "ecx", "edx" // clobbers
+    );
+    #else
+    asm(
+        "xor %%eax, %%eax; cpuid;"
+        : "=a" (max_leaf) // output
+        : // input
+        : "ebx", "ecx", "edx" // clobbers
+    );
+    #endif
+    if (max_leaf < 7) {
+        return 0;
+    }
+    #if defined(__i386__)
+    asm(
+        "pushl %%ebx; \
+         mov $1, %%eax; cpuid; \
+         popl %%ebx;"
+        : "=c" (flags) // output
+        : // input
+        : "eax", "edx" // clobbers
+    );
+    #else
+    asm(
+        "mov $1, %%eax; cpuid;"
+        : "=c" (flags) // output
+        : // input
+        : "eax", "ebx", "edx" // clobbers
+    );
+    #endif
+    // SSSE3 and SSE4.1
+    if ((flags & 0x00080200) != 0x00080200) {
+        return 0;
+    }
+    #if defined(__i386__)
+    asm(
+        "pushl %%ebx; \
+         mov $7, %%eax; xor %%ecx, %%ecx; cpuid; \
+         mov %%ebx, %%esi; \
+         popl %%ebx;"
+        : "=S" (ext_flags) // output
+        : // input
+        : "eax", "ecx", "edx" // clobbers
+    );
+    #else
+    asm(
+        "mov $7, %%eax; xor %%ecx, %%ecx; cpuid;"
+        : "=b" (ext_flags) // output
+        : // input
+        : "eax", "ecx", "edx" // clobbers
+    );
+    #endif
+    return ext_flags & 0x20000000;
+    #else
+    return 0;
+    #endif
+}
+#endif
+
 #if defined(__i386__) || defined(__x86_64__)
 uint32_t rust_crypto_util_fixed_time_eq_asm(uint8_t* lhsp, uint8_t* rhsp, size_t count) {
     if (count == 0) {
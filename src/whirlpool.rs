@@ -41,6 +41,8 @@ use digest::Digest;
 
 #[derive(Clone, Copy)]
 pub struct Whirlpool {
+    // The full 256 bit message length in bits, stored big endian as it is encoded in the final
+    // block
     bit_length: [u8; 32],
     buffer: FixedBuffer64,
     hash: [u64; 8],
@@ -145,10 +147,17 @@ impl Digest for Whirlpool {
     }
 
     fn block_size(&self) -> usize {
-        512
+        64
     }
 }
 
+/// Process a single 64 byte block with the Whirlpool compression function, updating the chaining
+/// state in place. No padding or length encoding is applied - callers building higher-level
+/// constructions on top of this are responsible for that. A fresh Whirlpool state is all zeros.
+pub fn whirlpool_compress(state: &mut [u64; 8], block: &[u8; 64]) {
+    process_buffer(state, block);
+}
+
 fn process_buffer(hash: &mut[u64; 8], buffer: &[u8]) {
     let mut k: [u64; 8] = unsafe { uninitialized() };
     let mut block: [u64; 8] = unsafe { uninitialized() };
@@ -207,6 +216,7 @@ fn process_buffer(hash: &mut[u64; 8], buffer: &[u8]) {
 mod test {
     use super::*;
     use digest::Digest;
+    use cryptoutil::write_u64_be;
     use std::ascii::AsciiExt;
 
     static TESTS: [(&'static str, &'static str); 18] = [
@@ -243,6 +253,115 @@ mod test {
         }
     }
 
+    #[test]
+    fn whirlpool_abc_test() {
+        // ISO/IEC 10118-3 test vector
+        let mut d = Whirlpool::new();
+        d.input_str("abc");
+        assert_eq!(d.result_str(), concat!(
+            "4e2448a4c6f486bb16b6562c73b4020bf3043e3a731bce721ae1b303d97e6d4c",
+            "7181eebdb6c57e277d0e34957114cbd6c797fc9d95d8b582d225292076d4eef5"));
+    }
+
+    #[test]
+    fn whirlpool_reset_test() {
+        let mut d = Whirlpool::new();
+        d.input_str("The quick brown fox jumps over the lazy dog");
+        d.result_str();
+        d.reset();
+
+        d.input_str("abc");
+        let mut fresh = Whirlpool::new();
+        fresh.input_str("abc");
+        assert_eq!(d.result_str(), fresh.result_str());
+    }
+
+    #[test]
+    fn whirlpool_incremental_test() {
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+
+        let mut one_shot = Whirlpool::new();
+        one_shot.input(&data);
+        let expected = one_shot.result_str();
+
+        for &chunk_size in [1usize, 3, 63, 64, 65, 200].iter() {
+            let mut d = Whirlpool::new();
+            for chunk in data.chunks(chunk_size) {
+                d.input(chunk);
+            }
+            assert_eq!(d.result_str(), expected);
+        }
+    }
+
+    #[test]
+    fn whirlpool_padding_chunks_test() {
+        // Messages of 32 bytes or more leave no room for the 256-bit length after the padding
+        // byte, so the padding spills into an extra block
+        for tuple in TESTS.iter().filter(|t| t.0.len() >= 32) {
+            let (s, hash) = *tuple;
+            for &chunk_size in [1usize, 7, 31, 32, 33].iter() {
+                let mut d = Whirlpool::new();
+                for chunk in s.as_bytes().chunks(chunk_size) {
+                    d.input(chunk);
+                }
+                assert_eq!(d.result_str(), hash.to_ascii_lowercase());
+            }
+        }
+    }
+
+    #[test]
+    fn whirlpool_length_carry_test() {
+        // A length of 2^72 - 8 bits, one byte short of overflowing the 72 bits a single input
+        // call can add, must carry into the upper part of the 256-bit counter
+        let mut d = Whirlpool::new();
+        for b in d.bit_length[23..].iter_mut() {
+            *b = 0xff;
+        }
+        d.bit_length[31] = 0xf8;
+        d.input(&[0]);
+
+        let mut expected = [0u8; 32];
+        expected[22] = 1;
+        assert!(d.bit_length == expected);
+
+        // The counter is written big endian into the last 32 bytes of the final block
+        let mut state = d.hash;
+        let mut block = [0u8; 64];
+        block[1] = 0x80;
+        block[32..].copy_from_slice(&expected);
+        whirlpool_compress(&mut state, &block);
+
+        let mut out = [0u8; 64];
+        d.result(&mut out);
+        let mut expected_out = [0u8; 64];
+        for (chunk, &word) in expected_out.chunks_mut(8).zip(state.iter()) {
+            write_u64_be(chunk, word);
+        }
+        assert!(out[..] == expected_out[..]);
+    }
+
+    #[test]
+    fn whirlpool_compress_test() {
+        // "abc" fits into a single block along with its padding and 256-bit length
+        let mut block = [0u8; 64];
+        block[..3].copy_from_slice(b"abc");
+        block[3] = 0x80;
+        block[63] = 24;
+
+        let mut state = [0u64; 8];
+        whirlpool_compress(&mut state, &block);
+
+        let mut d = Whirlpool::new();
+        d.input_str("abc");
+        let mut expected = [0u8; 64];
+        d.result(&mut expected);
+        let mut out = [0u8; 64];
+        for (chunk, &word) in out.chunks_mut(8).zip(state.iter()) {
+            write_u64_be(chunk, word);
+        }
+        assert!(&out[..] == &expected[..]);
+    }
+
     #[test]
     fn whirlpool_1000000a_test() {
         let mut d = Whirlpool::new();